@@ -1,12 +1,13 @@
 //! The different actions that can be done.
 
-use crate::key_code::KeyCode;
+use crate::key_code::{KeyCode, KeymapLocale, UsagePage};
 use crate::layout::{StackedIter, WaitingAction};
 use core::fmt::Debug;
 
 /// Behavior configuration of HoldTap.
 #[non_exhaustive]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HoldTapConfig {
     /// Only the timeout will determine between hold and tap action.
     ///
@@ -26,6 +27,29 @@ pub enum HoldTapConfig {
     /// events than on timing. Be aware that doing the good succession
     /// of key might require some training.
     PermissiveHold,
+    /// If there is a key press on the opposite hand, the hold action
+    /// is activated.
+    ///
+    /// A key press on the same hand as the HoldTap key is likely a
+    /// roll while typing: in this case, the hold action will only be
+    /// activated by the timeout. A key press on the opposite hand is
+    /// likely an intentional shortcut, activating the hold action
+    /// immediately. This is the behavior of "Achordion" in QMK.
+    ///
+    /// The hand of each key is given by
+    /// [`Layout::set_hands`](crate::layout::Layout::set_hands). If
+    /// the hand of a key is unknown, this behavior is equivalent to
+    /// `Default`.
+    HoldOnOppositeHandPress,
+    /// The `Default` behavior, but if the key is held past the
+    /// timeout without any other key press, the tap action is
+    /// performed on release ("retro tap").
+    ///
+    /// The hold action is still activated on timeout, the tap action
+    /// being tapped after its release. Useful for the home row
+    /// modifiers: a modifier held alone, by hesitation, still types
+    /// its letter.
+    RetroTap,
     /// A custom configuration. Allows the behavior to be controlled by a caller
     /// supplied handler function.
     ///
@@ -89,6 +113,7 @@ pub enum HoldTapConfig {
     ///     tap_hold_interval: 0,
     /// });
     /// ```
+    #[cfg_attr(feature = "serde", serde(skip))]
     Custom(fn(StackedIter) -> Option<WaitingAction>),
 }
 
@@ -98,6 +123,8 @@ impl Debug for HoldTapConfig {
             HoldTapConfig::Default => f.write_str("Default"),
             HoldTapConfig::HoldOnOtherKeyPress => f.write_str("HoldOnOtherKeyPress"),
             HoldTapConfig::PermissiveHold => f.write_str("PermissiveHold"),
+            HoldTapConfig::HoldOnOppositeHandPress => f.write_str("HoldOnOppositeHandPress"),
+            HoldTapConfig::RetroTap => f.write_str("RetroTap"),
             HoldTapConfig::Custom(func) => f
                 .debug_tuple("Custom")
                 .field(&(*func as fn(StackedIter<'static>) -> Option<WaitingAction>) as &dyn Debug)
@@ -111,9 +138,14 @@ impl PartialEq for HoldTapConfig {
         match (self, other) {
             (HoldTapConfig::Default, HoldTapConfig::Default)
             | (HoldTapConfig::HoldOnOtherKeyPress, HoldTapConfig::HoldOnOtherKeyPress)
-            | (HoldTapConfig::PermissiveHold, HoldTapConfig::PermissiveHold) => true,
+            | (HoldTapConfig::PermissiveHold, HoldTapConfig::PermissiveHold)
+            | (HoldTapConfig::HoldOnOppositeHandPress, HoldTapConfig::HoldOnOppositeHandPress)
+            | (HoldTapConfig::RetroTap, HoldTapConfig::RetroTap) => true,
             (HoldTapConfig::Custom(self_func), HoldTapConfig::Custom(other_func)) => {
-                *self_func as fn(StackedIter<'static>) -> Option<WaitingAction> == *other_func
+                core::ptr::fn_addr_eq(
+                    *self_func as fn(StackedIter<'static>) -> Option<WaitingAction>,
+                    *other_func as fn(StackedIter<'static>) -> Option<WaitingAction>,
+                )
             }
             _ => false,
         }
@@ -129,20 +161,34 @@ impl Eq for HoldTapConfig {}
 /// `tap` action.  Mostly used with a modifier for the hold action
 /// and a normal key on the tap action. Any action can be
 /// performed, but using a `HoldTap` in a `HoldTap` is not
-/// specified (but guaranteed to not crash).
+/// specified (but guaranteed to not crash). For example, a layer on
+/// hold and `Escape` on tap:
+///
+/// ```
+/// use keyberon::action::{k, l, Action, HoldTapAction};
+/// use keyberon::key_code::KeyCode::*;
+/// const L2_ESC: Action = Action::HoldTap(&HoldTapAction::new(l(2), k(Escape)));
+/// ```
+///
+/// [`LayerTapAction`] is a layer on hold with the lock of the layer
+/// on double tap and hold.
 ///
 /// Different behaviors can be configured using the config field,
 /// but whatever the configuration is, if the key is pressed more
 /// than `timeout`, the hold action is activated (if no other
 /// action was determined before).
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde-leak", derive(serde::Deserialize))]
 pub struct HoldTapAction<T, K>
 where
     T: 'static,
     K: 'static,
 {
     /// The duration, in ticks (usually milliseconds) giving the
-    /// difference between a hold and a tap.
+    /// difference between a hold and a tap. If [`TAPPING_TERM`], the
+    /// tapping term of the layout is used (see
+    /// [`Layout::set_tapping_term`](crate::layout::Layout::set_tapping_term)).
     pub timeout: u16,
     /// The hold action.
     pub hold: Action<T, K>,
@@ -158,35 +204,631 @@ where
     /// be held. This allows the tap action to be held by
     /// pressing, releasing and holding the key, allowing the computer
     /// to auto repeat the tap behavior. The timeout starts on the
-    /// first press of the key, NOT on the release.
+    /// first press of the key, NOT on the release. This is also
+    /// known as "quick tap", and is the way to get an auto repeated
+    /// letter from a layer tap key: use a hold tap action with a
+    /// [`Action::Layer`] hold action.
     ///
     /// Pressing a different key in between will not result in the
     /// behaviour described above; the HoldTap key must be pressed twice
     /// in a row.
     ///
-    /// To deactivate the functionality, set this to 0.
+    /// To deactivate the functionality, set this to 0: a hold after
+    /// a tap then always resolves as a hold (known as "tapping force
+    /// hold"). As this is a field of the action, it is configured per
+    /// key.
     pub tap_hold_interval: u16,
 }
 
+/// The default `timeout` of [`HoldTapAction::new`], in ticks.
+pub const DEFAULT_HOLD_TAP_TIMEOUT: u16 = 200;
+
+/// The `timeout` of the hold tap and layer tap actions using the
+/// tapping term of the layout, that can be changed at runtime (see
+/// [`Layout::set_tapping_term`](crate::layout::Layout::set_tapping_term)).
+pub const TAPPING_TERM: u16 = u16::MAX;
+
+impl<T, K> HoldTapAction<T, K> {
+    /// Creates a hold tap action with the default configuration, and
+    /// a timeout of [`DEFAULT_HOLD_TAP_TIMEOUT`].
+    ///
+    /// As each action carries its own timeout, it can be adjusted
+    /// per key with [`with_timeout`](Self::with_timeout), for example
+    /// longer for the home row modifiers than for the thumb keys:
+    ///
+    /// ```
+    /// use keyberon::action::{k, Action, HoldTapAction};
+    /// use keyberon::key_code::KeyCode::*;
+    /// const A_GUI: Action = Action::HoldTap(&HoldTapAction::new(k(LGui), k(A)).with_timeout(250));
+    /// ```
+    pub const fn new(hold: Action<T, K>, tap: Action<T, K>) -> Self {
+        Self {
+            timeout: DEFAULT_HOLD_TAP_TIMEOUT,
+            hold,
+            tap,
+            config: HoldTapConfig::Default,
+            tap_hold_interval: 0,
+        }
+    }
+}
+
+impl<T: Copy, K: Copy> HoldTapAction<T, K> {
+    /// Sets the timeout, in ticks.
+    pub const fn with_timeout(self, timeout: u16) -> Self {
+        Self { timeout, ..self }
+    }
+    /// Uses the tapping term of the layout as timeout (see
+    /// [`TAPPING_TERM`]).
+    pub const fn with_layout_term(self) -> Self {
+        self.with_timeout(TAPPING_TERM)
+    }
+    /// Sets the behavior configuration.
+    pub const fn with_config(self, config: HoldTapConfig) -> Self {
+        Self { config, ..self }
+    }
+}
+
+/// Activate a layer on hold, perform an action on tap, and lock
+/// the layer on double tap and hold.
+///
+/// If the key is held more than `timeout` ticks (usually
+/// milliseconds), the layer is activated while the key is held, else
+/// the `tap` action is performed. If the key is tapped and then
+/// pressed again and held in less than `lock_interval` ticks, the
+/// layer is locked: it stays active after the key release, until
+/// the key is pressed again.
+///
+/// Pressing a different key in between will not result in locking
+/// the layer; the key must be pressed twice in a row. The layer
+/// being locked, the key position on the locked layer must resolve
+/// to this action (typically using `Trans`) to be able to unlock it.
+///
+/// With a `NoOp` tap action, this is a layer key that can be quickly
+/// locked by tapping it and then holding it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde-leak", derive(serde::Deserialize))]
+pub struct LayerTapAction<T, K>
+where
+    T: 'static,
+    K: 'static,
+{
+    /// The duration, in ticks (usually milliseconds) giving the
+    /// difference between a hold and a tap. If [`TAPPING_TERM`], the
+    /// tapping term of the layout is used (see
+    /// [`Layout::set_tapping_term`](crate::layout::Layout::set_tapping_term)).
+    pub timeout: u16,
+    /// The layer activated on hold.
+    pub layer: usize,
+    /// The tap action.
+    pub tap: Action<T, K>,
+    /// The interval, in ticks, in which the key must be pressed
+    /// again after a tap to lock the layer. The timeout starts on
+    /// the first press of the key, NOT on the release.
+    ///
+    /// To deactivate the functionality, set this to 0.
+    pub lock_interval: u16,
+}
+
+/// A chord with a defined press order, see [`Action::HoldChord`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HoldChordAction<K: 'static> {
+    /// The leading keys, as modifiers, pressed first.
+    pub held: &'static [K],
+    /// The trailing key, pressed once the leading keys are pressed.
+    pub key: K,
+}
+
+/// An action needing a confirmation: the first press arms it, and a
+/// second press within `window` ticks (usually milliseconds) does
+/// the inner action. Otherwise, the action is silently disarmed.
+///
+/// Useful for dangerous keys, as a macro closing all the windows.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConfirmAction<T, K>
+where
+    T: 'static,
+    K: 'static,
+{
+    /// The confirmed action.
+    pub action: Action<T, K>,
+    /// The duration, in ticks, in which the second press must
+    /// happen.
+    pub window: u16,
+}
+
+/// Cycle through layers by tapping, with a momentary layer on hold.
+///
+/// The taps are counted until the key is not pressed again during
+/// `timeout` ticks, or another key is pressed. `n` taps then toggle
+/// the `n`th layer of `layers`, cycling through the list if there
+/// are more taps than layers: the layer is locked, replacing any
+/// other locked layer of the list, or unlocked if it was already
+/// locked. If the key is held more than `timeout` ticks, `hold` is
+/// activated until the key release.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LayerTapDanceAction {
+    /// The layers toggled by the taps.
+    pub layers: &'static [usize],
+    /// The duration, in ticks (usually milliseconds), after which
+    /// the taps are resolved, or the key is considered held.
+    pub timeout: u16,
+    /// The layer activated on hold.
+    pub hold: usize,
+}
+
+/// Perform an action depending on the number of taps.
+///
+/// The taps are counted until the key is not pressed again during
+/// `timeout` ticks (usually milliseconds), or another key is pressed.
+/// `n` taps then perform the `n`th action of `actions`, or the last
+/// one if there are more taps than actions. If the key is still held
+/// at this time, the action is held until the key release.
+///
+/// For example, `Escape` on one tap and `CapsLock` on two taps:
+///
+/// ```
+/// use keyberon::action::{k, td, Action, TapDanceAction};
+/// use keyberon::key_code::KeyCode::*;
+/// const ESC_CAPS: Action = td(&TapDanceAction {
+///     actions: &[k(Escape), k(CapsLock)],
+///     timeout: 200,
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TapDanceAction<T, K>
+where
+    T: 'static,
+    K: 'static,
+{
+    /// The actions, performed depending on the number of taps.
+    pub actions: &'static [Action<T, K>],
+    /// The duration, in ticks, after which the taps are resolved.
+    pub timeout: u16,
+}
+
+/// Latch some key codes until a number of key presses or a
+/// cancelling key press.
+///
+/// Once the key is pressed, the `keys` are sent until one of these
+/// conditions is met:
+/// - `count` other keys have been pressed and the last one is
+///   released, i.e. the `keys` modify the `count` following key
+///   presses. If `count` is 0, there is no limit;
+/// - a key which coordinates are in `cancel` is pressed. This key is
+///   then not modified by the `keys`;
+/// - the sticky key is pressed again.
+///
+/// The keys only changing the layers (as `Layer` or a held
+/// `LayerTap`) are not counted: the `keys` modify the key pressed on
+/// the new layer.
+///
+/// For example, a sticky `LShift` with a `count` of 1 is a classic
+/// one shot shift, and a sticky `NumLock` like modifier, with no
+/// `count` and the conflicting keys in `cancel`, stays active until
+/// a conflicting key is pressed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StickyAction<K>
+where
+    K: 'static,
+{
+    /// The latched key codes.
+    pub keys: &'static [K],
+    /// The number of key presses modified by the latch, 0 for no
+    /// limit.
+    pub count: u16,
+    /// The coordinates, as `(row, column)`, of the keys clearing the
+    /// latch when pressed.
+    pub cancel: &'static [(u8, u8)],
+}
+
+/// One shot key codes: tapped, the key codes modify the next key
+/// press.
+///
+/// - When tapped, the `keys` stay pressed until the next key press
+///   is released. Pressing another one shot key doesn't consume them:
+///   several one shot modifiers can be combined;
+/// - When held, the `keys` behave as normal keys, released with the
+///   one shot key if another key was pressed during the hold;
+/// - When tapped twice, the `keys` are locked, until the one shot
+///   key is pressed again.
+///
+/// An unused tap expires after `timeout` ticks (usually
+/// milliseconds). If `timeout` is 0, it never expires. The keys only
+/// changing the layers don't consume the tap, as with
+/// [`StickyAction`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OneShotAction<K>
+where
+    K: 'static,
+{
+    /// The one shot key codes.
+    pub keys: &'static [K],
+    /// The duration, in ticks, after which an unused tap expires, 0
+    /// for no expiration.
+    pub timeout: u16,
+}
+
+/// An event of a sequence (see [`Action::Sequence`]).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SequenceEvent<K>
+where
+    K: 'static,
+{
+    /// Press the key code. It stays pressed until released by a
+    /// `Release` event.
+    Press(K),
+    /// Release the last key code pressed by a `Press` event of the
+    /// sequence, and not released yet. The key codes are released in
+    /// the reverse order of their presses, as in `Press(LShift),
+    /// Press(A), Release(A), Release(LShift)`, the key code given
+    /// being the one of the matching `Press` event.
+    Release(K),
+    /// Wait the given duration, in ticks (usually milliseconds),
+    /// before the next event.
+    Delay {
+        /// The duration of the pause.
+        duration: u32,
+    },
+}
+
+/// The configuration of [`Action::DebugDump`].
+///
+/// For the [`KeyCode`] key codes, use
+/// [`DebugDumpAction::KEY_CODES`]. On a non US host layout, type the
+/// characters using a [`KeymapLocale`]:
+///
+/// ```
+/// use keyberon::action::DebugDumpAction;
+/// use keyberon::key_code::{KeyCode, KeymapLocale};
+///
+/// // partial german layout
+/// const DE: KeymapLocale = KeymapLocale {
+///     overrides: &[(b'y', KeyCode::Z, 0), (b'z', KeyCode::Y, 0)],
+/// };
+/// fn de_key(c: u8) -> Option<(KeyCode, u8)> {
+///     DE.key(c)
+/// }
+/// static DEBUG_DUMP: DebugDumpAction<KeyCode> = DebugDumpAction {
+///     from_ascii: de_key,
+///     ..DebugDumpAction::KEY_CODES
+/// };
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DebugDumpAction<K>
+where
+    K: 'static,
+{
+    /// Returns the key code typing an ASCII character, and the
+    /// modifiers to press, as a USB HID modifier bitfield (see
+    /// [`KeymapLocale::key`]).
+    pub from_ascii: fn(u8) -> Option<(K, u8)>,
+    /// The modifier key codes, in the order of the bits of the
+    /// modifier bitfield: left ctrl, shift, alt and gui, then the
+    /// right ones.
+    pub modifier_keys: [K; 8],
+    /// Returns the character representing the key code if it is a
+    /// modifier to report, the modifiers being reported in the order
+    /// they were pressed.
+    pub modifier: fn(K) -> Option<u8>,
+}
+
+impl<K: PartialEq> PartialEq for DebugDumpAction<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.from_ascii as usize == other.from_ascii as usize
+            && self.modifier_keys == other.modifier_keys
+            && self.modifier as usize == other.modifier as usize
+    }
+}
+impl<K: Eq> Eq for DebugDumpAction<K> {}
+
+impl DebugDumpAction<KeyCode> {
+    /// The configuration for [`KeyCode`], typing on a US host
+    /// layout (see [`KeymapLocale::US`]) and reporting the modifiers
+    /// as `S` (shift), `C` (ctrl), `A` (alt) and `G` (gui).
+    pub const KEY_CODES: Self = Self {
+        from_ascii: us_key,
+        modifier_keys: [
+            KeyCode::LCtrl,
+            KeyCode::LShift,
+            KeyCode::LAlt,
+            KeyCode::LGui,
+            KeyCode::RCtrl,
+            KeyCode::RShift,
+            KeyCode::RAlt,
+            KeyCode::RGui,
+        ],
+        modifier: modifier_char,
+    };
+}
+
+fn us_key(c: u8) -> Option<(KeyCode, u8)> {
+    KeymapLocale::US.key(c)
+}
+
+fn modifier_char(kc: KeyCode) -> Option<u8> {
+    use KeyCode::*;
+    match kc {
+        LShift | RShift => Some(b'S'),
+        LCtrl | RCtrl => Some(b'C'),
+        LAlt | RAlt => Some(b'A'),
+        LGui | RGui => Some(b'G'),
+        _ => None,
+    }
+}
+
+/// The input method of the [`Action::Unicode`] actions, set by
+/// [`Layout::set_unicode_mode`](crate::layout::Layout::set_unicode_mode).
+///
+/// A code point is typed as the `start` events, its hexadecimal
+/// digits, and the `end` events. At least 4 digits are typed, and
+/// all the digits of the code points above `U+FFFF`, except with
+/// `utf16`, typing them as surrogate pairs.
+///
+/// For the [`KeyCode`] key codes, use [`UnicodeMode::LINUX`],
+/// [`UnicodeMode::WINDOWS`] or [`UnicodeMode::MAC_OS`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct UnicodeMode<K>
+where
+    K: 'static,
+{
+    /// The events starting the input of a code point.
+    pub start: &'static [SequenceEvent<K>],
+    /// The events ending the input of a code point.
+    pub end: &'static [SequenceEvent<K>],
+    /// The key codes of the hexadecimal digits, from `0` to `f`.
+    pub digits: &'static [K; 16],
+    /// Types the code points above `U+FFFF` as UTF-16 surrogate
+    /// pairs.
+    pub utf16: bool,
+}
+
+impl UnicodeMode<KeyCode> {
+    const DIGITS: &'static [KeyCode; 16] = {
+        use KeyCode::*;
+        &[
+            Kb0, Kb1, Kb2, Kb3, Kb4, Kb5, Kb6, Kb7, Kb8, Kb9, A, B, C, D, E, F,
+        ]
+    };
+
+    /// Linux (IBus and GTK): `Ctrl+Shift+U`, the digits, and space.
+    pub const LINUX: Self = {
+        use KeyCode::*;
+        use SequenceEvent::*;
+        Self {
+            start: &[
+                Press(LCtrl),
+                Press(LShift),
+                Press(U),
+                Release(U),
+                Release(LShift),
+                Release(LCtrl),
+            ],
+            end: &[Press(Space), Release(Space)],
+            digits: Self::DIGITS,
+            utf16: false,
+        }
+    };
+
+    /// Windows: the digits typed after `KpPlus` while `LAlt` is held.
+    /// The `EnableHexNumpad` registry setting must be enabled.
+    pub const WINDOWS: Self = {
+        use KeyCode::*;
+        use SequenceEvent::*;
+        Self {
+            start: &[Press(LAlt), Press(KpPlus), Release(KpPlus)],
+            end: &[Release(LAlt)],
+            digits: Self::DIGITS,
+            utf16: false,
+        }
+    };
+
+    /// macOS: the digits typed while `LAlt` (option) is held. The
+    /// "Unicode Hex Input" input source must be selected.
+    pub const MAC_OS: Self = {
+        use KeyCode::*;
+        use SequenceEvent::*;
+        Self {
+            start: &[Press(LAlt)],
+            end: &[Release(LAlt)],
+            digits: Self::DIGITS,
+            utf16: true,
+        }
+    };
+}
+
+/// The configuration of [`Action::CapsWord`].
+///
+/// For the [`KeyCode`] key codes, use [`CapsWordAction::KEY_CODES`].
+#[derive(Debug, Clone, Copy)]
+pub struct CapsWordAction<K>
+where
+    K: 'static,
+{
+    /// Returns `true` if the key code must be shifted, as the letters.
+    pub shifted: fn(K) -> bool,
+    /// Returns `true` if the key code continues the word without
+    /// being shifted, as the digits. The other key codes end the
+    /// word.
+    pub continues: fn(K) -> bool,
+    /// The shift key code.
+    pub shift: K,
+}
+
+impl<K: PartialEq> PartialEq for CapsWordAction<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.shifted as usize == other.shifted as usize
+            && self.continues as usize == other.continues as usize
+            && self.shift == other.shift
+    }
+}
+impl<K: Eq> Eq for CapsWordAction<K> {}
+
+impl CapsWordAction<KeyCode> {
+    /// The configuration for [`KeyCode`]: the letters are shifted,
+    /// and the digits, `-`, backspace, delete and the modifiers
+    /// continue the word.
+    pub const KEY_CODES: Self = Self {
+        shifted: KeyCode::is_letter,
+        continues: |kc| {
+            kc.is_digit()
+                || kc.is_modifier()
+                || matches!(kc, KeyCode::Minus | KeyCode::BSpace | KeyCode::Delete)
+        },
+        shift: KeyCode::LShift,
+    };
+}
+
+/// The configuration of [`Action::GraveEscape`].
+///
+/// For the [`KeyCode`] key codes, use
+/// [`GraveEscapeAction::KEY_CODES`].
+#[derive(Debug, Clone, Copy)]
+pub struct GraveEscapeAction<K>
+where
+    K: 'static,
+{
+    /// The key code sent by default.
+    pub escape: K,
+    /// The key code sent when a shift is held.
+    pub grave: K,
+    /// Returns `true` if the key code is a shift.
+    pub is_shift: fn(K) -> bool,
+    /// Returns `true` if the key code forces the escape key code,
+    /// even if a shift is held, as the GUI keys.
+    pub forces_escape: fn(K) -> bool,
+}
+
+impl<K: PartialEq> PartialEq for GraveEscapeAction<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.escape == other.escape
+            && self.grave == other.grave
+            && self.is_shift as usize == other.is_shift as usize
+            && self.forces_escape as usize == other.forces_escape as usize
+    }
+}
+impl<K: Eq> Eq for GraveEscapeAction<K> {}
+
+impl GraveEscapeAction<KeyCode> {
+    /// The configuration for [`KeyCode`]: `Escape`, and `Grave` with
+    /// a shift held (giving `~`), except if a GUI key is held.
+    pub const KEY_CODES: Self = Self {
+        escape: KeyCode::Escape,
+        grave: KeyCode::Grave,
+        is_shift: |kc| matches!(kc, KeyCode::LShift | KeyCode::RShift),
+        forces_escape: |kc| matches!(kc, KeyCode::LGui | KeyCode::RGui),
+    };
+}
+
+/// A mouse action, see [`Action::Mouse`].
+///
+/// The pointer moves every [`MOUSE_MOVE_INTERVAL`] ticks (usually
+/// milliseconds), the displacement being multiplied by the number of
+/// moves since the key press, up to [`MOUSE_MAX_SPEED`]: the pointer
+/// accelerates while the key is held. The wheel scrolls every
+/// [`MOUSE_WHEEL_INTERVAL`] ticks, without acceleration.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseAction {
+    /// Press the buttons while held, bit 0 being the left button, bit
+    /// 1 the right button and bit 2 the middle button.
+    Click(u8),
+    /// Move the pointer while held.
+    Move {
+        /// The initial horizontal displacement, positive to the right.
+        x: i8,
+        /// The initial vertical displacement, positive to the bottom.
+        y: i8,
+    },
+    /// Scroll the wheel while held, positive to the top.
+    Wheel(i8),
+}
+
+/// The period of the pointer moves of [`MouseAction::Move`], in ticks.
+pub const MOUSE_MOVE_INTERVAL: u16 = 16;
+/// The maximum multiplier of the displacement of
+/// [`MouseAction::Move`].
+pub const MOUSE_MAX_SPEED: u16 = 8;
+/// The period of the scrolls of [`MouseAction::Wheel`], in ticks.
+pub const MOUSE_WHEEL_INTERVAL: u16 = 64;
+
+/// A standard RGB lighting control, see [`Action::Rgb`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RgbEvent {
+    /// Toggle the lighting on or off.
+    Toggle,
+    /// Increase the hue.
+    HueUp,
+    /// Decrease the hue.
+    HueDown,
+    /// Increase the brightness.
+    BrightnessUp,
+    /// Decrease the brightness.
+    BrightnessDown,
+    /// Switch to the next lighting mode.
+    ModeNext,
+}
+
 /// The different actions that can be done.
+///
+/// With the `serde` feature, the actions can be serialized, as an
+/// externally tagged enum, if `T` and `K` can (`Infallible` can't, `()`
+/// can be used instead). The `DebugDump` action and the `Custom`
+/// [`HoldTapConfig`] can't be serialized.
+///
+/// With the `serde-leak` feature, the actions without configuration
+/// (as `KeyCode`, `Layer` or `Unicode`), and the `MultipleKeyCodes`,
+/// `MultipleActions`, `Sequence`, `HoldTap` and `LayerTap` actions can
+/// be deserialized, their `'static` parts being leaked: useful for a
+/// keymap loaded once, not to reload it again and again.
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Action<T = core::convert::Infallible, K = KeyCode>
 where
     T: 'static,
     K: 'static,
 {
-    /// No operation action: just do nothing.
+    /// No operation action: just do nothing. Contrary to `Trans`, it
+    /// hides the actions of the layers below (see [`n`]), allowing
+    /// to disable some keys on a momentary layer.
     NoOp,
-    /// Transparent, i.e. get the action from the default layer. On
-    /// the default layer, it is equivalent to `NoOp`.
+    /// Transparent, i.e. get the action from the active layers below,
+    /// down to the default layer (see
+    /// [`Layout::active_layers`](crate::layout::Layout::active_layers)).
+    /// On the default layer, it is equivalent to `NoOp`.
     Trans,
     /// A key code, i.e. a classic key.
     KeyCode(K),
+    /// A key code sent on the given usage page, overriding the
+    /// default routing of
+    /// [`KeyReports`](crate::key_code::KeyReports). Useful for
+    /// applications remapping the keys oddly.
+    OnPage {
+        /// The key code.
+        key: K,
+        /// The usage page on which the key code is sent.
+        page: UsagePage,
+    },
     /// Multiple key codes sent at the same time, as if these keys
     /// were pressed at the same time. Useful to send a shifted key,
     /// or complex shortcuts like Ctrl+Alt+Del in a single key press.
     MultipleKeyCodes(&'static &'static [K]),
+    /// A chord pressed in order: the leading keys are pressed, then
+    /// the trailing key on the next report, the final report
+    /// containing all the keys. On release, the trailing key is
+    /// released first, then the leading keys in reverse order, one
+    /// per report. Useful for the shortcuts the OS is picky about,
+    /// as Ctrl+Alt+Del (see [`HoldChordAction`]).
+    HoldChord(&'static HoldChordAction<K>),
     /// Multiple actions sent at the same time.
     MultipleActions(&'static &'static [Action<T, K>]),
     /// While pressed, change the current layer. That's the classic
@@ -195,16 +837,257 @@ where
     Layer(usize),
     /// Change the default layer.
     DefaultLayer(usize),
+    /// Toggle a layer on press: the layer is locked until toggled
+    /// again (see [`tg`]). Contrary to [`Layer`](Action::Layer), the
+    /// layer stays active after the release.
+    ToggleLayer(usize),
+    /// Lock the momentary layer currently held (by a
+    /// [`Layer`](Action::Layer) action, or the hold of a layer tap),
+    /// the layer staying active after its key release. If no
+    /// momentary layer is held, unlock the last layer locked this
+    /// way, if any, the layers toggled by other actions staying
+    /// active.
+    ///
+    /// As the layer is locked, the key position on the locked layer
+    /// must resolve to this action (typically using `Trans`) to be
+    /// able to unlock it.
+    LayerLock,
     /// Perform different actions on key hold/tap (see [`HoldTapAction`]).
     HoldTap(&'static HoldTapAction<T, K>),
+    /// Activate a layer on hold, perform an action on tap, and lock
+    /// the layer on double tap and hold (see [`LayerTapAction`]).
+    LayerTap(&'static LayerTapAction<T, K>),
+    /// Do an action only when pressed twice in a row (see
+    /// [`ConfirmAction`]).
+    Confirm(&'static ConfirmAction<T, K>),
+    /// Toggle layers by tapping, with a momentary layer on hold (see
+    /// [`LayerTapDanceAction`]).
+    LayerTapDance(&'static LayerTapDanceAction),
+    /// Perform an action depending on the number of taps (see
+    /// [`TapDanceAction`] and [`td`]).
+    TapDance(&'static TapDanceAction<T, K>),
+    /// An RGB lighting control.
+    ///
+    /// On press, the event is queued by the layout, to be retrieved
+    /// by the firmware using
+    /// [`Layout::take_rgb_event`](crate::layout::Layout::take_rgb_event).
+    /// The firmware is in charge of driving the LEDs.
+    Rgb(RgbEvent),
+    /// Play a sequence of events, as a macro.
+    ///
+    /// An event is played per tick, the sequence continuing after
+    /// the key release. A `Delay` event allows to wait a bit before
+    /// the next event, for example to let the host switch window,
+    /// without blocking the rest of the layout. The key codes
+    /// pressed by the sequence must be released by the sequence.
+    ///
+    /// Pressing another key interrupts the sequence, releasing the
+    /// key codes it pressed.
+    Sequence(&'static &'static [SequenceEvent<K>]),
+    /// Type the current layer and the active modifiers, as `L2 S`
+    /// for the layer 2 with shift held, as a sequence (see
+    /// [`DebugDumpAction`]).
+    ///
+    /// Useful to debug a layout on a host without a display.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    DebugDump(&'static DebugDumpAction<K>),
+    /// Type a Unicode code point, as a sequence, using the input
+    /// method of the host set by
+    /// [`Layout::set_unicode_mode`](crate::layout::Layout::set_unicode_mode)
+    /// (see [`UnicodeMode`]). Without input method, nothing is typed.
+    Unicode(char),
+    /// Record the key codes sent by the following key presses in the
+    /// given dynamic macro, until a `MacroStop` action, or until the
+    /// same action is done again. Recording replaces the previous
+    /// content of the macro.
+    ///
+    /// As in a [`Sequence`](Action::Sequence), the key codes are
+    /// released in the reverse order of their presses: a key code
+    /// released while a key code pressed after it is held is released
+    /// with it.
+    ///
+    /// There are [`DYNAMIC_MACROS`](crate::layout::DYNAMIC_MACROS)
+    /// macros, recording at most
+    /// [`DYNAMIC_MACRO_LEN`](crate::layout::DYNAMIC_MACRO_LEN) events
+    /// each: the recording stops silently when the macro is full.
+    MacroRecord(u8),
+    /// Stop recording a dynamic macro (see
+    /// [`MacroRecord`](Action::MacroRecord)).
+    MacroStop,
+    /// Play the given dynamic macro, as a sequence (see
+    /// [`MacroRecord`](Action::MacroRecord)). Does nothing while
+    /// recording.
+    MacroPlay(u8),
+    /// Toggle the caps word mode (see [`CapsWordAction`]): the
+    /// following letters are shifted, until a key code ending the
+    /// word, as space or enter, is pressed.
+    ///
+    /// Useful to type an identifier in capitals, without caps lock to
+    /// disable afterward.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    CapsWord(&'static CapsWordAction<K>),
+    /// Escape, or grave with a shift held, as on the 60% keyboards
+    /// without a grave key (see [`GraveEscapeAction`]). The key code
+    /// is chosen on press, according to the modifiers held.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    GraveEscape(&'static GraveEscapeAction<K>),
+    /// Latch key codes until some other keys are pressed (see
+    /// [`StickyAction`]).
+    Sticky(&'static StickyAction<K>),
+    /// One shot key codes, as one shot modifiers (see
+    /// [`OneShotAction`]).
+    OneShot(&'static OneShotAction<K>),
+    /// A one shot layer: the layer is active for the next key press
+    /// only, as [`OneShot`](Action::OneShot) for key codes.
+    ///
+    /// When held, it behaves as [`Layer`](Action::Layer). When tapped
+    /// twice, the layer is locked until the key is pressed again. An
+    /// unused tap expires after `timeout` ticks (usually
+    /// milliseconds), or never if `timeout` is 0.
+    OneShotLayer {
+        /// The layer.
+        layer: usize,
+        /// The duration, in ticks, after which an unused tap expires,
+        /// 0 for no expiration.
+        timeout: u16,
+    },
+    /// A key code repeated by the firmware while the key is held.
+    ///
+    /// A tap sends the key code once. If the key is held more than
+    /// `delay` ticks (usually milliseconds), the key code is
+    /// released and pressed again every `rate` ticks, until the key
+    /// is released. Useful to have auto repeat on some specific
+    /// keys, like arrows or backspace, whatever the host
+    /// configuration is.
+    HoldRepeat {
+        /// The repeated key code.
+        key: K,
+        /// The number of ticks before the repetition starts.
+        delay: u16,
+        /// The period of the repetition, in ticks.
+        rate: u16,
+    },
+    /// Activate a layer while held, as [`Layer`](Action::Layer), and
+    /// toggle it on `taps` successive taps, as the `TT` key of QMK.
+    ///
+    /// A tap is a press and release shorter than `timeout` ticks
+    /// (usually milliseconds), the next tap having to be pressed
+    /// before `timeout` ticks. A longer hold, or the press of another
+    /// key, resets the tap count. Once toggled, the layer is locked
+    /// until toggled again the same way.
+    ToggleTap {
+        /// The toggled layer.
+        layer: usize,
+        /// The number of taps toggling the layer.
+        taps: u8,
+        /// The tapping term, in ticks.
+        timeout: u16,
+    },
+    /// A key code shifted when held, as the auto shift of QMK (see
+    /// [`auto_shift`]).
+    ///
+    /// A tap sends the key code, but holding the key more than
+    /// `timeout` ticks (usually milliseconds) sends it with `shift`,
+    /// giving `A` instead of `a` or `!` instead of `1`. As for
+    /// [`HoldTap`](Action::HoldTap), the following events are delayed
+    /// until the key is resolved, keeping their order.
+    AutoShift {
+        /// The key code.
+        key: K,
+        /// The shift key code.
+        shift: K,
+        /// The hold duration after which the key code is shifted, in
+        /// ticks.
+        timeout: u16,
+    },
+    /// A mouse action (see [`MouseAction`]), the mouse report being
+    /// given by [`Layout::mouse_report`](crate::layout::Layout::mouse_report).
+    Mouse(MouseAction),
     /// Custom action.
     ///
     /// Define a user defined action. This enum can be anything you
     /// want, as long as it has the `'static` lifetime. It can be used
     /// to drive any non keyboard related actions that you might
     /// manage with key events.
+    ///
+    /// The payload is given back by the layout in the
+    /// [`CustomEvent`](crate::layout::CustomEvent) on press and
+    /// release, allowing the firmware to pattern match on it. For
+    /// example, with `Layers<C, R, L, u16>`, `Custom(0x0102)` can be
+    /// used to toggle the flag `0x02` of the peripheral `0x01`.
+    ///
+    /// This also allows vendor specific key codes (as a fn lock)
+    /// without extending [`KeyCode`]: the firmware defines the
+    /// meaning of the payload, that can be read back using
+    /// [`Layout::action_at`](crate::layout::Layout::action_at) and
+    /// [`Layout::active_customs`](crate::layout::Layout::active_customs).
     Custom(T),
 }
+
+/// The actions that can be deserialized, see [`Action`].
+#[cfg(feature = "serde-leak")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Action")]
+enum LeakedAction<T: 'static, K: 'static> {
+    NoOp,
+    Trans,
+    KeyCode(K),
+    OnPage { key: K, page: UsagePage },
+    MultipleKeyCodes(alloc::vec::Vec<K>),
+    MultipleActions(alloc::vec::Vec<Action<T, K>>),
+    Layer(usize),
+    DefaultLayer(usize),
+    ToggleLayer(usize),
+    LayerLock,
+    HoldTap(HoldTapAction<T, K>),
+    LayerTap(LayerTapAction<T, K>),
+    Rgb(RgbEvent),
+    Sequence(alloc::vec::Vec<SequenceEvent<K>>),
+    Unicode(char),
+    MacroRecord(u8),
+    MacroStop,
+    MacroPlay(u8),
+    Custom(T),
+}
+
+#[cfg(feature = "serde-leak")]
+impl<'de, T, K> serde::Deserialize<'de> for Action<T, K>
+where
+    T: serde::Deserialize<'de> + 'static,
+    K: serde::Deserialize<'de> + 'static,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use alloc::boxed::Box;
+        use alloc::vec::Vec;
+        fn leak<X>(x: X) -> &'static X {
+            Box::leak(Box::new(x))
+        }
+        fn leak_slice<X>(v: Vec<X>) -> &'static &'static [X] {
+            leak(&*Box::leak(v.into_boxed_slice()))
+        }
+        Ok(match LeakedAction::deserialize(deserializer)? {
+            LeakedAction::NoOp => Action::NoOp,
+            LeakedAction::Trans => Action::Trans,
+            LeakedAction::KeyCode(kc) => Action::KeyCode(kc),
+            LeakedAction::OnPage { key, page } => Action::OnPage { key, page },
+            LeakedAction::MultipleKeyCodes(kcs) => Action::MultipleKeyCodes(leak_slice(kcs)),
+            LeakedAction::MultipleActions(actions) => Action::MultipleActions(leak_slice(actions)),
+            LeakedAction::Layer(l) => Action::Layer(l),
+            LeakedAction::DefaultLayer(l) => Action::DefaultLayer(l),
+            LeakedAction::ToggleLayer(l) => Action::ToggleLayer(l),
+            LeakedAction::LayerLock => Action::LayerLock,
+            LeakedAction::HoldTap(a) => Action::HoldTap(leak(a)),
+            LeakedAction::LayerTap(a) => Action::LayerTap(leak(a)),
+            LeakedAction::Rgb(e) => Action::Rgb(e),
+            LeakedAction::Sequence(events) => Action::Sequence(leak_slice(events)),
+            LeakedAction::Unicode(c) => Action::Unicode(c),
+            LeakedAction::MacroRecord(id) => Action::MacroRecord(id),
+            LeakedAction::MacroStop => Action::MacroStop,
+            LeakedAction::MacroPlay(id) => Action::MacroPlay(id),
+            LeakedAction::Custom(t) => Action::Custom(t),
+        })
+    }
+}
 impl<T, K: Clone> Action<T, K> {
     /// Gets the layer number if the action is the `Layer` action.
     pub fn layer(self) -> Option<usize> {
@@ -223,6 +1106,13 @@ impl<T, K: Clone> Action<T, K> {
     }
 }
 
+/// A shortcut to create a `Action::NoOp`, useful to create compact
+/// layout. This key does nothing, even if the layers below have an
+/// action for it, contrary to `Action::Trans`.
+pub const fn n<T, K>() -> Action<T, K> {
+    Action::NoOp
+}
+
 /// A shortcut to create a `Action::KeyCode`, useful to create compact
 /// layout.
 pub const fn k<T, K>(kc: K) -> Action<T, K> {
@@ -241,6 +1131,31 @@ pub const fn d<T, K>(layer: usize) -> Action<T, K> {
     Action::DefaultLayer(layer)
 }
 
+/// A shortcut to create a `Action::ToggleLayer`, useful to create
+/// compact layout.
+pub const fn tg<T, K>(layer: usize) -> Action<T, K> {
+    Action::ToggleLayer(layer)
+}
+
+/// The default timeout of [`auto_shift`], in ticks.
+pub const DEFAULT_AUTO_SHIFT_TIMEOUT: u16 = 175;
+
+/// A shortcut to create a `Action::AutoShift`, using `LShift` and
+/// [`DEFAULT_AUTO_SHIFT_TIMEOUT`], useful to create compact layout.
+pub const fn auto_shift<T>(key: KeyCode) -> Action<T> {
+    Action::AutoShift {
+        key,
+        shift: KeyCode::LShift,
+        timeout: DEFAULT_AUTO_SHIFT_TIMEOUT,
+    }
+}
+
+/// A shortcut to create a `Action::TapDance`, useful to create
+/// compact layout.
+pub const fn td<T, K>(dance: &'static TapDanceAction<T, K>) -> Action<T, K> {
+    Action::TapDance(dance)
+}
+
 /// A shortcut to create a `Action::MultipleKeyCodes`, useful to
 /// create compact layout.
 pub const fn m<T, K>(kcs: &'static &'static [K]) -> Action<T, K> {
@@ -257,4 +1172,76 @@ mod tests {
         const PTR_SIZE: usize = mem::size_of::<&()>();
         assert_eq!(mem::size_of::<Action::<(), ()>>(), 2 * PTR_SIZE);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::key_code::KeyCode::*;
+        type Action = super::Action<()>;
+        const HOLD_TAP: Action = Action::HoldTap(&HoldTapAction {
+            timeout: 200,
+            hold: Action::KeyCode(LShift),
+            tap: Action::KeyCode(A),
+            config: HoldTapConfig::PermissiveHold,
+            tap_hold_interval: 0,
+        });
+        const LAYER_TAP: Action = Action::LayerTap(&LayerTapAction {
+            timeout: 200,
+            layer: 1,
+            tap: Action::NoOp,
+            lock_interval: 150,
+        });
+        static ACTIONS: [Action; 4] = [k(B), m(&[LCtrl, C].as_slice()), HOLD_TAP, LAYER_TAP];
+        assert_eq!(
+            r#"[{"KeyCode":5},{"MultipleKeyCodes":[224,6]},{"HoldTap":{"timeout":200,"hold":{"KeyCode":225},"tap":{"KeyCode":4},"config":"PermissiveHold","tap_hold_interval":0}},{"LayerTap":{"timeout":200,"layer":1,"tap":"NoOp","lock_interval":150}}]"#,
+            serde_json::to_string(&ACTIONS).unwrap()
+        );
+
+        for kc in [A, Kb1, LShift, MediaCalc] {
+            let json = serde_json::to_string(&kc).unwrap();
+            assert_eq!(kc, serde_json::from_str(&json).unwrap());
+        }
+        assert!(serde_json::from_str::<crate::key_code::KeyCode>("200").is_err());
+        let seq = [
+            SequenceEvent::Press(A),
+            SequenceEvent::Delay { duration: 10 },
+        ];
+        let json = serde_json::to_string(&seq).unwrap();
+        assert_eq!(
+            seq,
+            serde_json::from_str::<[SequenceEvent<_>; 2]>(&json).unwrap()
+        );
+    }
+
+    #[cfg(feature = "serde-leak")]
+    #[test]
+    fn serde_leak() {
+        use crate::key_code::KeyCode::*;
+        type Action = super::Action<()>;
+        const HOLD_TAP: Action = Action::HoldTap(&HoldTapAction {
+            timeout: 200,
+            hold: Action::KeyCode(LShift),
+            tap: Action::MultipleKeyCodes(&[LCtrl, C].as_slice()),
+            config: HoldTapConfig::PermissiveHold,
+            tap_hold_interval: 0,
+        });
+        const LAYER_TAP: Action = Action::LayerTap(&LayerTapAction {
+            timeout: 200,
+            layer: 1,
+            tap: Action::NoOp,
+            lock_interval: 150,
+        });
+        static ACTIONS: [Action; 5] = [
+            k(B),
+            m(&[LCtrl, C].as_slice()),
+            HOLD_TAP,
+            LAYER_TAP,
+            Action::Layer(2),
+        ];
+        let json = serde_json::to_string(&ACTIONS).unwrap();
+        assert_eq!(ACTIONS, serde_json::from_str::<[Action; 5]>(&json).unwrap());
+
+        // the actions with a configuration can't be deserialized
+        assert!(serde_json::from_str::<Action>(r#"{"Confirm":{}}"#).is_err());
+    }
 }
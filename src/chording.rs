@@ -9,6 +9,12 @@
 //! the debounce period determines the period in which all keys
 //! need to be pressed to trigger the chord.
 //!
+//! As the presses of a chord rarely land in the same scan, a
+//! coalescing window can be set with
+//! [`Chording::set_chord_window_ms`]: the presses of the keys of the
+//! chords are then delayed up to this number of milliseconds, the
+//! presses in this window being treated as simultaneous.
+//!
 //! You must use a virtual row/area of your layout to
 //! define the result of the chord if the desired result is
 //! not already on the layer that you want to use the chord on.
@@ -100,9 +106,9 @@ impl Chord {
         }
     }
 
-    fn contains_chord(&mut self, events: &[Event]) -> bool {
+    fn contains_chord(&self, events: &[Event]) -> bool {
         for key in self.def.1 {
-            if !events.iter().any(|&k| (&k.coord() == key && k.is_press())) {
+            if !events.iter().any(|&k| &k.coord() == key && k.is_press()) {
                 return false;
             }
         }
@@ -114,7 +120,7 @@ impl Chord {
         for key in self.def.1 {
             if let Some(position) = events
                 .iter()
-                .position(|&k| (&k.coord() == key && k.is_press()))
+                .position(|&k| &k.coord() == key && k.is_press())
             {
                 events.swap_remove(position);
             }
@@ -129,7 +135,7 @@ impl Chord {
             for key in self.def.1 {
                 if let Some(position) = events
                     .iter()
-                    .position(|&k| (&k.coord() == key && k.is_release()))
+                    .position(|&k| &k.coord() == key && k.is_release())
                 {
                     events.swap_remove(position);
                 }
@@ -149,6 +155,13 @@ impl Chord {
 pub struct Chording<const N: usize> {
     /// Defined chords
     chords: Vec<Chord, N>,
+    /// Coalescing window, in ms
+    chord_window_ms: u16,
+    /// Delayed presses of the keys of the chords, and the events
+    /// not fitting in the previous flush
+    pending: Vec<Event, 8>,
+    /// Age of the oldest pending press, in ms
+    since: u16,
 }
 
 impl<const N: usize> Chording<N> {
@@ -156,11 +169,64 @@ impl<const N: usize> Chording<N> {
     pub fn new(chords: &'static [ChordDef; N]) -> Self {
         Self {
             chords: chords.iter().map(Chord::new).collect(),
+            chord_window_ms: 0,
+            pending: Vec::new(),
+            since: 0,
         }
     }
 
+    /// Sets the coalescing window, in milliseconds. 0, the default,
+    /// disables it.
+    ///
+    /// The presses of the keys of the chords are delayed until a
+    /// chord is complete, another event happens, or the window
+    /// expires. In this case, [`tick`](Self::tick) must be called
+    /// every millisecond.
+    pub fn set_chord_window_ms(&mut self, chord_window_ms: u16) {
+        self.chord_window_ms = chord_window_ms;
+    }
+
     /// Consolidate events and return processed results as a result.
-    pub fn tick(&mut self, mut vec: Vec<Event, 8>) -> Vec<Event, 8> {
+    pub fn tick(&mut self, vec: Vec<Event, 8>) -> Vec<Event, 8> {
+        if self.chord_window_ms == 0 && self.pending.is_empty() {
+            return self.process(vec);
+        }
+        if !self.pending.is_empty() {
+            self.since = self.since.saturating_add(1);
+        }
+        let mut others = Vec::<Event, 8>::new();
+        for e in vec {
+            if others.is_empty() && e.is_press() && self.is_chord_key(e.coord()) {
+                if self.pending.push(e).is_err() {
+                    let _ = others.push(e);
+                }
+            } else {
+                let _ = others.push(e);
+            }
+        }
+        let complete = self.chords.iter().any(|c| c.contains_chord(&self.pending));
+        if complete || !others.is_empty() || self.since >= self.chord_window_ms {
+            let mut batch = core::mem::take(&mut self.pending);
+            self.since = 0;
+            for e in others {
+                if let Err(e) = batch.push(e) {
+                    // at most `others.len()` events are left: they
+                    // fit, and are flushed on the next tick
+                    self.pending.push(e).unwrap();
+                    self.since = self.chord_window_ms;
+                }
+            }
+            self.process(batch)
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn is_chord_key(&self, coord: KeyPosition) -> bool {
+        self.chords.iter().any(|c| c.def.1.contains(&coord))
+    }
+
+    fn process(&mut self, mut vec: Vec<Event, 8>) -> Vec<Event, 8> {
         for c in &mut self.chords {
             c.tick(&vec);
             if c.contains_chord(&vec) {
@@ -299,4 +365,77 @@ mod test {
         double_press.push(Press(0, 2)).ok();
         assert_eq!(chording.tick(double_press), &[Press(1, 2)]);
     }
+
+    #[test]
+    fn chord_window() {
+        const CHORDS: [ChordDef; 1] = [((0, 2), &[(0, 0), (0, 1)])];
+        let mut chording = Chording::new(&CHORDS);
+        chording.set_chord_window_ms(10);
+        let press = |i, j| Vec::<Event, 8>::from_slice(&[Press(i, j)]).unwrap();
+
+        // 5 ms apart: the chord is matched
+        assert_eq!(chording.tick(press(0, 0)), &[]);
+        for _ in 0..4 {
+            assert_eq!(chording.tick(Vec::new()), &[]);
+        }
+        assert_eq!(chording.tick(press(0, 1)), &[Press(0, 2)]);
+        let mut double_release = Vec::<Event, 8>::new();
+        double_release.push(Release(0, 0)).ok();
+        double_release.push(Release(0, 1)).ok();
+        assert_eq!(chording.tick(double_release), &[Release(0, 2)]);
+
+        // 50 ms apart: the presses are delayed by the window, but
+        // the chord is not matched
+        assert_eq!(chording.tick(press(0, 0)), &[]);
+        for _ in 0..9 {
+            assert_eq!(chording.tick(Vec::new()), &[]);
+        }
+        assert_eq!(chording.tick(Vec::new()), &[Press(0, 0)]);
+        for _ in 0..39 {
+            assert_eq!(chording.tick(Vec::new()), &[]);
+        }
+        assert_eq!(chording.tick(press(0, 1)), &[]);
+        for _ in 0..9 {
+            assert_eq!(chording.tick(Vec::new()), &[]);
+        }
+        assert_eq!(chording.tick(Vec::new()), &[Press(0, 1)]);
+
+        // another event flushes the pending presses, keeping the order
+        let mut release = Vec::<Event, 8>::new();
+        release.push(Release(0, 0)).ok();
+        assert_eq!(chording.tick(release), &[Release(0, 0)]);
+        assert_eq!(chording.tick(press(0, 0)), &[]);
+        assert_eq!(chording.tick(press(1, 0)), &[Press(0, 0), Press(1, 0)]);
+    }
+
+    #[test]
+    fn chord_window_overflow() {
+        const CHORDS: [ChordDef; 1] = [((2, 0), &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)])];
+        let mut chording = Chording::new(&CHORDS);
+        chording.set_chord_window_ms(10);
+
+        let presses: Vec<Event, 8> = (0..4).map(|j| Press(0, j)).collect();
+        assert_eq!(chording.tick(presses), &[]);
+        // the events not fitting with the pending presses are
+        // carried to the next tick, not dropped
+        let releases: Vec<Event, 8> = (0..8).map(|j| Release(1, j)).collect();
+        assert_eq!(
+            chording.tick(releases),
+            &[
+                Press(0, 0),
+                Press(0, 1),
+                Press(0, 2),
+                Press(0, 3),
+                Release(1, 0),
+                Release(1, 1),
+                Release(1, 2),
+                Release(1, 3),
+            ]
+        );
+        assert_eq!(
+            chording.tick(Vec::new()),
+            &[Release(1, 4), Release(1, 5), Release(1, 6), Release(1, 7)]
+        );
+        assert_eq!(chording.tick(Vec::new()), &[]);
+    }
 }
@@ -0,0 +1,142 @@
+//! Shared millisecond clock.
+//!
+//! By default, the [`Debouncer`](crate::debounce::Debouncer) counts
+//! its updates and the [`Layout`](crate::layout::Layout) counts its
+//! ticks, both assuming a call every millisecond. If the scan rate
+//! is variable, their notions of time diverge. A [`Clock`], advanced
+//! by the firmware, can be given to both, keeping all the timings
+//! consistent.
+//!
+//! # Example
+//!
+//! ```
+//! use keyberon::clock::Clock;
+//! use keyberon::debounce::Debouncer;
+//! use keyberon::layout::Layout;
+//!
+//! pub static LAYERS: keyberon::layout::Layers<2, 1, 1> = keyberon::layout::layout! {
+//!     { [ A B ] }
+//! };
+//! let mut layout = Layout::new(&LAYERS);
+//! let mut debouncer = Debouncer::new([[false; 2]; 1], [[false; 2]; 1], 5);
+//! let mut clock = Clock::new();
+//!
+//! // on each scan, using for example a hardware timer
+//! clock.set(4);
+//! for event in debouncer.events_with_clock([[true, false]], &clock) {
+//!     layout.event(event);
+//! }
+//! layout.tick_with_clock(&clock);
+//! ```
+
+/// A millisecond counter, advanced by the firmware.
+///
+/// The counter wraps around on overflow.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Clock(u32);
+
+impl Clock {
+    /// Creates a new clock, at 0 ms.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Returns the current time, in milliseconds.
+    pub fn now(&self) -> u32 {
+        self.0
+    }
+
+    /// Advances the clock by the given number of milliseconds.
+    pub fn advance(&mut self, ms: u32) {
+        self.0 = self.0.wrapping_add(ms);
+    }
+
+    /// Sets the current time, in milliseconds.
+    pub fn set(&mut self, now: u32) {
+        self.0 = now;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::{k, Action, Action::HoldTap, HoldTapAction, HoldTapConfig};
+    use crate::debounce::Debouncer;
+    use crate::key_code::KeyCode::*;
+    use crate::layout::{CustomEvent, Event, Layers, Layout, WaitingAction};
+
+    static LAYERS: Layers<1, 1, 1> = [[[HoldTap(&HoldTapAction {
+        timeout: 200,
+        hold: k(LCtrl),
+        tap: k(Space),
+        config: HoldTapConfig::Default,
+        tap_hold_interval: 0,
+    })]]];
+
+    /// Holds the key `held` ms, scanning every `period` ms, and
+    /// returns the hold-tap resolution.
+    fn resolution(period: u32, held: u32) -> Option<WaitingAction> {
+        let mut layout = Layout::new(&LAYERS);
+        let mut debouncer = Debouncer::new([[false]], [[false]], 5);
+        let mut clock = Clock::new();
+        let mut resolution = None;
+        while clock.now() < 500 {
+            let pressed = (10..10 + held).contains(&clock.now());
+            for event in debouncer.events_with_clock([[pressed]], &clock) {
+                layout.event(event);
+            }
+            layout.tick_with_clock(&clock);
+            if let Some(decision) = layout.take_hold_tap_decision() {
+                assert_eq!(None, resolution);
+                resolution = Some(decision.action);
+            }
+            clock.advance(period);
+        }
+        resolution
+    }
+
+    #[test]
+    fn consistent_with_scan_rate() {
+        for &period in &[1, 3, 4, 7] {
+            assert_eq!(Some(WaitingAction::Tap), resolution(period, 150));
+            assert_eq!(Some(WaitingAction::Hold), resolution(period, 250));
+        }
+    }
+
+    #[test]
+    fn custom_events() {
+        static LAYERS: Layers<1, 1, 1, u8> = [[[Action::Custom(42)]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut clock = Clock::new();
+        assert_eq!(CustomEvent::NoEvent, layout.tick_with_clock(&clock));
+        layout.event(Event::Press(0, 0));
+        layout.event(Event::Release(0, 0));
+        clock.advance(10);
+        assert_eq!(CustomEvent::Press(&42), layout.tick_with_clock(&clock));
+        assert_eq!(CustomEvent::Release(&42), layout.take_custom_event());
+        assert_eq!(CustomEvent::NoEvent, layout.take_custom_event());
+
+        // the ticks after a long suspend are clamped
+        clock.advance(u32::MAX);
+        layout.event(Event::Press(0, 0));
+        assert_eq!(CustomEvent::Press(&42), layout.tick_with_clock(&clock));
+        assert_eq!(CustomEvent::NoEvent, layout.take_custom_event());
+    }
+
+    #[test]
+    fn debounce_duration() {
+        let mut debouncer = Debouncer::new(false, false, 5);
+        let mut clock = Clock::new();
+        assert!(!debouncer.update_with_clock(true, &clock));
+        clock.advance(4);
+        assert!(!debouncer.update_with_clock(true, &clock));
+        // bounce: restart
+        assert!(!debouncer.update_with_clock(false, &clock));
+        assert!(!debouncer.update_with_clock(true, &clock));
+        clock.advance(4);
+        assert!(!debouncer.update_with_clock(true, &clock));
+        clock.advance(1);
+        assert!(debouncer.update_with_clock(true, &clock));
+        assert!(debouncer.get());
+    }
+}
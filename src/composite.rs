@@ -0,0 +1,847 @@
+//! Composite USB HID device: keyboard, mouse, consumer control,
+//! system control and raw HID.
+//!
+//! The keyboard has its own boot interface, without report ID, to be
+//! usable in the BIOS. The other enabled reports share a second
+//! interface, each with its own report ID, given by the
+//! `*_REPORT_ID` constants. A keyboard report, without LEDs, can also
+//! be sent on this shared interface.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut class = CompositeBuilder::new(&usb_bus)
+//!     .keyboard(leds)
+//!     .consumer()
+//!     .build();
+//! let mut usb_dev = keyberon::new_device(&usb_bus);
+//! // ...
+//! usb_dev.poll(&mut [&mut class]);
+//! class.write_consumer(0xe9)?; // Volume increment
+//! ```
+
+use crate::hid::{self, HidClass, HidDevice, Protocol, ReportType, Subclass};
+use crate::key_code::{Consumer, KbHidReport, MouseReport, SystemControl};
+use crate::keyboard::{Keyboard, Leds, ReportSink};
+use usb_device::bus::{StringIndex, UsbBus, UsbBusAllocator};
+use usb_device::class::{ControlIn, ControlOut, UsbClass};
+use usb_device::descriptor::DescriptorWriter;
+use usb_device::endpoint::EndpointAddress;
+
+/// The size of the raw HID reports, in both directions.
+pub const RAW_REPORT_SIZE: usize = 32;
+
+/// The report ID of the keyboard report on the shared interface.
+pub const KEYBOARD_REPORT_ID: u8 = 1;
+/// The report ID of the mouse report.
+pub const MOUSE_REPORT_ID: u8 = 2;
+/// The report ID of the consumer control report.
+pub const CONSUMER_REPORT_ID: u8 = 3;
+/// The report ID of the raw HID report.
+pub const RAW_REPORT_ID: u8 = 4;
+/// The report ID of the system control report.
+pub const SYSTEM_REPORT_ID: u8 = 5;
+
+/// The reports that can share the extra interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// Keyboard report, without LEDs (see
+    /// [`KbHidReport::as_bytes_with_id`]).
+    Keyboard,
+    /// Mouse report: buttons, x, y and wheel.
+    Mouse,
+    /// Consumer control report, containing one usage.
+    Consumer,
+    /// Vendor defined report of [`RAW_REPORT_SIZE`] bytes.
+    Raw,
+    /// System control report (power down, sleep, wake up),
+    /// containing one usage.
+    System,
+}
+
+#[rustfmt::skip]
+const KEYBOARD_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x06,        // Usage (Keyboard)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, KEYBOARD_REPORT_ID, // Report ID
+    0x05, 0x07,        //   Usage Page (Kbrd/Keypad)
+    0x19, 0xE0,        //   Usage Minimum (0xE0)
+    0x29, 0xE7,        //   Usage Maximum (0xE7)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x95, 0x08,        //   Report Count (8)
+    0x75, 0x01,        //   Report Size (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x95, 0x01,        //   Report Count (1)
+    0x75, 0x08,        //   Report Size (8)
+    0x81, 0x03,        //   Input (Const,Var,Abs)
+    0x05, 0x07,        //   Usage Page (Kbrd/Keypad)
+    0x19, 0x00,        //   Usage Minimum (0x00)
+    0x29, 0xFF,        //   Usage Maximum (0xFF)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x95, 0x06,        //   Report Count (6)
+    0x75, 0x08,        //   Report Size (8)
+    0x81, 0x00,        //   Input (Data,Array,Abs)
+    0xC0,              // End Collection
+];
+
+#[rustfmt::skip]
+const MOUSE_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x02,        // Usage (Mouse)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, MOUSE_REPORT_ID, // Report ID
+    0x09, 0x01,        //   Usage (Pointer)
+    0xA1, 0x00,        //   Collection (Physical)
+    0x05, 0x09,        //     Usage Page (Button)
+    0x19, 0x01,        //     Usage Minimum (0x01)
+    0x29, 0x05,        //     Usage Maximum (0x05)
+    0x15, 0x00,        //     Logical Minimum (0)
+    0x25, 0x01,        //     Logical Maximum (1)
+    0x95, 0x05,        //     Report Count (5)
+    0x75, 0x01,        //     Report Size (1)
+    0x81, 0x02,        //     Input (Data,Var,Abs)
+    0x95, 0x01,        //     Report Count (1)
+    0x75, 0x03,        //     Report Size (3)
+    0x81, 0x03,        //     Input (Const,Var,Abs)
+    0x05, 0x01,        //     Usage Page (Generic Desktop Ctrls)
+    0x09, 0x30,        //     Usage (X)
+    0x09, 0x31,        //     Usage (Y)
+    0x09, 0x38,        //     Usage (Wheel)
+    0x15, 0x81,        //     Logical Minimum (-127)
+    0x25, 0x7F,        //     Logical Maximum (127)
+    0x75, 0x08,        //     Report Size (8)
+    0x95, 0x03,        //     Report Count (3)
+    0x81, 0x06,        //     Input (Data,Var,Rel)
+    0xC0,              //   End Collection
+    0xC0,              // End Collection
+];
+
+#[rustfmt::skip]
+const CONSUMER_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0C,        // Usage Page (Consumer)
+    0x09, 0x01,        // Usage (Consumer Control)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, CONSUMER_REPORT_ID, // Report ID
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x03,  //   Logical Maximum (1023)
+    0x19, 0x00,        //   Usage Minimum (Unassigned)
+    0x2A, 0xFF, 0x03,  //   Usage Maximum (0x03FF)
+    0x95, 0x01,        //   Report Count (1)
+    0x75, 0x10,        //   Report Size (16)
+    0x81, 0x00,        //   Input (Data,Array,Abs)
+    0xC0,              // End Collection
+];
+
+#[rustfmt::skip]
+const RAW_DESCRIPTOR: &[u8] = &[
+    0x06, 0x60, 0xFF,  // Usage Page (Vendor Defined 0xFF60)
+    0x09, 0x61,        // Usage (0x61)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, RAW_REPORT_ID, // Report ID
+    0x09, 0x62,        //   Usage (0x62)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x95, 0x20,        //   Report Count (32)
+    0x75, 0x08,        //   Report Size (8)
+    0x81, 0x02,        //   Input (Data,Var,Abs)
+    0x09, 0x63,        //   Usage (0x63)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xFF, 0x00,  //   Logical Maximum (255)
+    0x95, 0x20,        //   Report Count (32)
+    0x75, 0x08,        //   Report Size (8)
+    0x91, 0x02,        //   Output (Data,Var,Abs)
+    0xC0,              // End Collection
+];
+
+#[rustfmt::skip]
+const SYSTEM_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x80,        // Usage (Sys Control)
+    0xA1, 0x01,        // Collection (Application)
+    0x85, SYSTEM_REPORT_ID, // Report ID
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x26, 0xB7, 0x00,  //   Logical Maximum (183)
+    0x19, 0x00,        //   Usage Minimum (Undefined)
+    0x29, 0xB7,        //   Usage Maximum (Sys Display LCD Autoscale)
+    0x95, 0x01,        //   Report Count (1)
+    0x75, 0x08,        //   Report Size (8)
+    0x81, 0x00,        //   Input (Data,Array,Abs)
+    0xC0,              // End Collection
+];
+
+const DESCRIPTOR_CAPACITY: usize = KEYBOARD_DESCRIPTOR.len()
+    + MOUSE_DESCRIPTOR.len()
+    + CONSUMER_DESCRIPTOR.len()
+    + RAW_DESCRIPTOR.len()
+    + SYSTEM_DESCRIPTOR.len();
+
+impl Report {
+    /// Returns the report ID of the report.
+    pub fn id(self) -> u8 {
+        match self {
+            Report::Keyboard => KEYBOARD_REPORT_ID,
+            Report::Mouse => MOUSE_REPORT_ID,
+            Report::Consumer => CONSUMER_REPORT_ID,
+            Report::Raw => RAW_REPORT_ID,
+            Report::System => SYSTEM_REPORT_ID,
+        }
+    }
+
+    fn descriptor(self) -> &'static [u8] {
+        match self {
+            Report::Keyboard => KEYBOARD_DESCRIPTOR,
+            Report::Mouse => MOUSE_DESCRIPTOR,
+            Report::Consumer => CONSUMER_DESCRIPTOR,
+            Report::Raw => RAW_DESCRIPTOR,
+            Report::System => SYSTEM_DESCRIPTOR,
+        }
+    }
+}
+
+/// A consumer control report, containing one usage, 0 meaning no
+/// usage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConsumerReport(pub u16);
+
+impl ConsumerReport {
+    /// Returns the bytes of the report, prefixed by
+    /// [`CONSUMER_REPORT_ID`].
+    pub fn as_bytes_with_id(&self) -> [u8; 3] {
+        let [lo, hi] = self.0.to_le_bytes();
+        [CONSUMER_REPORT_ID, lo, hi]
+    }
+}
+
+impl From<Consumer> for ConsumerReport {
+    fn from(usage: Consumer) -> Self {
+        ConsumerReport(usage as u16)
+    }
+}
+
+/// A system control report, containing one usage, 0 meaning no
+/// usage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemControlReport(pub u8);
+
+impl SystemControlReport {
+    /// Returns the bytes of the report, prefixed by
+    /// [`SYSTEM_REPORT_ID`].
+    pub fn as_bytes_with_id(&self) -> [u8; 2] {
+        [SYSTEM_REPORT_ID, self.0]
+    }
+}
+
+impl From<SystemControl> for SystemControlReport {
+    fn from(usage: SystemControl) -> Self {
+        SystemControlReport(usage as u8)
+    }
+}
+
+/// The HID device of the extra interface, sending the reports with
+/// their report IDs.
+pub struct Extra {
+    reports: heapless::Vec<Report, 5>,
+    descriptor: heapless::Vec<u8, DESCRIPTOR_CAPACITY>,
+    raw_received: Option<[u8; RAW_REPORT_SIZE]>,
+    keyboard_report: KbHidReport,
+}
+
+impl Extra {
+    fn new(reports: heapless::Vec<Report, 5>) -> Self {
+        let mut descriptor = heapless::Vec::new();
+        for report in &reports {
+            let _ = descriptor.extend_from_slice(report.descriptor());
+        }
+        Extra {
+            reports,
+            descriptor,
+            raw_received: None,
+            keyboard_report: KbHidReport::default(),
+        }
+    }
+
+    /// Sets the keyboard report sent on the shared interface. Returns
+    /// `true` if it is modified.
+    fn set_keyboard_report(&mut self, report: KbHidReport) -> bool {
+        if report == self.keyboard_report {
+            false
+        } else {
+            self.keyboard_report = report;
+            true
+        }
+    }
+
+    /// Returns the report ID of the given report, if enabled.
+    pub fn report_id(&self, report: Report) -> Option<u8> {
+        if self.reports.contains(&report) {
+            Some(report.id())
+        } else {
+            None
+        }
+    }
+}
+
+impl HidDevice for Extra {
+    fn subclass(&self) -> Subclass {
+        Subclass::None
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::None
+    }
+
+    fn max_packet_size(&self) -> u16 {
+        64
+    }
+
+    fn report_descriptor(&self) -> &[u8] {
+        &self.descriptor
+    }
+
+    fn get_report(
+        &mut self,
+        _report_type: ReportType,
+        _report_id: u8,
+    ) -> Result<&[u8], hid::Error> {
+        Err(hid::Error)
+    }
+
+    fn set_report(
+        &mut self,
+        report_type: ReportType,
+        report_id: u8,
+        data: &[u8],
+    ) -> Result<(), hid::Error> {
+        let raw_id = self.report_id(Report::Raw);
+        match data.split_first() {
+            Some((&id, data))
+                if report_type == ReportType::Output
+                    && Some(report_id) == raw_id
+                    && id == report_id
+                    && data.len() == RAW_REPORT_SIZE =>
+            {
+                let mut report = [0; RAW_REPORT_SIZE];
+                report.copy_from_slice(data);
+                self.raw_received = Some(report);
+                Ok(())
+            }
+            _ => Err(hid::Error),
+        }
+    }
+}
+
+/// A builder for a [`CompositeClass`].
+pub struct CompositeBuilder<'a, B: UsbBus, L> {
+    alloc: &'a UsbBusAllocator<B>,
+    leds: Option<L>,
+    reports: heapless::Vec<Report, 5>,
+}
+
+impl<'a, B: UsbBus, L: Leds> CompositeBuilder<'a, B, L> {
+    /// Creates a new builder, with no interface enabled.
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Self {
+            alloc,
+            leds: None,
+            reports: heapless::Vec::new(),
+        }
+    }
+
+    /// Enables the keyboard interface.
+    pub fn keyboard(mut self, leds: L) -> Self {
+        self.leds = Some(leds);
+        self
+    }
+
+    /// Enables the keyboard report on the shared interface. Contrary
+    /// to the keyboard interface, it doesn't support the boot
+    /// protocol and the LEDs, but it doesn't need its own interface.
+    pub fn keyboard_report(self) -> Self {
+        self.report(Report::Keyboard)
+    }
+
+    /// Enables the mouse report.
+    pub fn mouse(self) -> Self {
+        self.report(Report::Mouse)
+    }
+
+    /// Enables the consumer control report.
+    pub fn consumer(self) -> Self {
+        self.report(Report::Consumer)
+    }
+
+    /// Enables the raw HID report.
+    pub fn raw(self) -> Self {
+        self.report(Report::Raw)
+    }
+
+    /// Enables the system control report.
+    pub fn system(self) -> Self {
+        self.report(Report::System)
+    }
+
+    fn report(mut self, report: Report) -> Self {
+        if !self.reports.contains(&report) {
+            let _ = self.reports.push(report);
+        }
+        self
+    }
+
+    /// Builds the class, allocating its interfaces and endpoints.
+    pub fn build(mut self) -> CompositeClass<'a, B, L> {
+        let keyboard = self
+            .leds
+            .take()
+            .map(|leds| HidClass::new(Keyboard::new(leds), self.alloc));
+        self.reports.sort_unstable_by_key(|&r| r as u8);
+        let extra = if self.reports.is_empty() {
+            None
+        } else {
+            Some(HidClass::new(Extra::new(self.reports), self.alloc))
+        };
+        CompositeClass { keyboard, extra }
+    }
+}
+
+/// A USB class composed of a keyboard interface and an interface
+/// for the other reports. Built using a [`CompositeBuilder`].
+pub struct CompositeClass<'a, B: UsbBus, L: Leds> {
+    keyboard: Option<HidClass<'a, B, Keyboard<L>>>,
+    extra: Option<HidClass<'a, B, Extra>>,
+}
+
+impl<B: UsbBus, L: Leds> CompositeClass<'_, B, L> {
+    /// Returns the number of allocated interfaces.
+    pub fn nb_interfaces(&self) -> usize {
+        self.keyboard.iter().count() + self.extra.iter().count()
+    }
+
+    /// Returns the report ID of the given report, if enabled.
+    pub fn report_id(&self, report: Report) -> Option<u8> {
+        self.extra.as_ref()?.device().report_id(report)
+    }
+
+    /// Returns the keyboard device, if enabled.
+    pub fn keyboard_mut(&mut self) -> Option<&mut Keyboard<L>> {
+        self.keyboard.as_mut().map(HidClass::device_mut)
+    }
+
+    /// Sends a keyboard report if it is modified, on the keyboard
+    /// interface if enabled, else on the shared interface. Returns
+    /// the number of bytes written.
+    pub fn write_keyboard(&mut self, report: KbHidReport) -> Result<usize, hid::Error> {
+        match self.keyboard.as_mut() {
+            Some(keyboard) => {
+                if keyboard.device_mut().set_keyboard_report(report.clone()) {
+                    keyboard.write(report.as_bytes())
+                } else {
+                    Ok(0)
+                }
+            }
+            None => {
+                let extra = self.extra.as_mut().ok_or(hid::Error)?;
+                extra
+                    .device()
+                    .report_id(Report::Keyboard)
+                    .ok_or(hid::Error)?;
+                if extra.device_mut().set_keyboard_report(report.clone()) {
+                    extra.write(&report.as_bytes_with_id())
+                } else {
+                    Ok(0)
+                }
+            }
+        }
+    }
+
+    /// A time event, to call every millisecond, resending the
+    /// keyboard report on the keyboard interface according to the
+    /// idle rate set by the host (see
+    /// [`HidClass::tick`](crate::hid::HidClass::tick)).
+    pub fn tick(&mut self) -> Result<usize, hid::Error> {
+        match self.keyboard.as_mut() {
+            Some(keyboard) => keyboard.tick(),
+            None => Ok(0),
+        }
+    }
+
+    /// Sends a mouse report.
+    pub fn write_mouse(&mut self, report: MouseReport) -> Result<usize, hid::Error> {
+        self.write_report(Report::Mouse, &report.as_bytes_with_id())
+    }
+
+    /// Sends a consumer control report with the given usage, 0
+    /// meaning no usage.
+    pub fn write_consumer(&mut self, usage: u16) -> Result<usize, hid::Error> {
+        self.write_report(Report::Consumer, &ConsumerReport(usage).as_bytes_with_id())
+    }
+
+    /// Sends a system control report with the given usage, 0 meaning
+    /// no usage.
+    pub fn write_system(&mut self, usage: u8) -> Result<usize, hid::Error> {
+        self.write_report(
+            Report::System,
+            &SystemControlReport(usage).as_bytes_with_id(),
+        )
+    }
+
+    /// Sends a raw HID report.
+    pub fn write_raw(&mut self, data: &[u8; RAW_REPORT_SIZE]) -> Result<usize, hid::Error> {
+        let mut bytes = [RAW_REPORT_ID; RAW_REPORT_SIZE + 1];
+        bytes[1..].copy_from_slice(data);
+        self.write_report(Report::Raw, &bytes)
+    }
+
+    /// Returns the last raw HID report received from the host, if
+    /// not already read.
+    pub fn read_raw(&mut self) -> Option<[u8; RAW_REPORT_SIZE]> {
+        self.extra.as_mut()?.device_mut().raw_received.take()
+    }
+
+    fn write_report(&mut self, report: Report, bytes: &[u8]) -> Result<usize, hid::Error> {
+        let extra = self.extra.as_mut().ok_or(hid::Error)?;
+        extra.device().report_id(report).ok_or(hid::Error)?;
+        extra.write(bytes)
+    }
+
+    fn class_for(&mut self, interface: u16) -> Option<&mut dyn UsbClass<B>> {
+        if let Some(k) = self
+            .keyboard
+            .as_mut()
+            .filter(|k| k.interface_index() == interface)
+        {
+            return Some(k);
+        }
+        if let Some(e) = self
+            .extra
+            .as_mut()
+            .filter(|e| e.interface_index() == interface)
+        {
+            return Some(e);
+        }
+        None
+    }
+
+    fn classes(&mut self) -> impl Iterator<Item = &mut dyn UsbClass<B>> {
+        let keyboard = self.keyboard.as_mut().map(|c| c as &mut dyn UsbClass<B>);
+        let extra = self.extra.as_mut().map(|c| c as &mut dyn UsbClass<B>);
+        keyboard.into_iter().chain(extra)
+    }
+}
+
+impl<B: UsbBus, L: Leds> UsbClass<B> for CompositeClass<'_, B, L> {
+    fn poll(&mut self) {
+        self.classes().for_each(|c| c.poll());
+    }
+
+    fn reset(&mut self) {
+        self.classes().for_each(|c| c.reset());
+    }
+
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        if let Some(k) = &self.keyboard {
+            k.get_configuration_descriptors(writer)?;
+        }
+        if let Some(e) = &self.extra {
+            e.get_configuration_descriptors(writer)?;
+        }
+        Ok(())
+    }
+
+    fn get_string(&self, _index: StringIndex, _lang_id: u16) -> Option<&str> {
+        None
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        self.classes().for_each(|c| c.endpoint_in_complete(addr));
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        self.classes().for_each(|c| c.endpoint_out(addr));
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        if let Some(c) = self.class_for(xfer.request().index) {
+            c.control_in(xfer);
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        if let Some(c) = self.class_for(xfer.request().index) {
+            c.control_out(xfer);
+        }
+    }
+}
+
+impl<B: UsbBus, L: Leds> ReportSink for CompositeClass<'_, B, L> {
+    fn send_keyboard(&mut self, report: &KbHidReport) -> Result<(), hid::Error> {
+        self.write_keyboard(report.clone()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use crate::key_code::KeyCode::{self, *};
+    use crate::keyboard::ReportSink;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+    use usb_device::bus::PollResult;
+    use usb_device::endpoint::EndpointType;
+    use usb_device::UsbDirection;
+
+    #[derive(Default)]
+    struct Bus {
+        nb_endpoints: usize,
+        /// The data written on the endpoints.
+        written: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+    impl UsbBus for Bus {
+        fn alloc_ep(
+            &mut self,
+            ep_dir: UsbDirection,
+            _ep_addr: Option<EndpointAddress>,
+            _ep_type: EndpointType,
+            _max_packet_size: u16,
+            _interval: u8,
+        ) -> usb_device::Result<EndpointAddress> {
+            self.nb_endpoints += 1;
+            Ok(EndpointAddress::from_parts(self.nb_endpoints, ep_dir))
+        }
+        fn enable(&mut self) {}
+        fn reset(&self) {}
+        fn set_device_address(&self, _addr: u8) {}
+        fn write(&self, _ep_addr: EndpointAddress, buf: &[u8]) -> usb_device::Result<usize> {
+            self.written.lock().unwrap().push(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn read(&self, _ep_addr: EndpointAddress, _buf: &mut [u8]) -> usb_device::Result<usize> {
+            Ok(0)
+        }
+        fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+        fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+            false
+        }
+        fn suspend(&self) {}
+        fn resume(&self) {}
+        fn poll(&self) -> PollResult {
+            PollResult::None
+        }
+    }
+
+    #[test]
+    fn keyboard_and_consumer() {
+        let alloc = UsbBusAllocator::new(Bus::default());
+        let mut class = CompositeBuilder::new(&alloc)
+            .consumer()
+            .keyboard(())
+            .build();
+        assert_eq!(2, class.nb_interfaces());
+        assert_eq!(None, class.report_id(Report::Mouse));
+        assert_eq!(Some(CONSUMER_REPORT_ID), class.report_id(Report::Consumer));
+        assert_eq!(None, class.report_id(Report::Raw));
+        assert_eq!(
+            CONSUMER_DESCRIPTOR.len(),
+            class
+                .extra
+                .as_ref()
+                .unwrap()
+                .device()
+                .report_descriptor()
+                .len()
+        );
+        let device = crate::new_device(&alloc);
+        assert!(!crate::wakeup_host(&device, |_| panic!("not suspended")));
+        assert_eq!(Ok(3), class.write_consumer(0xe9));
+        assert!(class.write_mouse(MouseReport::default()).is_err());
+        assert!(class.keyboard_mut().is_some());
+    }
+
+    #[test]
+    fn report_ids() {
+        let alloc = UsbBusAllocator::new(Bus::default());
+        let mut class = CompositeBuilder::<_, ()>::new(&alloc)
+            .raw()
+            .mouse()
+            .keyboard_report()
+            .consumer()
+            .system()
+            .build();
+        assert_eq!(1, class.nb_interfaces());
+        assert_eq!(Some(KEYBOARD_REPORT_ID), class.report_id(Report::Keyboard));
+        assert_eq!(Some(MOUSE_REPORT_ID), class.report_id(Report::Mouse));
+        assert_eq!(Some(CONSUMER_REPORT_ID), class.report_id(Report::Consumer));
+        assert_eq!(Some(RAW_REPORT_ID), class.report_id(Report::Raw));
+        assert_eq!(Some(SYSTEM_REPORT_ID), class.report_id(Report::System));
+        let descriptor = class.extra.as_ref().unwrap().device().report_descriptor();
+        for report in [
+            Report::Keyboard,
+            Report::Mouse,
+            Report::Consumer,
+            Report::Raw,
+            Report::System,
+        ] {
+            assert!(descriptor.windows(2).any(|w| w == [0x85, report.id()]));
+        }
+        let config = crate::UsbConfig {
+            pid: 0x27dc,
+            product: "Keyberon composite",
+            ..crate::UsbConfig::default()
+        };
+        let _device = crate::new_device_with(&alloc, config);
+        let report = core::iter::once(KeyCode::A).collect();
+        assert_eq!(Ok(9), class.write_keyboard(report));
+        assert!(class.write_system(SystemControl::Sleep as u8).is_ok());
+    }
+
+    /// Sends reports through a sink, completing the transfers on the
+    /// given endpoint, and returns the data written on the bus.
+    fn send_reports<S: ReportSink + UsbClass<Bus>>(
+        sink: &mut S,
+        ep: usize,
+        written: &Mutex<Vec<Vec<u8>>>,
+    ) -> Vec<Vec<u8>> {
+        written.lock().unwrap().clear();
+        for keys in [&[A][..], &[A], &[A, LShift], &[]] {
+            let report: KbHidReport = keys.iter().copied().collect();
+            (sink as &mut dyn ReportSink)
+                .send_keyboard(&report)
+                .unwrap();
+            sink.endpoint_in_complete(EndpointAddress::from_parts(ep, UsbDirection::In));
+        }
+        core::mem::take(&mut *written.lock().unwrap())
+    }
+
+    #[test]
+    fn report_sink() {
+        let written = Arc::default();
+        let bus = Bus {
+            written: Arc::clone(&written),
+            ..Bus::default()
+        };
+        let alloc = UsbBusAllocator::new(bus);
+        let mut keyboard = crate::new_class(&alloc, ());
+        let mut composite = CompositeBuilder::new(&alloc).keyboard(()).build();
+        let mut shared = CompositeBuilder::<_, ()>::new(&alloc)
+            .keyboard_report()
+            .build();
+        let _device = crate::new_device(&alloc);
+        let expected: Vec<KbHidReport> = [&[A][..], &[A, LShift], &[]]
+            .iter()
+            .map(|keys| keys.iter().copied().collect())
+            .collect();
+
+        // the endpoints are allocated in order, and the same report
+        // is sent only once
+        let bytes: Vec<_> = expected.iter().map(|r| r.as_bytes().to_vec()).collect();
+        assert_eq!(bytes, send_reports(&mut keyboard, 1, &written));
+        assert_eq!(bytes, send_reports(&mut composite, 2, &written));
+        let bytes: Vec<_> = expected
+            .iter()
+            .map(|r| r.as_bytes_with_id().to_vec())
+            .collect();
+        assert_eq!(bytes, send_reports(&mut shared, 3, &written));
+    }
+
+    #[test]
+    fn boot_protocol() {
+        let alloc = UsbBusAllocator::new(Bus::default());
+        let mut class = CompositeBuilder::new(&alloc)
+            .keyboard(())
+            .consumer()
+            .build();
+        let _device = crate::new_device(&alloc);
+        let keyboard = class.keyboard.as_mut().unwrap();
+        assert!(!keyboard.is_boot_protocol());
+        assert!(keyboard.set_protocol(0).is_ok());
+        assert!(keyboard.is_boot_protocol());
+        assert!(keyboard.set_protocol(2).is_err());
+        assert!(keyboard.is_boot_protocol());
+        let report = core::iter::once(crate::key_code::KeyCode::A).collect();
+        assert_eq!(Ok(8), class.write_keyboard(report));
+        let keyboard = class.keyboard.as_mut().unwrap();
+        assert!(keyboard.set_protocol(1).is_ok());
+        assert!(!keyboard.is_boot_protocol());
+        keyboard.set_protocol(0).unwrap();
+        keyboard.reset();
+        assert!(!keyboard.is_boot_protocol());
+
+        // the shared interface doesn't support the boot protocol
+        assert!(class.extra.as_mut().unwrap().set_protocol(0).is_err());
+    }
+
+    #[test]
+    fn idle() {
+        let alloc = UsbBusAllocator::new(Bus::default());
+        let mut class = CompositeBuilder::new(&alloc).keyboard(()).build();
+        let _device = crate::new_device(&alloc);
+        let keyboard = class.keyboard.as_mut().unwrap();
+        let ep = EndpointAddress::from_parts(1, UsbDirection::In);
+
+        // only on change by default
+        for _ in 0..1000 {
+            assert_eq!(Ok(0), keyboard.tick());
+        }
+
+        // 500 ms
+        keyboard.set_idle(125 << 8);
+        assert_eq!(125, keyboard.idle());
+        for _ in 0..499 {
+            assert_eq!(Ok(0), keyboard.tick());
+        }
+        assert_eq!(Ok(8), keyboard.tick());
+        keyboard.endpoint_in_complete(ep);
+
+        // a report sent restarts the idle timer
+        for _ in 0..250 {
+            assert_eq!(Ok(0), keyboard.tick());
+        }
+        let report = core::iter::once(crate::key_code::KeyCode::A).collect();
+        assert_eq!(Ok(8), class.write_keyboard(report));
+        let keyboard = class.keyboard.as_mut().unwrap();
+        keyboard.endpoint_in_complete(ep);
+        for _ in 0..499 {
+            assert_eq!(Ok(0), keyboard.tick());
+        }
+        assert_eq!(Ok(8), keyboard.tick());
+        keyboard.endpoint_in_complete(ep);
+
+        keyboard.set_idle(0);
+        for _ in 0..1000 {
+            assert_eq!(Ok(0), class.tick());
+        }
+    }
+
+    #[test]
+    fn as_bytes_with_id() {
+        assert_eq!(
+            KEYBOARD_REPORT_ID,
+            KbHidReport::default().as_bytes_with_id()[0]
+        );
+        let mouse = MouseReport {
+            buttons: 1,
+            x: -1,
+            y: 2,
+            wheel: 0,
+        };
+        assert_eq!([MOUSE_REPORT_ID, 1, 0xff, 2, 0], mouse.as_bytes_with_id());
+        assert_eq!(
+            [CONSUMER_REPORT_ID, 0xe9, 0],
+            ConsumerReport(0xe9).as_bytes_with_id()
+        );
+        assert_eq!(
+            [CONSUMER_REPORT_ID, 0x92, 0x01],
+            ConsumerReport::from(Consumer::Calculator).as_bytes_with_id()
+        );
+        assert_eq!(
+            [SYSTEM_REPORT_ID, 0x82],
+            SystemControlReport::from(SystemControl::Sleep).as_bytes_with_id()
+        );
+    }
+}
@@ -5,8 +5,17 @@
 //! implementation validate the state change when the state is stable
 //! during a configurable number of update. 5 ms is the recommended
 //! duration for keyboard switches.
+//!
+//! Using a [`Clock`], the state change is validated when the state is
+//! stable during a number of milliseconds instead, whatever the scan
+//! rate is.
+//!
+//! The [`PerKeyDebouncer`] debounces each key of a matrix
+//! independently.
 
+use crate::clock::Clock;
 use crate::layout::Event;
+use crate::matrix::PressedKeys;
 use either::Either::*;
 
 /// The debouncer type.
@@ -14,7 +23,10 @@ pub struct Debouncer<T> {
     cur: T,
     new: T,
     since: u16,
+    changed_at: Option<u32>,
     nb_bounce: u16,
+    nb_bounce_release: u16,
+    is_release: Option<fn(&T, &T) -> bool>,
 }
 
 impl<T> Debouncer<T> {
@@ -25,23 +37,66 @@ impl<T> Debouncer<T> {
     /// `const fn` and allow non clonable types to be used.
     ///
     /// `nb_bounce` correspond to the number of update with same state
-    /// needed to validate the new state.
+    /// needed to validate the new state, or to the number of
+    /// milliseconds when used with a [`Clock`].
     pub const fn new(cur: T, new: T, nb_bounce: u16) -> Self {
         Self {
             cur,
             new,
             since: 0,
+            changed_at: None,
             nb_bounce,
+            nb_bounce_release: nb_bounce,
+            is_release: None,
+        }
+    }
+}
+
+impl<const CS: usize, const RS: usize> Debouncer<[[bool; CS]; RS]> {
+    /// Create a new debouncer for a key matrix, with different
+    /// timings for the presses and the releases, as some switches
+    /// bounce more on release.
+    ///
+    /// A new state only releasing keys is validated after
+    /// `nb_bounce_release` updates (or milliseconds), any other new
+    /// state after `nb_bounce_press` updates.
+    pub const fn with_timings(
+        cur: [[bool; CS]; RS],
+        new: [[bool; CS]; RS],
+        nb_bounce_press: u16,
+        nb_bounce_release: u16,
+    ) -> Self {
+        Self {
+            cur,
+            new,
+            since: 0,
+            changed_at: None,
+            nb_bounce: nb_bounce_press,
+            nb_bounce_release,
+            is_release: Some(|cur, new| {
+                cur.iter()
+                    .flatten()
+                    .zip(new.iter().flatten())
+                    .all(|(&c, &n)| c || !n)
+            }),
         }
     }
 }
 
 impl<T: PartialEq> Debouncer<T> {
-    /// Gets the current state.
+    /// Gets the current state, i.e. the debounced state, as the
+    /// pressed keys for a key matrix.
     pub fn get(&self) -> &T {
         &self.cur
     }
 
+    fn nb_bounce(&self, new: &T) -> u16 {
+        match self.is_release {
+            Some(is_release) if is_release(&self.cur, new) => self.nb_bounce_release,
+            _ => self.nb_bounce,
+        }
+    }
+
     /// Updates the current state.  Returns `true` if the state changes.
     pub fn update(&mut self, new: T) -> bool {
         if self.cur == new {
@@ -56,7 +111,7 @@ impl<T: PartialEq> Debouncer<T> {
             self.since += 1;
         }
 
-        if self.since > self.nb_bounce {
+        if self.since > self.nb_bounce(&self.new) {
             core::mem::swap(&mut self.cur, &mut self.new);
             self.since = 0;
             true
@@ -65,6 +120,56 @@ impl<T: PartialEq> Debouncer<T> {
         }
     }
 
+    /// Updates the current state, using the given clock. Returns
+    /// `true` if the state changes.
+    ///
+    /// The new state is validated when it is stable during
+    /// `nb_bounce` milliseconds.
+    pub fn update_with_clock(&mut self, new: T, clock: &Clock) -> bool {
+        let now = clock.now();
+        if self.cur == new {
+            self.changed_at = None;
+            return false;
+        }
+
+        let changed_at = match self.changed_at {
+            Some(changed_at) if self.new == new => changed_at,
+            _ => {
+                self.new = new;
+                self.changed_at = Some(now);
+                now
+            }
+        };
+
+        if now.wrapping_sub(changed_at) >= u32::from(self.nb_bounce(&self.new)) {
+            core::mem::swap(&mut self.cur, &mut self.new);
+            self.changed_at = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Iterates on the `Event`s generated by the update, using the
+    /// given clock (see [`update_with_clock`](Self::update_with_clock)).
+    ///
+    /// `T` must be some kind of array of array of bool.
+    ///
+    /// Panics if the coordinates doesn't fit in a `(u8, u8)`.
+    pub fn events_with_clock<'a, U>(
+        &'a mut self,
+        new: T,
+        clock: &Clock,
+    ) -> impl Iterator<Item = Event> + 'a
+    where
+        &'a T: IntoIterator<Item = U>,
+        U: IntoIterator<Item = &'a bool>,
+        U::IntoIter: 'a,
+    {
+        let changed = self.update_with_clock(new, clock);
+        self.changes(changed)
+    }
+
     /// Iterates on the `Event`s generated by the update.
     ///
     /// `T` must be some kind of array of array of bool.
@@ -101,20 +206,31 @@ impl<T: PartialEq> Debouncer<T> {
         U: IntoIterator<Item = &'a bool>,
         U::IntoIter: 'a,
     {
-        if self.update(new) {
+        let changed = self.update(new);
+        self.changes(changed)
+    }
+
+    fn changes<'a, U>(&'a self, changed: bool) -> impl Iterator<Item = Event> + 'a
+    where
+        &'a T: IntoIterator<Item = U>,
+        U: IntoIterator<Item = &'a bool>,
+        U::IntoIter: 'a,
+    {
+        if changed {
             Left(
                 self.new
                     .into_iter()
-                    .zip(self.cur.into_iter())
+                    .zip(&self.cur)
                     .enumerate()
                     .flat_map(move |(i, (o, n))| {
-                        o.into_iter().zip(n.into_iter()).enumerate().filter_map(
-                            move |(j, bools)| match bools {
+                        o.into_iter()
+                            .zip(n)
+                            .enumerate()
+                            .filter_map(move |(j, bools)| match bools {
                                 (false, true) => Some(Event::Press(i as u8, j as u8)),
                                 (true, false) => Some(Event::Release(i as u8, j as u8)),
                                 _ => None,
-                            },
-                        )
+                            })
                     }),
             )
         } else {
@@ -122,3 +238,154 @@ impl<T: PartialEq> Debouncer<T> {
         }
     }
 }
+
+/// A debouncer for a key matrix, with a counter per key.
+///
+/// Contrary to [`Debouncer`], that waits for the whole matrix to be
+/// stable, each key is debounced independently: a bouncing key
+/// doesn't delay the events of the other keys.
+pub struct PerKeyDebouncer<const CS: usize, const RS: usize> {
+    cur: PressedKeys<CS, RS>,
+    changed: PressedKeys<CS, RS>,
+    counters: [[u16; CS]; RS],
+    nb_bounce: u16,
+}
+
+impl<const CS: usize, const RS: usize> PerKeyDebouncer<CS, RS> {
+    /// Creates a new debouncer, all the keys being released.
+    ///
+    /// `nb_bounce` correspond to the number of update with same state
+    /// of a key needed to validate its new state.
+    pub const fn new(nb_bounce: u16) -> Self {
+        Self {
+            cur: [[false; CS]; RS],
+            changed: [[false; CS]; RS],
+            counters: [[0; CS]; RS],
+            nb_bounce,
+        }
+    }
+
+    /// Gets the current state.
+    pub fn get(&self) -> &PressedKeys<CS, RS> {
+        &self.cur
+    }
+
+    /// Updates the current state. Returns `true` if the state of a
+    /// key changes.
+    pub fn update(&mut self, new: PressedKeys<CS, RS>) -> bool {
+        let mut res = false;
+        let keys = self.cur.iter_mut().flatten().zip(new.iter().flatten());
+        let counters = self.counters.iter_mut().flatten();
+        let changed = self.changed.iter_mut().flatten();
+        for (((cur, &new), counter), changed) in keys.zip(counters).zip(changed) {
+            *changed = false;
+            if *cur == new {
+                *counter = 0;
+                continue;
+            }
+            *counter += 1;
+            if *counter > self.nb_bounce {
+                *cur = new;
+                *counter = 0;
+                *changed = true;
+                res = true;
+            }
+        }
+        res
+    }
+
+    /// Iterates on the `Event`s generated by the update.
+    pub fn events(&mut self, new: PressedKeys<CS, RS>) -> impl Iterator<Item = Event> + '_ {
+        self.update(new);
+        let cur = &self.cur;
+        self.changed.iter().enumerate().flat_map(move |(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, &changed)| changed)
+                .map(move |(j, _)| {
+                    if cur[i][j] {
+                        Event::Press(i as u8, j as u8)
+                    } else {
+                        Event::Release(i as u8, j as u8)
+                    }
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    #[test]
+    fn events_coordinates() {
+        let mut debouncer = Debouncer::new([[false; 3]; 2], [[false; 3]; 2], 0);
+        let old = [[true, false, true], [false, true, false]];
+        let new = [[false, false, true], [true, true, true]];
+        assert_eq!(3, debouncer.events(old).count());
+        assert_eq!(&old, debouncer.get());
+        let events: Vec<_> = debouncer.events(new).collect();
+        assert_eq!(
+            vec![Event::Release(0, 0), Event::Press(1, 0), Event::Press(1, 2)],
+            events
+        );
+        assert_eq!(&new, debouncer.get());
+    }
+
+    #[test]
+    fn timings() {
+        let mut debouncer = Debouncer::with_timings([[false; 2]], [[false; 2]], 1, 3);
+        let mut scan = |keys: [[bool; 2]; 1]| debouncer.events(keys).collect::<Vec<_>>();
+
+        // noisy press, validated after 2 stable scans
+        assert!(scan([[true, false]]).is_empty());
+        assert!(scan([[false, false]]).is_empty());
+        assert!(scan([[true, false]]).is_empty());
+        assert_eq!(vec![Event::Press(0, 0)], scan([[true, false]]));
+
+        // noisy release, validated after 4 stable scans
+        assert!(scan([[false, false]]).is_empty());
+        assert!(scan([[true, false]]).is_empty());
+        for _ in 0..3 {
+            assert!(scan([[false, false]]).is_empty());
+        }
+        assert_eq!(vec![Event::Release(0, 0)], scan([[false, false]]));
+
+        // a release with a press uses the press timing
+        assert!(scan([[true, false]]).is_empty());
+        assert_eq!(vec![Event::Press(0, 0)], scan([[true, false]]));
+        assert!(scan([[false, true]]).is_empty());
+        assert_eq!(
+            vec![Event::Release(0, 0), Event::Press(0, 1)],
+            scan([[false, true]])
+        );
+    }
+
+    #[test]
+    fn per_key() {
+        let mut debouncer = PerKeyDebouncer::<2, 1>::new(2);
+        // A bounces every scan, B is pressed and stable
+        let mut events = Vec::new();
+        for i in 0..10 {
+            let a = i % 2 == 0;
+            events.push(debouncer.events([[a, true]]).collect::<Vec<_>>());
+        }
+        assert_eq!(vec![Event::Press(0, 1)], events[2]);
+        assert!(events
+            .iter()
+            .enumerate()
+            .all(|(i, e)| i == 2 || e.is_empty()));
+        assert_eq!(&[[false, true]], debouncer.get());
+
+        // A is stable, released
+        assert!(debouncer.events([[true, false]]).next().is_none());
+        assert!(debouncer.events([[true, false]]).next().is_none());
+        assert_eq!(
+            vec![Event::Press(0, 0), Event::Release(0, 1)],
+            debouncer.events([[true, false]]).collect::<Vec<_>>()
+        );
+    }
+}
@@ -14,6 +14,7 @@ use usb_device::UsbError;
 const SPECIFICATION_RELEASE: u16 = 0x111;
 const INTERFACE_CLASS_HID: u8 = 0x03;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Error;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -107,6 +108,9 @@ pub struct HidClass<'a, B: UsbBus, D: HidDevice> {
     interface: InterfaceNumber,
     endpoint_interrupt_in: EndpointIn<'a, B>,
     expect_interrupt_in_complete: bool,
+    boot_protocol: bool,
+    idle: u8,
+    idle_elapsed: u16,
 }
 
 impl<B: UsbBus, D: HidDevice> HidClass<'_, B, D> {
@@ -117,6 +121,9 @@ impl<B: UsbBus, D: HidDevice> HidClass<'_, B, D> {
             interface: alloc.interface(),
             endpoint_interrupt_in: alloc.interrupt(max_packet_size, 10),
             expect_interrupt_in_complete: false,
+            boot_protocol: false,
+            idle: 0,
+            idle_elapsed: 0,
         }
     }
 
@@ -127,13 +134,77 @@ impl<B: UsbBus, D: HidDevice> HidClass<'_, B, D> {
             interface: alloc.interface(),
             endpoint_interrupt_in: alloc.interrupt(max_packet_size, interval),
             expect_interrupt_in_complete: false,
+            boot_protocol: false,
+            idle: 0,
+            idle_elapsed: 0,
         }
     }
 
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
     pub fn device_mut(&mut self) -> &mut D {
         &mut self.device
     }
 
+    /// Returns `true` if the host selected the boot protocol, as a
+    /// BIOS. Only the devices of the boot interface subclass support
+    /// it, the report being then the 8 bytes boot report, as
+    /// [`KbHidReport`](crate::key_code::KbHidReport).
+    pub fn is_boot_protocol(&self) -> bool {
+        self.boot_protocol
+    }
+
+    /// Handles a SET_PROTOCOL request, 0 being the boot protocol and
+    /// 1 the report protocol.
+    pub fn set_protocol(&mut self, protocol: u16) -> Result<(), Error> {
+        if self.device.subclass() != Subclass::BootInterface {
+            return Err(Error);
+        }
+        match protocol {
+            0 => self.boot_protocol = true,
+            1 => self.boot_protocol = false,
+            _ => return Err(Error),
+        }
+        Ok(())
+    }
+
+    /// Returns the idle rate set by the host, in units of 4 ms, 0
+    /// meaning that the reports are only sent on change.
+    pub fn idle(&self) -> u8 {
+        self.idle
+    }
+
+    /// Handles a SET_IDLE request, the idle rate being the upper
+    /// byte of the value.
+    pub fn set_idle(&mut self, value: u16) {
+        self.idle = value.to_be_bytes()[0];
+        self.idle_elapsed = 0;
+    }
+
+    /// A time event, to call every millisecond. Resends the current
+    /// input report if the idle rate set by the host elapsed without
+    /// any report sent. Returns the number of bytes written.
+    pub fn tick(&mut self) -> Result<usize, Error> {
+        if self.idle == 0 {
+            return Ok(0);
+        }
+        self.idle_elapsed = self.idle_elapsed.saturating_add(1);
+        if self.idle_elapsed < u16::from(self.idle) * 4 {
+            return Ok(0);
+        }
+        let mut buf = [0; 64];
+        let len = match self.device.get_report(ReportType::Input, 0) {
+            Ok(report) if report.len() <= buf.len() => {
+                buf[..report.len()].copy_from_slice(report);
+                report.len()
+            }
+            _ => return Ok(0),
+        };
+        self.write(&buf[..len])
+    }
+
     pub fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
         if self.expect_interrupt_in_complete {
             return Ok(0);
@@ -144,7 +215,10 @@ impl<B: UsbBus, D: HidDevice> HidClass<'_, B, D> {
         }
 
         match self.endpoint_interrupt_in.write(data) {
-            Ok(count) => Ok(count),
+            Ok(count) => {
+                self.idle_elapsed = 0;
+                Ok(count)
+            }
             Err(UsbError::WouldBlock) => Ok(0),
             Err(_) => Err(Error),
         }
@@ -170,7 +244,7 @@ impl<B: UsbBus, D: HidDevice> HidClass<'_, B, D> {
         };
     }
 
-    fn interface_index(&self) -> u16 {
+    pub(crate) fn interface_index(&self) -> u16 {
         let iface: u8 = self.interface.into();
         iface as u16
     }
@@ -181,6 +255,9 @@ impl<B: UsbBus, D: HidDevice> UsbClass<B> for HidClass<'_, B, D> {
 
     fn reset(&mut self) {
         self.expect_interrupt_in_complete = false;
+        self.boot_protocol = false;
+        self.idle = 0;
+        self.idle_elapsed = 0;
     }
 
     fn get_configuration_descriptors(
@@ -196,7 +273,7 @@ impl<B: UsbBus, D: HidDevice> UsbClass<B> for HidClass<'_, B, D> {
 
         let report_descriptor = self.device.report_descriptor();
         let descriptor_len = report_descriptor.len();
-        if descriptor_len > u16::max_value() as usize {
+        if descriptor_len > u16::MAX as usize {
             return Err(UsbError::InvalidState);
         }
         let descriptor_len = (descriptor_len as u16).to_le_bytes();
@@ -234,23 +311,33 @@ impl<B: UsbBus, D: HidDevice> UsbClass<B> for HidClass<'_, B, D> {
     fn control_in(&mut self, xfer: ControlIn<B>) {
         let req = xfer.request();
         match (req.request_type, req.recipient) {
-            (RequestType::Standard, Recipient::Interface) => {
-                if req.request == control::Request::GET_DESCRIPTOR {
-                    let (dtype, index) = req.descriptor_type_index();
-                    if dtype == DescriptorType::Report as u8
-                        && index == 0
-                        && req.index == self.interface_index()
-                    {
-                        let descriptor = self.device.report_descriptor();
-                        xfer.accept_with(descriptor).ok();
-                    }
+            (RequestType::Standard, Recipient::Interface)
+                if req.request == control::Request::GET_DESCRIPTOR =>
+            {
+                let (dtype, index) = req.descriptor_type_index();
+                if dtype == DescriptorType::Report as u8
+                    && index == 0
+                    && req.index == self.interface_index()
+                {
+                    let descriptor = self.device.report_descriptor();
+                    xfer.accept_with(descriptor).ok();
                 }
             }
             (RequestType::Class, Recipient::Interface) => {
-                if let Some(request) = Request::new(req.request) {
-                    if request == Request::GetReport && req.index == self.interface_index() {
-                        self.get_report(xfer);
+                if req.index != self.interface_index() {
+                    return;
+                }
+                match Request::new(req.request) {
+                    Some(Request::GetReport) => self.get_report(xfer),
+                    Some(Request::GetIdle) => {
+                        xfer.accept_with(&[self.idle]).ok();
                     }
+                    Some(Request::GetProtocol)
+                        if self.device.subclass() == Subclass::BootInterface =>
+                    {
+                        xfer.accept_with(&[u8::from(!self.boot_protocol)]).ok();
+                    }
+                    _ => {}
                 }
             }
             _ => {}
@@ -260,10 +347,22 @@ impl<B: UsbBus, D: HidDevice> UsbClass<B> for HidClass<'_, B, D> {
     fn control_out(&mut self, xfer: ControlOut<B>) {
         let req = xfer.request();
         if req.request_type == RequestType::Class && req.recipient == Recipient::Interface {
-            if let Some(request) = Request::new(req.request) {
-                if request == Request::SetReport && req.index == self.interface_index() {
-                    self.set_report(xfer);
+            if req.index != self.interface_index() {
+                return;
+            }
+            match Request::new(req.request) {
+                Some(Request::SetReport) => self.set_report(xfer),
+                Some(Request::SetIdle) => {
+                    self.set_idle(req.value);
+                    xfer.accept().ok();
+                }
+                Some(Request::SetProtocol) => {
+                    match self.set_protocol(req.value) {
+                        Ok(()) => xfer.accept().ok(),
+                        Err(Error) => xfer.reject().ok(),
+                    };
                 }
+                _ => {}
             }
         }
     }
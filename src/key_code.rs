@@ -257,6 +257,129 @@ pub enum KeyCode {
     MediaCalc, // 0xFB
 }
 
+/// The key codes, in the order of their values: from `No` to `ExSel`,
+/// and then from `LCtrl` to `MediaCalc`.
+#[rustfmt::skip]
+const KEY_CODES: [KeyCode; 193] = {
+    use KeyCode::*;
+    [
+        No, ErrorRollOver, PostFail, ErrorUndefined, A, B, C, D, E, F, G, H, I, J, K, L, M, N,
+        O, P, Q, R, S, T, U, V, W, X, Y, Z, Kb1, Kb2, Kb3, Kb4, Kb5, Kb6, Kb7, Kb8, Kb9, Kb0,
+        Enter, Escape, BSpace, Tab, Space, Minus, Equal, LBracket, RBracket, Bslash, NonUsHash,
+        SColon, Quote, Grave, Comma, Dot, Slash, CapsLock, F1, F2, F3, F4, F5, F6, F7, F8, F9,
+        F10, F11, F12, PScreen, ScrollLock, Pause, Insert, Home, PgUp, Delete, End, PgDown,
+        Right, Left, Down, Up, NumLock, KpSlash, KpAsterisk, KpMinus, KpPlus, KpEnter, Kp1, Kp2,
+        Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, Kp0, KpDot, NonUsBslash, Application, Power, KpEqual,
+        F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, Execute, Help, Menu, Select,
+        Stop, Again, Undo, Cut, Copy, Paste, Find, Mute, VolUp, VolDown, LockingCapsLock,
+        LockingNumLock, LockingScrollLock, KpComma, KpEqualSign, Intl1, Intl2, Intl3, Intl4,
+        Intl5, Intl6, Intl7, Intl8, Intl9, Lang1, Lang2, Lang3, Lang4, Lang5, Lang6, Lang7,
+        Lang8, Lang9, AltErase, SysReq, Cancel, Clear, Prior, Return, Separator, Out, Oper,
+        ClearAgain, CrSel, ExSel, LCtrl, LShift, LAlt, LGui, RCtrl, RShift, RAlt, RGui,
+        MediaPlayPause, MediaStopCD, MediaPreviousSong, MediaNextSong, MediaEjectCD, MediaVolUp,
+        MediaVolDown, MediaMute, MediaWWW, MediaBack, MediaForward, MediaStop, MediaFind,
+        MediaScrollUp, MediaScrollDown, MediaEdit, MediaSleep, MediaCoffee, MediaRefresh,
+        MediaCalc,
+    ]
+};
+
+impl KeyCode {
+    /// Returns the key code of the given value, if any. This is the
+    /// inverse of `kc as u8`.
+    pub const fn from_u8(value: u8) -> Option<KeyCode> {
+        match value {
+            0x00..=0xA4 => Some(KEY_CODES[value as usize]),
+            0xE0..=0xFB => Some(KEY_CODES[value as usize - 0xE0 + 0xA5]),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes the key code as its `u8` value.
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// Deserializes the key code from its `u8` value.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, Unexpected};
+        let value = u8::deserialize(deserializer)?;
+        KeyCode::from_u8(value).ok_or_else(|| {
+            D::Error::invalid_value(Unexpected::Unsigned(value.into()), &"a key code")
+        })
+    }
+}
+
+/// Error converting an invalid value to a [`KeyCode`], containing
+/// this value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidKeyCode(pub u8);
+
+impl core::convert::TryFrom<u8> for KeyCode {
+    type Error = InvalidKeyCode;
+
+    /// Returns the key code of the given value, failing on the
+    /// reserved values. This is the inverse of `kc as u8`.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        KeyCode::from_u8(value).ok_or(InvalidKeyCode(value))
+    }
+}
+
+/// Error parsing a [`KeyCode`] from a string that is not the name of
+/// a variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseKeyCodeError;
+
+/// A writer checking that the written string is equal to `expected`.
+struct Matcher<'a> {
+    expected: &'a str,
+}
+
+impl core::fmt::Write for Matcher<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        match self.expected.strip_prefix(s) {
+            Some(rest) => {
+                self.expected = rest;
+                Ok(())
+            }
+            None => Err(core::fmt::Error),
+        }
+    }
+}
+
+impl core::str::FromStr for KeyCode {
+    type Err = ParseKeyCodeError;
+
+    /// Parses the name of a variant, as `"A"`, `"Kb1"` or `"LShift"`.
+    /// This is the inverse of the `Debug` implementation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use core::fmt::Write;
+        KEY_CODES
+            .iter()
+            .copied()
+            .find(|kc| {
+                let mut matcher = Matcher { expected: s };
+                write!(matcher, "{:?}", kc).is_ok() && matcher.expected.is_empty()
+            })
+            .ok_or(ParseKeyCodeError)
+    }
+}
+
+/// A side of the keyboard, see
+/// [`KeyCode::with_modifier_side`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierSide {
+    /// The left modifiers, from `LCtrl` to `LGui`.
+    Left,
+    /// The right modifiers, from `RCtrl` to `RGui`.
+    Right,
+}
+
 impl KeyCode {
     /// Returns `true` if the key code corresponds to a modifier (sent
     /// separately on the USB HID report).
@@ -273,6 +396,491 @@ impl KeyCode {
             0
         }
     }
+
+    /// Returns an iterator on the modifiers set in the given USB HID
+    /// modifier bitfield, from `LCtrl` to `RGui`. This is the inverse
+    /// of [`as_modifier_bit`](KeyCode::as_modifier_bit).
+    pub fn modifiers_from_byte(b: u8) -> impl Iterator<Item = KeyCode> {
+        use KeyCode::*;
+        [LCtrl, LShift, LAlt, LGui, RCtrl, RShift, RAlt, RGui]
+            .iter()
+            .copied()
+            .filter(move |kc| b & kc.as_modifier_bit() != 0)
+    }
+
+    /// Returns the modifier of the same kind on the given side if the
+    /// key code is a modifier, else returns the key code unchanged.
+    ///
+    /// As some OSes treat the left and right modifiers differently
+    /// for some shortcuts, the key codes of the layout can be
+    /// normalized before building the report:
+    ///
+    /// ```
+    /// use keyberon::key_code::{KbHidReport, KeyCode, ModifierSide};
+    /// let report: KbHidReport = [KeyCode::RShift, KeyCode::A]
+    ///     .iter()
+    ///     .map(|kc| kc.with_modifier_side(ModifierSide::Left))
+    ///     .collect();
+    /// assert_eq!(KeyCode::LShift.as_modifier_bit(), report.as_bytes()[0]);
+    /// ```
+    pub fn with_modifier_side(self, side: ModifierSide) -> KeyCode {
+        let bit = self.as_modifier_bit();
+        let bit = match side {
+            ModifierSide::Left => (bit | bit >> 4) & 0x0F,
+            ModifierSide::Right => (bit | bit << 4) & 0xF0,
+        };
+        KeyCode::modifiers_from_byte(bit).next().unwrap_or(self)
+    }
+
+    /// Returns `true` if the key code is a letter, from `A` to `Z`.
+    pub fn is_letter(self) -> bool {
+        (KeyCode::A..=KeyCode::Z).contains(&self)
+    }
+
+    /// Returns `true` if the key code is a digit, from `Kb1` to `Kb0`
+    /// or from `Kp1` to `Kp0`.
+    pub fn is_digit(self) -> bool {
+        (KeyCode::Kb1..=KeyCode::Kb0).contains(&self)
+            || (KeyCode::Kp1..=KeyCode::Kp0).contains(&self)
+    }
+
+    /// Returns `true` if the key code is a function key, from `F1` to
+    /// `F24`.
+    pub fn is_function(self) -> bool {
+        (KeyCode::F1..=KeyCode::F12).contains(&self)
+            || (KeyCode::F13..=KeyCode::F24).contains(&self)
+    }
+
+    /// Returns `true` if the key code is a navigation key, i.e. an
+    /// arrow, `Home`, `End`, `PgUp` or `PgDown`.
+    pub fn is_navigation(self) -> bool {
+        use KeyCode::*;
+        matches!(self, Home | PgUp | End | PgDown) || (Right..=Up).contains(&self)
+    }
+
+    /// Returns `true` if the key code is a media key, i.e. `Mute`,
+    /// `VolUp`, `VolDown` or one of the `Media*` key codes (except
+    /// `MediaSleep` that is a system key). These keys are sent on the
+    /// consumer control report, see
+    /// [`as_consumer_usage`](KeyCode::as_consumer_usage).
+    pub fn is_media(self) -> bool {
+        self.as_consumer_usage().is_some()
+    }
+
+    /// Returns `true` if the key code is a system key, i.e. `Power`
+    /// or `MediaSleep`. These keys are sent on the system control
+    /// report, see [`as_system_usage`](KeyCode::as_system_usage).
+    pub fn is_system(self) -> bool {
+        self.as_system_usage().is_some()
+    }
+
+    /// Returns the usage of the consumer page (0x0C) corresponding
+    /// to this key code, if it is a media key.
+    pub fn as_consumer_usage(self) -> Option<u16> {
+        self.as_consumer().map(|c| c as u16)
+    }
+
+    /// Returns the consumer control usage corresponding to this key
+    /// code, if it is a media key.
+    pub fn as_consumer(self) -> Option<Consumer> {
+        use KeyCode::*;
+        Some(match self {
+            Mute | MediaMute => Consumer::Mute,
+            VolUp | MediaVolUp => Consumer::VolumeIncrement,
+            VolDown | MediaVolDown => Consumer::VolumeDecrement,
+            MediaPlayPause => Consumer::PlayPause,
+            MediaStopCD => Consumer::Stop,
+            MediaPreviousSong => Consumer::ScanPreviousTrack,
+            MediaNextSong => Consumer::ScanNextTrack,
+            MediaEjectCD => Consumer::Eject,
+            MediaWWW => Consumer::InternetBrowser,
+            MediaBack => Consumer::AcBack,
+            MediaForward => Consumer::AcForward,
+            MediaStop => Consumer::AcStop,
+            MediaFind => Consumer::AcSearch,
+            MediaScrollUp => Consumer::AcScrollUp,
+            MediaScrollDown => Consumer::AcScrollDown,
+            MediaEdit => Consumer::TextEditor,
+            MediaCoffee => Consumer::TerminalLock,
+            MediaRefresh => Consumer::AcRefresh,
+            MediaCalc => Consumer::Calculator,
+            _ => return None,
+        })
+    }
+
+    /// Returns the usage of the generic desktop page (0x01)
+    /// corresponding to this key code, if it is a system key.
+    pub fn as_system_usage(self) -> Option<u8> {
+        self.as_system_control().map(|s| s as u8)
+    }
+
+    /// Returns the system control usage corresponding to this key
+    /// code, if it is a system key.
+    pub fn as_system_control(self) -> Option<SystemControl> {
+        match self {
+            KeyCode::Power => Some(SystemControl::PowerDown),
+            KeyCode::MediaSleep => Some(SystemControl::Sleep),
+            _ => None,
+        }
+    }
+}
+
+/// A usage of the consumer page (0x0C), sent on the consumer control
+/// report.
+///
+/// The `Media*` key codes are not valid keyboard usages, and some OSes
+/// ignore them in a keyboard report. These usages can be sent instead,
+/// using [`ConsumerReport`](crate::composite::ConsumerReport) (see
+/// also [`KeyCode::as_consumer`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u16)]
+pub enum Consumer {
+    /// Display brightness increment.
+    BrightnessIncrement = 0x006F,
+    /// Display brightness decrement.
+    BrightnessDecrement = 0x0070,
+    /// Next track.
+    ScanNextTrack = 0x00B5,
+    /// Previous track.
+    ScanPreviousTrack = 0x00B6,
+    /// Stop.
+    Stop = 0x00B7,
+    /// Eject.
+    Eject = 0x00B8,
+    /// Play/Pause.
+    PlayPause = 0x00CD,
+    /// Mute.
+    Mute = 0x00E2,
+    /// Volume increment.
+    VolumeIncrement = 0x00E9,
+    /// Volume decrement.
+    VolumeDecrement = 0x00EA,
+    /// Application launch: text editor.
+    TextEditor = 0x0185,
+    /// Application launch: calculator.
+    Calculator = 0x0192,
+    /// Application launch: internet browser.
+    InternetBrowser = 0x0196,
+    /// Application launch: terminal lock/screensaver.
+    TerminalLock = 0x019E,
+    /// Application control: search.
+    AcSearch = 0x0221,
+    /// Application control: back.
+    AcBack = 0x0224,
+    /// Application control: forward.
+    AcForward = 0x0225,
+    /// Application control: stop.
+    AcStop = 0x0226,
+    /// Application control: refresh.
+    AcRefresh = 0x0227,
+    /// Application control: scroll up.
+    AcScrollUp = 0x0233,
+    /// Application control: scroll down.
+    AcScrollDown = 0x0234,
+}
+
+/// A system control usage of the generic desktop page (0x01), sent
+/// on the system control report (see
+/// [`SystemControlReport`](crate::composite::SystemControlReport)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum SystemControl {
+    /// System power down.
+    PowerDown = 0x81,
+    /// System sleep.
+    Sleep = 0x82,
+    /// System wake up.
+    WakeUp = 0x83,
+}
+
+/// The usage page, i.e. the report, on which a key code is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UsagePage {
+    /// The keyboard report.
+    Keyboard,
+    /// The consumer control report.
+    Consumer,
+    /// The system control report.
+    System,
+}
+
+impl KeyCode {
+    /// Returns the key code typing the given ASCII character on a US
+    /// layout, and `true` if shift must be pressed. Returns `None` if
+    /// the character can't be typed.
+    ///
+    /// ```
+    /// use keyberon::key_code::KeyCode;
+    /// assert_eq!(Some((KeyCode::A, true)), KeyCode::from_ascii(b'A'));
+    /// assert_eq!(Some((KeyCode::Kb2, false)), KeyCode::from_ascii(b'2'));
+    /// assert_eq!(None, KeyCode::from_ascii(0));
+    /// ```
+    pub fn from_ascii(c: u8) -> Option<(KeyCode, bool)> {
+        use KeyCode::*;
+        const LETTERS: [KeyCode; 26] = [
+            A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        ];
+        const DIGITS: [KeyCode; 10] = [Kb0, Kb1, Kb2, Kb3, Kb4, Kb5, Kb6, Kb7, Kb8, Kb9];
+        let res = match c {
+            b'a'..=b'z' => (LETTERS[usize::from(c - b'a')], false),
+            b'A'..=b'Z' => (LETTERS[usize::from(c - b'A')], true),
+            b'0'..=b'9' => (DIGITS[usize::from(c - b'0')], false),
+            b'!' => (Kb1, true),
+            b'@' => (Kb2, true),
+            b'#' => (Kb3, true),
+            b'$' => (Kb4, true),
+            b'%' => (Kb5, true),
+            b'^' => (Kb6, true),
+            b'&' => (Kb7, true),
+            b'*' => (Kb8, true),
+            b'(' => (Kb9, true),
+            b')' => (Kb0, true),
+            b'\n' => (Enter, false),
+            b'\t' => (Tab, false),
+            b' ' => (Space, false),
+            b'-' => (Minus, false),
+            b'_' => (Minus, true),
+            b'=' => (Equal, false),
+            b'+' => (Equal, true),
+            b'[' => (LBracket, false),
+            b'{' => (LBracket, true),
+            b']' => (RBracket, false),
+            b'}' => (RBracket, true),
+            b'\\' => (Bslash, false),
+            b'|' => (Bslash, true),
+            b';' => (SColon, false),
+            b':' => (SColon, true),
+            b'\'' => (Quote, false),
+            b'"' => (Quote, true),
+            b'`' => (Grave, false),
+            b'~' => (Grave, true),
+            b',' => (Comma, false),
+            b'<' => (Comma, true),
+            b'.' => (Dot, false),
+            b'>' => (Dot, true),
+            b'/' => (Slash, false),
+            b'?' => (Slash, true),
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    /// Returns the key code typing the given character on a US
+    /// layout, and `true` if shift must be pressed. Returns `None` if
+    /// the character is not ASCII or can't be typed (see
+    /// [`from_ascii`](KeyCode::from_ascii)).
+    pub fn from_char(c: char) -> Option<(KeyCode, bool)> {
+        use core::convert::TryFrom;
+        u8::try_from(c).ok().and_then(KeyCode::from_ascii)
+    }
+
+    /// Returns the character typed by the key code with shift pressed
+    /// on a US layout, as `"!"` for `Kb1` or `"A"` for `A`. Returns an
+    /// empty string if there is no such printable character.
+    ///
+    /// ```
+    /// use keyberon::key_code::KeyCode;
+    /// assert_eq!("_", KeyCode::Minus.shifted_str());
+    /// assert_eq!("", KeyCode::Enter.shifted_str());
+    /// ```
+    pub fn shifted_str(&self) -> &'static str {
+        KeymapLocale::US.shifted_str(*self)
+    }
+
+    /// Returns the usage page on which the key code is sent by
+    /// default, see [`KeyReports`].
+    pub fn default_page(self) -> UsagePage {
+        if self.is_system() {
+            UsagePage::System
+        } else if self.is_media() {
+            UsagePage::Consumer
+        } else {
+            UsagePage::Keyboard
+        }
+    }
+}
+
+/// A keyboard layout of the host, giving the keys to type the ASCII
+/// characters.
+///
+/// The characters not in `overrides` are typed as on the US layout
+/// (see [`KeyCode::from_ascii`]).
+///
+/// # Example
+///
+/// ```
+/// use keyberon::key_code::{KeyCode, KeymapLocale};
+/// // partial german layout
+/// const DE: KeymapLocale = KeymapLocale {
+///     overrides: &[
+///         (b'y', KeyCode::Z, 0),
+///         (b'z', KeyCode::Y, 0),
+///         (b'@', KeyCode::Q, 0x40), // AltGr
+///     ],
+/// };
+/// assert_eq!(Some((KeyCode::Q, 0x40)), DE.key(b'@'));
+/// assert_eq!(Some((KeyCode::Kb2, 0x02)), KeymapLocale::US.key(b'@'));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeymapLocale {
+    /// The characters typed differently than on the US layout: the
+    /// character, the key code, and the modifiers to press, as a USB
+    /// HID modifier bitfield (see [`KeyCode::as_modifier_bit`]).
+    pub overrides: &'static [(u8, KeyCode, u8)],
+}
+
+impl KeymapLocale {
+    /// The US layout.
+    pub const US: KeymapLocale = KeymapLocale { overrides: &[] };
+
+    /// Returns the key code and the modifiers, as a USB HID modifier
+    /// bitfield, typing the given ASCII character, if possible.
+    pub fn key(&self, c: u8) -> Option<(KeyCode, u8)> {
+        match self.overrides.iter().find(|&&(o, ..)| o == c) {
+            Some(&(_, kc, modifiers)) => Some((kc, modifiers)),
+            None => KeyCode::from_ascii(c).map(|(kc, shift)| {
+                let modifiers = if shift {
+                    KeyCode::LShift.as_modifier_bit()
+                } else {
+                    0
+                };
+                (kc, modifiers)
+            }),
+        }
+    }
+
+    /// Returns the character typed by the key code with shift on
+    /// this layout, or an empty string if there is no such printable
+    /// ASCII character (see [`KeyCode::shifted_str`]).
+    ///
+    /// ```
+    /// use keyberon::key_code::{KeyCode, KeymapLocale};
+    /// // partial french layout
+    /// const FR: KeymapLocale = KeymapLocale {
+    ///     overrides: &[(b'1', KeyCode::Kb1, 0x02), (b'&', KeyCode::Kb1, 0)],
+    /// };
+    /// assert_eq!("1", FR.shifted_str(KeyCode::Kb1));
+    /// assert_eq!("!", KeymapLocale::US.shifted_str(KeyCode::Kb1));
+    /// ```
+    pub fn shifted_str(&self, kc: KeyCode) -> &'static str {
+        const PRINTABLE: &str = " !\"#$%&'()*+,-./0123456789:;<=>?@\
+                                 ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`\
+                                 abcdefghijklmnopqrstuvwxyz{|}~";
+        let shift = KeyCode::LShift.as_modifier_bit();
+        // the overrides first, as they are partial
+        let overridden = self
+            .overrides
+            .iter()
+            .find(|&&(_, o, modifiers)| (o, modifiers) == (kc, shift))
+            .map(|&(c, ..)| c);
+        let c = overridden.or_else(|| {
+            PRINTABLE
+                .bytes()
+                .find(|&c| self.key(c) == Some((kc, shift)))
+        });
+        c.and_then(|c| PRINTABLE.bytes().position(|p| p == c))
+            .map_or("", |i| &PRINTABLE[i..=i])
+    }
+
+    /// Iterates on the key codes to press to type the given ASCII
+    /// character: the modifiers, and then the key.
+    pub fn key_codes(&self, c: u8) -> impl Iterator<Item = KeyCode> {
+        let (kc, modifiers) = match self.key(c) {
+            Some((kc, modifiers)) => (Some(kc), modifiers),
+            None => (None, 0),
+        };
+        KeyCode::modifiers_from_byte(modifiers).chain(kc)
+    }
+}
+
+/// The reports corresponding to a set of key codes: keyboard,
+/// consumer control and system control.
+///
+/// When collected from key codes, each key code is routed to the
+/// right report: the media keys to the consumer control report, the
+/// system keys to the system control report, and the other ones to
+/// the keyboard report. Thus, a layout can use `k(VolUp)` and have
+/// it working on every OS.
+///
+/// The consumer and system control reports contain only one usage:
+/// if several media or system keys are pressed, the last one wins.
+///
+/// ```
+/// use keyberon::key_code::{KeyCode, KeyReports};
+///
+/// let reports: KeyReports = [KeyCode::A, KeyCode::VolUp].iter().copied().collect();
+/// assert_eq!(&[0, 0, 4, 0, 0, 0, 0, 0], reports.keyboard().as_bytes());
+/// assert_eq!(0xE9, reports.consumer());
+/// assert_eq!(0, reports.system());
+/// ```
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct KeyReports {
+    keyboard: KbHidReport,
+    consumer: u16,
+    system: u8,
+}
+
+impl core::iter::FromIterator<KeyCode> for KeyReports {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = KeyCode>,
+    {
+        iter.into_iter().map(|kc| (kc, None)).collect()
+    }
+}
+
+/// Collects key codes with an optional usage page, as given by
+/// [`Layout::keycodes_with_page`](crate::layout::Layout::keycodes_with_page),
+/// overriding the default routing when the page is given.
+impl core::iter::FromIterator<(KeyCode, Option<UsagePage>)> for KeyReports {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = (KeyCode, Option<UsagePage>)>,
+    {
+        let mut res = Self::default();
+        for (kc, page) in iter {
+            res.pressed(kc, page.unwrap_or_else(|| kc.default_page()));
+        }
+        res
+    }
+}
+
+impl KeyReports {
+    /// Adds the key code to the report of the given page.
+    ///
+    /// On the consumer and system pages, a key code without usage on
+    /// this page is ignored.
+    pub fn pressed(&mut self, kc: KeyCode, page: UsagePage) {
+        match page {
+            UsagePage::Keyboard => self.keyboard.pressed(kc),
+            UsagePage::Consumer => {
+                if let Some(usage) = kc.as_consumer_usage() {
+                    self.consumer = usage;
+                }
+            }
+            UsagePage::System => {
+                if let Some(usage) = kc.as_system_usage() {
+                    self.system = usage;
+                }
+            }
+        }
+    }
+
+    /// Returns the keyboard report.
+    pub fn keyboard(&self) -> &KbHidReport {
+        &self.keyboard
+    }
+
+    /// Returns the consumer control usage, 0 if none.
+    pub fn consumer(&self) -> u16 {
+        self.consumer
+    }
+
+    /// Returns the system control usage, 0 if none.
+    pub fn system(&self) -> u8 {
+        self.system
+    }
 }
 
 /// A standard keyboard USB HID report.
@@ -281,6 +889,19 @@ impl KeyCode {
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct KbHidReport([u8; 8]);
 
+/// The behavior of a [`KbHidReport`] when more than 6 keys are
+/// pressed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RolloverPolicy {
+    /// All the key slots are set to `ErrorRollOver`, as the USB HID
+    /// specification requires. This is the default.
+    #[default]
+    ErrorRollOver,
+    /// The first six keys stay in the report, the additional ones
+    /// being ignored until a slot is freed.
+    KeepFirstSix,
+}
+
 impl core::iter::FromIterator<KeyCode> for KbHidReport {
     fn from_iter<T>(iter: T) -> Self
     where
@@ -300,24 +921,643 @@ impl KbHidReport {
         &self.0
     }
 
+    /// Returns the bytes of the report, prefixed by
+    /// [`KEYBOARD_REPORT_ID`](crate::composite::KEYBOARD_REPORT_ID),
+    /// as sent on a shared interface.
+    pub fn as_bytes_with_id(&self) -> [u8; 9] {
+        let mut res = [crate::composite::KEYBOARD_REPORT_ID; 9];
+        res[1..].copy_from_slice(&self.0);
+        res
+    }
+
+    /// Creates a report from key codes, handling the overflow
+    /// according to the given policy. Collecting the key codes is
+    /// equivalent to using [`RolloverPolicy::ErrorRollOver`].
+    pub fn from_keycodes(
+        keycodes: impl IntoIterator<Item = KeyCode>,
+        policy: RolloverPolicy,
+    ) -> Self {
+        let mut res = Self::default();
+        for kc in keycodes {
+            res.pressed_with_policy(kc, policy);
+        }
+        res
+    }
+
+    /// Add the given key code to the report, handling the overflow
+    /// according to the given policy.
+    pub fn pressed_with_policy(&mut self, kc: KeyCode, policy: RolloverPolicy) {
+        match policy {
+            RolloverPolicy::ErrorRollOver => self.pressed(kc),
+            RolloverPolicy::KeepFirstSix => {
+                self.try_pressed(kc);
+            }
+        }
+    }
+
     /// Add the given key code to the report. If the report is full,
     /// it will be set to `ErrorRollOver`.
     pub fn pressed(&mut self, kc: KeyCode) {
+        if !self.try_pressed(kc) {
+            self.set_all(KeyCode::ErrorRollOver);
+        }
+    }
+
+    /// Add the given key code to the report. Returns `false` if the
+    /// report is full, in which case the report is not modified.
+    pub fn try_pressed(&mut self, kc: KeyCode) -> bool {
         use KeyCode::*;
         match kc {
-            No => (),
-            ErrorRollOver | PostFail | ErrorUndefined => self.set_all(kc),
-            kc if kc.is_modifier() => self.0[0] |= kc.as_modifier_bit(),
+            No => true,
+            ErrorRollOver | PostFail | ErrorUndefined => {
+                self.set_all(kc);
+                true
+            }
+            kc if kc.is_modifier() => {
+                self.0[0] |= kc.as_modifier_bit();
+                true
+            }
             _ => self.0[2..]
                 .iter_mut()
                 .find(|c| **c == 0)
                 .map(|c| *c = kc as u8)
-                .unwrap_or_else(|| self.set_all(ErrorRollOver)),
+                .is_some(),
+        }
+    }
+    /// Returns `true` if the given key code is pressed in the report.
+    ///
+    /// `No` is never pressed, and in the `ErrorRollOver` state, the
+    /// pressed keys are unknown: only the modifiers are reported.
+    pub fn contains(&self, kc: KeyCode) -> bool {
+        if kc.is_modifier() {
+            self.0[0] & kc.as_modifier_bit() != 0
+        } else {
+            kc > KeyCode::ErrorUndefined && self.0[2..].contains(&(kc as u8))
         }
     }
+
+    /// Removes the given key code from the report, the other keys
+    /// staying in their slot.
+    ///
+    /// In the `ErrorRollOver` state, the pressed keys are unknown:
+    /// releasing a key doesn't restore them, and the report stays in
+    /// this state (only the modifiers can be released).
+    pub fn released(&mut self, kc: KeyCode) {
+        if kc.is_modifier() {
+            self.0[0] &= !kc.as_modifier_bit();
+        } else if kc != KeyCode::No {
+            for c in self.0[2..].iter_mut().filter(|c| **c == kc as u8) {
+                *c = 0;
+            }
+        }
+    }
+
+    /// Iterates on the pressed key codes: the modifiers, from `LCtrl`
+    /// to `RGui`, and then the keys, in the order of the report. The
+    /// `ErrorRollOver` state gives no key.
+    pub fn keys(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        let keys = self.0[2..]
+            .iter()
+            .filter_map(|&b| KeyCode::from_u8(b))
+            .filter(|&kc| kc > KeyCode::ErrorUndefined);
+        KeyCode::modifiers_from_byte(self.0[0]).chain(keys)
+    }
     fn set_all(&mut self, kc: KeyCode) {
         for c in &mut self.0[2..] {
             *c = kc as u8;
         }
     }
+    /// Merges the content of `other` in the report. Modifiers are
+    /// OR-ed, and the keys of `other` not already in the report are
+    /// added. If there is not enough room, the report is set to
+    /// `ErrorRollOver`.
+    fn merge(&mut self, other: &Self) {
+        self.0[0] |= other.0[0];
+        if other.0[2] == KeyCode::ErrorRollOver as u8 {
+            self.set_all(KeyCode::ErrorRollOver);
+            return;
+        }
+        for &kc in other.0[2..].iter().filter(|&&kc| kc != 0) {
+            if self.0[2..].contains(&kc) {
+                continue;
+            }
+            match self.0[2..].iter_mut().find(|c| **c == 0) {
+                Some(c) => *c = kc,
+                None => self.set_all(KeyCode::ErrorRollOver),
+            }
+        }
+    }
+
+    /// Returns the union of the 2 reports: the modifiers are OR-ed,
+    /// and the keys are merged without duplicates. If more than 6
+    /// different keys are pressed, the report is set to
+    /// `ErrorRollOver`. The `|=` operator can also be used.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        res.merge(other);
+        res
+    }
+}
+
+impl core::ops::BitOrAssign<&KbHidReport> for KbHidReport {
+    fn bitor_assign(&mut self, other: &KbHidReport) {
+        self.merge(other);
+    }
+}
+
+/// A mouse USB HID report, as generated by
+/// [`Layout::mouse_report`](crate::layout::Layout::mouse_report).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MouseReport {
+    /// The buttons bit field, bit 0 being the left button.
+    pub buttons: u8,
+    /// The horizontal displacement.
+    pub x: i8,
+    /// The vertical displacement.
+    pub y: i8,
+    /// The wheel displacement.
+    pub wheel: i8,
+}
+
+impl MouseReport {
+    /// Returns the bytes of the report, prefixed by
+    /// [`MOUSE_REPORT_ID`](crate::composite::MOUSE_REPORT_ID).
+    pub fn as_bytes_with_id(&self) -> [u8; 5] {
+        [
+            crate::composite::MOUSE_REPORT_ID,
+            self.buttons,
+            self.x as u8,
+            self.y as u8,
+            self.wheel as u8,
+        ]
+    }
+}
+
+/// Several keyboard reports, one per source, merged into a single
+/// outgoing report.
+///
+/// Useful when several logical keyboards (for example a main
+/// keyboard and a macro pad) share the same MCU and USB device: each
+/// source maintains its own report, and the merged report is sent.
+///
+/// # Example
+///
+/// ```
+/// use keyberon::key_code::{KbHidReport, KeyCode, MultiReport};
+/// let mut reports = MultiReport::<2>::new();
+/// reports.set(0, [KeyCode::LCtrl, KeyCode::A].iter().copied().collect());
+/// reports.set(1, [KeyCode::LShift, KeyCode::B].iter().copied().collect());
+/// let expected: KbHidReport = [KeyCode::LCtrl, KeyCode::LShift, KeyCode::A, KeyCode::B]
+///     .iter()
+///     .copied()
+///     .collect();
+/// assert_eq!(expected, reports.merged());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiReport<const N: usize>([KbHidReport; N]);
+
+impl<const N: usize> Default for MultiReport<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MultiReport<N> {
+    /// Creates a new `MultiReport` with all the reports empty.
+    pub fn new() -> Self {
+        Self(core::array::from_fn(|_| KbHidReport::default()))
+    }
+
+    /// Sets the report of the given source.
+    ///
+    /// Panics if `source >= N`.
+    pub fn set(&mut self, source: usize, report: KbHidReport) {
+        self.0[source] = report;
+    }
+
+    /// Returns the report of the given source, if it exists.
+    pub fn get(&self, source: usize) -> Option<&KbHidReport> {
+        self.0.get(source)
+    }
+
+    /// Returns a mutable reference to the report of the given
+    /// source, if it exists.
+    pub fn get_mut(&mut self, source: usize) -> Option<&mut KbHidReport> {
+        self.0.get_mut(source)
+    }
+
+    /// Returns the merged report of all the sources.
+    ///
+    /// The modifiers are OR-ed, and the keys are merged without
+    /// duplicates. If more than 6 different keys are pressed, the
+    /// report is set to `ErrorRollOver`.
+    pub fn merged(&self) -> KbHidReport {
+        let mut res = KbHidReport::default();
+        for report in &self.0 {
+            res.merge(report);
+        }
+        res
+    }
+}
+
+/// A N-key rollover keyboard USB HID report.
+///
+/// It can handle any number of keys: the first byte is the modifier
+/// bitfield, and the following 32 bytes are a bitmap of the usages
+/// 0x00 to 0xFF. It is sent using the
+/// [`NKRO_REPORT_DESCRIPTOR`](crate::keyboard::NKRO_REPORT_DESCRIPTOR).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NkroReport([u8; 33]);
+
+impl Default for NkroReport {
+    fn default() -> Self {
+        Self([0; 33])
+    }
+}
+
+impl core::iter::FromIterator<KeyCode> for NkroReport {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = KeyCode>,
+    {
+        let mut res = Self::default();
+        for kc in iter {
+            res.pressed(kc);
+        }
+        res
+    }
+}
+
+impl NkroReport {
+    /// Returns the byte slice corresponding to the report.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Add the given key code to the report.
+    pub fn pressed(&mut self, kc: KeyCode) {
+        match kc {
+            KeyCode::No => (),
+            kc if kc.is_modifier() => self.0[0] |= kc.as_modifier_bit(),
+            kc => self.0[1 + kc as usize / 8] |= 1 << (kc as u8 % 8),
+        }
+    }
+
+    /// Removes the given key code from the report.
+    pub fn released(&mut self, kc: KeyCode) {
+        match kc {
+            KeyCode::No => (),
+            kc if kc.is_modifier() => self.0[0] &= !kc.as_modifier_bit(),
+            kc => self.0[1 + kc as usize / 8] &= !(1 << (kc as u8 % 8)),
+        }
+    }
+
+    /// Iterates on the pressed key codes: the modifiers, from `LCtrl`
+    /// to `RGui`, and then the keys, by increasing value.
+    pub fn keys(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        let keys = (0..=255)
+            .filter(move |&i: &u8| self.0[1 + i as usize / 8] & 1 << (i % 8) != 0)
+            .filter_map(KeyCode::from_u8);
+        KeyCode::modifiers_from_byte(self.0[0]).chain(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+    use KeyCode::*;
+
+    fn report(kcs: &[KeyCode]) -> KbHidReport {
+        kcs.iter().copied().collect()
+    }
+
+    #[test]
+    fn keymap_locale() {
+        const FR: KeymapLocale = KeymapLocale {
+            overrides: &[
+                (b'a', Q, 0),
+                (b'q', A, 0),
+                (b'1', Kb1, 0x02),
+                (b'@', Kb0, 0x40),
+            ],
+        };
+        let keys = |locale: &KeymapLocale, c| locale.key_codes(c).collect::<Vec<_, 9>>();
+        assert_eq!(&[A], keys(&KeymapLocale::US, b'a').as_slice());
+        assert_eq!(&[Q], keys(&FR, b'a').as_slice());
+        assert_eq!(&[Kb1], keys(&KeymapLocale::US, b'1').as_slice());
+        assert_eq!(&[LShift, Kb1], keys(&FR, b'1').as_slice());
+        assert_eq!(&[LShift, Kb2], keys(&KeymapLocale::US, b'@').as_slice());
+        assert_eq!(&[RAlt, Kb0], keys(&FR, b'@').as_slice());
+        assert_eq!(&[B], keys(&FR, b'b').as_slice());
+        assert!(keys(&FR, 0).is_empty());
+    }
+
+    #[test]
+    fn from_u8() {
+        for kc in KEY_CODES {
+            assert_eq!(Some(kc), KeyCode::from_u8(kc as u8));
+        }
+        assert_eq!(None, KeyCode::from_u8(0xA5));
+        assert_eq!(None, KeyCode::from_u8(0xFF));
+    }
+
+    #[test]
+    fn try_from_u8() {
+        use core::convert::TryFrom;
+        for kc in KEY_CODES {
+            assert_eq!(Ok(kc), KeyCode::try_from(kc as u8));
+        }
+        let valid = (0..=255).filter(|&v| KeyCode::try_from(v).is_ok()).count();
+        assert_eq!(KEY_CODES.len(), valid);
+        for v in (0xA5..=0xDF).chain(0xFC..=0xFF) {
+            assert_eq!(Err(InvalidKeyCode(v)), KeyCode::try_from(v));
+        }
+    }
+
+    #[test]
+    fn from_str() {
+        extern crate std;
+        use std::format;
+        for kc in KEY_CODES {
+            assert_eq!(Ok(kc), format!("{:?}", kc).parse());
+        }
+        assert_eq!(Ok(MediaPlayPause), "MediaPlayPause".parse());
+        assert_eq!(Err(ParseKeyCodeError), "".parse::<KeyCode>());
+        assert_eq!(Err(ParseKeyCodeError), "Kb".parse::<KeyCode>());
+        assert_eq!(Err(ParseKeyCodeError), "LShiftt".parse::<KeyCode>());
+        assert_eq!(Err(ParseKeyCodeError), "lshift".parse::<KeyCode>());
+    }
+
+    #[test]
+    fn keys() {
+        let r = report(&[RGui, A, LCtrl, B]);
+        let keys: Vec<_, 8> = r.keys().collect();
+        assert_eq!(&[LCtrl, RGui, A, B], keys.as_slice());
+        assert_eq!(r, r.keys().collect());
+        assert_eq!(0, KbHidReport::default().keys().count());
+        let r = report(&[LShift, A, B, C, D, E, F, G]);
+        let keys: Vec<_, 8> = r.keys().collect();
+        assert_eq!(&[LShift], keys.as_slice());
+    }
+
+    #[test]
+    fn released() {
+        let mut r = report(&[A, B, C]);
+        r.released(B);
+        assert_eq!(&[0, 0, A as u8, 0, C as u8, 0, 0, 0], r.as_bytes());
+        let keys: Vec<_, 8> = r.keys().collect();
+        assert_eq!(&[A, C], keys.as_slice());
+
+        let mut r = report(&[LShift, RAlt, A]);
+        r.released(LShift);
+        r.released(D);
+        assert_eq!(report(&[RAlt, A]), r);
+
+        let mut r = report(&[LCtrl, A, B, C, D, E, F, G]);
+        r.released(A);
+        r.released(LCtrl);
+        assert_eq!(report(&[ErrorRollOver]), r);
+    }
+
+    #[test]
+    fn nkro_report() {
+        let all = [LCtrl, A, B, C, D, E, F, G, H, Kb1, Kb2, Space, MediaCalc];
+        let mut r: NkroReport = all.iter().copied().collect();
+        let keys: Vec<_, 16> = r.keys().collect();
+        assert_eq!(&all, keys.as_slice());
+        assert_eq!(33, r.as_bytes().len());
+        assert_eq!(0x01, r.as_bytes()[0]);
+        assert_eq!(0xF0, r.as_bytes()[1]);
+        assert_eq!(0x08, r.as_bytes()[32]);
+        r.released(C);
+        r.released(LCtrl);
+        r.released(MediaCalc);
+        let keys: Vec<_, 16> = r.keys().collect();
+        assert_eq!(&[A, B, D, E, F, G, H, Kb1, Kb2, Space], keys.as_slice());
+        assert!(!keys.contains(&ErrorRollOver));
+        all.iter().for_each(|&kc| r.released(kc));
+        assert_eq!(NkroReport::default(), r);
+    }
+
+    #[test]
+    fn contains() {
+        let r = report(&[LShift, A]);
+        assert!(r.contains(LShift));
+        assert!(r.contains(A));
+        assert!(!r.contains(RShift));
+        assert!(!r.contains(B));
+        assert!(!r.contains(No));
+        assert!(!KbHidReport::default().contains(No));
+
+        let r = report(&[LCtrl, A, B, C, D, E, F, G]);
+        assert!(r.contains(LCtrl));
+        assert!(!r.contains(A));
+        assert!(!r.contains(ErrorRollOver));
+    }
+
+    #[test]
+    fn consumer() {
+        assert_eq!(Some(Consumer::PlayPause), MediaPlayPause.as_consumer());
+        assert_eq!(Some(0xCD), MediaPlayPause.as_consumer_usage());
+        assert_eq!(Some(Consumer::VolumeIncrement), VolUp.as_consumer());
+        assert_eq!(Some(Consumer::VolumeIncrement), MediaVolUp.as_consumer());
+        assert_eq!(0xE9, Consumer::VolumeIncrement as u16);
+        assert_eq!(None, A.as_consumer());
+        assert_eq!(None, MediaSleep.as_consumer());
+    }
+
+    #[test]
+    fn system_control() {
+        assert_eq!(Some(SystemControl::Sleep), MediaSleep.as_system_control());
+        assert_eq!(Some(SystemControl::PowerDown), Power.as_system_control());
+        assert_eq!(None, A.as_system_control());
+        let reports: KeyReports = [A, MediaSleep].iter().copied().collect();
+        assert_eq!(0x82, reports.system());
+    }
+
+    #[test]
+    fn categories() {
+        let letters = [A, M, Z];
+        let digits = [Kb1, Kb5, Kb0, Kp1, Kp0];
+        let functions = [F1, F12, F13, F24];
+        let navigation = [Left, Right, Up, Down, Home, End, PgUp, PgDown];
+        let media = [VolUp, MediaPlayPause, MediaCalc];
+        let others = [No, Enter, Space, Insert, Delete, KpDot, LShift, Power];
+        let all = [
+            &letters[..],
+            &digits,
+            &functions,
+            &navigation,
+            &media,
+            &others,
+        ];
+        for (i, kcs) in all.iter().enumerate() {
+            for &kc in kcs.iter() {
+                assert_eq!(i == 0, kc.is_letter(), "{:?}", kc);
+                assert_eq!(i == 1, kc.is_digit(), "{:?}", kc);
+                assert_eq!(i == 2, kc.is_function(), "{:?}", kc);
+                assert_eq!(i == 3, kc.is_navigation(), "{:?}", kc);
+                assert_eq!(i == 4, kc.is_media(), "{:?}", kc);
+            }
+        }
+        assert_eq!(26, KEY_CODES.iter().filter(|kc| kc.is_letter()).count());
+        assert_eq!(20, KEY_CODES.iter().filter(|kc| kc.is_digit()).count());
+        assert_eq!(24, KEY_CODES.iter().filter(|kc| kc.is_function()).count());
+        assert_eq!(8, KEY_CODES.iter().filter(|kc| kc.is_navigation()).count());
+    }
+
+    #[test]
+    fn union() {
+        let left = report(&[LShift, A, B]);
+        let right = report(&[RCtrl, B, C]);
+        assert_eq!(report(&[LShift, RCtrl, A, B, C]), left.union(&right));
+        let mut r = left.clone();
+        r |= &right;
+        assert_eq!(left.union(&right), r);
+        r |= &report(&[D, E, F, G]);
+        assert_eq!(report(&[LShift, RCtrl, ErrorRollOver]), r);
+        let rollover = report(&[A, B, C, D, E, F, G]);
+        assert_eq!(
+            report(&[LShift, ErrorRollOver]),
+            report(&[LShift]).union(&rollover)
+        );
+    }
+
+    #[test]
+    fn shifted_str() {
+        extern crate std;
+        use std::string::ToString;
+        for c in (b' '..=b'~').map(char::from) {
+            let (kc, shift) = KeyCode::from_char(c).unwrap();
+            if shift {
+                assert_eq!(c.to_string(), kc.shifted_str());
+            } else {
+                assert_ne!(c.to_string(), kc.shifted_str());
+            }
+        }
+        assert_eq!("!", Kb1.shifted_str());
+        assert_eq!("@", Kb2.shifted_str());
+        assert_eq!("\"", Quote.shifted_str());
+        assert_eq!("", Space.shifted_str());
+        assert_eq!("", F1.shifted_str());
+        assert_eq!(None, KeyCode::from_char('é'));
+        assert_eq!(None, KeyCode::from_char('\0'));
+    }
+
+    #[test]
+    fn modifiers_from_byte() {
+        let mods: Vec<_, 8> = KeyCode::modifiers_from_byte(0b0000_0101).collect();
+        assert_eq!(&[LCtrl, LAlt], mods.as_slice());
+        assert_eq!(0, KeyCode::modifiers_from_byte(0).count());
+        for kc in KeyCode::modifiers_from_byte(0xff) {
+            let mut mods = KeyCode::modifiers_from_byte(kc.as_modifier_bit());
+            assert_eq!(Some(kc), mods.next());
+            assert_eq!(None, mods.next());
+        }
+        assert_eq!(8, KeyCode::modifiers_from_byte(0xff).count());
+    }
+
+    #[test]
+    fn media_and_system() {
+        assert!(VolUp.is_media());
+        assert!(MediaPlayPause.is_media());
+        assert!(!MediaSleep.is_media());
+        assert!(!A.is_media());
+        assert!(Power.is_system());
+        assert!(MediaSleep.is_system());
+        assert!(!VolUp.is_system());
+        assert!(!A.is_system());
+    }
+
+    #[test]
+    fn key_reports_routing() {
+        let reports: KeyReports = [VolUp].iter().copied().collect();
+        assert_eq!(&report(&[]), reports.keyboard());
+        assert_eq!(0xE9, reports.consumer());
+        assert_eq!(0, reports.system());
+
+        let reports: KeyReports = [Power].iter().copied().collect();
+        assert_eq!(&report(&[]), reports.keyboard());
+        assert_eq!(0, reports.consumer());
+        assert_eq!(0x81, reports.system());
+
+        let reports: KeyReports = [LShift, A].iter().copied().collect();
+        assert_eq!(&report(&[LShift, A]), reports.keyboard());
+        assert_eq!(0, reports.consumer());
+        assert_eq!(0, reports.system());
+
+        // no usage on the forced page: dropped
+        let mut reports = KeyReports::default();
+        reports.pressed(VolUp, UsagePage::Consumer);
+        reports.pressed(A, UsagePage::Consumer);
+        reports.pressed(VolUp, UsagePage::System);
+        assert_eq!(&report(&[]), reports.keyboard());
+        assert_eq!(0xE9, reports.consumer());
+        assert_eq!(0, reports.system());
+    }
+
+    #[test]
+    fn try_pressed() {
+        let mut r = report(&[A, B, C, D, E]);
+        assert!(r.try_pressed(LShift));
+        assert!(r.try_pressed(F));
+        let full = r.clone();
+        assert!(!r.try_pressed(G));
+        assert_eq!(full, r);
+        assert!(r.try_pressed(RCtrl));
+        assert_eq!(report(&[LShift, RCtrl, A, B, C, D, E, F]), r);
+        r.pressed(G);
+        assert_eq!(report(&[LShift, RCtrl, ErrorRollOver]), r);
+    }
+
+    #[test]
+    fn rollover_policy() {
+        let keys = [A, B, C, D, E, F, G, H];
+        assert_eq!(
+            report(&[ErrorRollOver]),
+            KbHidReport::from_keycodes(keys.iter().copied(), RolloverPolicy::ErrorRollOver)
+        );
+        assert_eq!(
+            report(&[A, B, C, D, E, F]),
+            KbHidReport::from_keycodes(keys.iter().copied(), RolloverPolicy::KeepFirstSix)
+        );
+        // A slot is freed: the next key takes it.
+        assert_eq!(
+            report(&[A, C, D, E, F, G]),
+            KbHidReport::from_keycodes(
+                keys.iter().copied().filter(|&kc| kc != B),
+                RolloverPolicy::KeepFirstSix
+            )
+        );
+    }
+
+    #[test]
+    fn multi_report_merge() {
+        let mut reports = MultiReport::<2>::new();
+        assert_eq!(KbHidReport::default(), reports.merged());
+        reports.set(0, report(&[LCtrl, LShift, A, B]));
+        reports.set(1, report(&[LShift, RAlt, B, C]));
+        assert_eq!(report(&[LCtrl, LShift, RAlt, A, B, C]), reports.merged());
+
+        reports.get_mut(1).unwrap().pressed(D);
+        reports.get_mut(1).unwrap().pressed(E);
+        reports.get_mut(1).unwrap().pressed(F);
+        assert_eq!(
+            report(&[LCtrl, LShift, RAlt, A, B, C, D, E, F]),
+            reports.merged()
+        );
+        reports.get_mut(0).unwrap().pressed(G);
+        assert_eq!(
+            report(&[LCtrl, LShift, RAlt, ErrorRollOver]),
+            reports.merged()
+        );
+        // a source in rollover gives a merged report in rollover
+        let mut reports = MultiReport::<2>::new();
+        reports.set(0, report(&[LCtrl, A]));
+        reports.set(1, report(&[B, C, D, E, F, G, H]));
+        assert_eq!(report(&[LCtrl, ErrorRollOver]), reports.merged());
+        reports.set(1, report(&[B]));
+        assert_eq!(report(&[LCtrl, A, B]), reports.merged());
+    }
 }
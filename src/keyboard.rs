@@ -1,7 +1,8 @@
 //! Keyboard HID device implementation.
 
-use crate::hid::{self, HidDevice, Protocol, ReportType, Subclass};
+use crate::hid::{self, HidClass, HidDevice, Protocol, ReportType, Subclass};
 use crate::key_code::KbHidReport;
+use usb_device::bus::UsbBus;
 
 /// A trait to manage keyboard LEDs.
 ///
@@ -20,6 +21,19 @@ pub trait Leds {
 }
 impl Leds for () {}
 
+/// A transport sending the keyboard reports to the host.
+///
+/// It is implemented by the USB classes, [`Class`](crate::Class) and
+/// [`CompositeClass`](crate::composite::CompositeClass), and can be
+/// implemented by any other transport, as a BLE HID stack. Sending
+/// the reports through a `&mut dyn ReportSink` keeps the firmware
+/// independent of the transport.
+pub trait ReportSink {
+    /// Sends the keyboard report, if it is different from the
+    /// previous one.
+    fn send_keyboard(&mut self, report: &KbHidReport) -> Result<(), hid::Error>;
+}
+
 #[rustfmt::skip]
 const REPORT_DESCRIPTOR: &[u8] = &[
     0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
@@ -56,6 +70,42 @@ const REPORT_DESCRIPTOR: &[u8] = &[
     0xC0,              // End Collection
 ];
 
+/// The report descriptor of a N-key rollover keyboard, corresponding
+/// to the [`NkroReport`](crate::key_code::NkroReport).
+///
+/// This report is not supported in the boot protocol: it is sent on a
+/// report protocol interface, as a custom [`HidDevice`], with a
+/// maximum packet size of at least 33 bytes.
+#[rustfmt::skip]
+pub const NKRO_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop Ctrls)
+    0x09, 0x06,        // Usage (Keyboard)
+    0xA1, 0x01,        // Collection (Application)
+    0x05, 0x07,        //   Usage Page (Kbrd/Keypad)
+    0x19, 0xE0,        //   Usage Minimum (0xE0)
+    0x29, 0xE7,        //   Usage Maximum (0xE7)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x95, 0x08,        //   Report Count (8)
+    0x75, 0x01,        //   Report Size (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs,No Wrap,Linear,Preferred State,No Null Position)
+    0x19, 0x00,        //   Usage Minimum (0x00)
+    0x29, 0xFF,        //   Usage Maximum (0xFF)
+    0x96, 0x00, 0x01,  //   Report Count (256)
+    0x75, 0x01,        //   Report Size (1)
+    0x81, 0x02,        //   Input (Data,Var,Abs,No Wrap,Linear,Preferred State,No Null Position)
+    0x05, 0x08,        //   Usage Page (LEDs)
+    0x19, 0x01,        //   Usage Minimum (Num Lock)
+    0x29, 0x05,        //   Usage Maximum (Kana)
+    0x95, 0x05,        //   Report Count (5)
+    0x75, 0x01,        //   Report Size (1)
+    0x91, 0x02,        //   Output (Data,Var,Abs,No Wrap,Linear,Preferred State,No Null Position,Non-volatile)
+    0x95, 0x01,        //   Report Count (1)
+    0x75, 0x03,        //   Report Size (3)
+    0x91, 0x03,        //   Output (Const,Var,Abs,No Wrap,Linear,Preferred State,No Null Position,Non-volatile)
+    0xC0,              // End Collection
+];
+
 /// A keyboard HID device.
 pub struct Keyboard<L> {
     report: KbHidReport,
@@ -128,3 +178,59 @@ impl<L: Leds> HidDevice for Keyboard<L> {
         Err(hid::Error)
     }
 }
+
+impl<B: UsbBus, L: Leds> ReportSink for HidClass<'_, B, Keyboard<L>> {
+    fn send_keyboard(&mut self, report: &KbHidReport) -> Result<(), hid::Error> {
+        if self.device_mut().set_keyboard_report(report.clone()) {
+            self.write(report.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The LED states, from num lock to kana.
+    #[derive(Default)]
+    struct MockLeds([bool; 5]);
+    impl Leds for MockLeds {
+        fn num_lock(&mut self, status: bool) {
+            self.0[0] = status;
+        }
+        fn caps_lock(&mut self, status: bool) {
+            self.0[1] = status;
+        }
+        fn scroll_lock(&mut self, status: bool) {
+            self.0[2] = status;
+        }
+        fn compose(&mut self, status: bool) {
+            self.0[3] = status;
+        }
+        fn kana(&mut self, status: bool) {
+            self.0[4] = status;
+        }
+    }
+
+    #[test]
+    fn leds() {
+        let mut keyboard = Keyboard::new(MockLeds::default());
+        keyboard.set_report(ReportType::Output, 0, &[0b1]).unwrap();
+        assert_eq!([true, false, false, false, false], keyboard.leds_mut().0);
+        keyboard
+            .set_report(ReportType::Output, 0, &[0b10110])
+            .unwrap();
+        assert_eq!([false, true, true, false, true], keyboard.leds_mut().0);
+        keyboard
+            .set_report(ReportType::Output, 0, &[0b01000])
+            .unwrap();
+        assert_eq!([false, false, false, true, false], keyboard.leds_mut().0);
+
+        // invalid reports are ignored
+        assert!(keyboard.set_report(ReportType::Output, 1, &[0]).is_err());
+        assert!(keyboard.set_report(ReportType::Feature, 0, &[0]).is_err());
+        assert!(keyboard.set_report(ReportType::Output, 0, &[0, 0]).is_err());
+        assert_eq!([false, false, false, true, false], keyboard.leds_mut().0);
+    }
+}
@@ -46,10 +46,17 @@
 /// ```
 pub use keyberon_macros::*;
 
-use crate::action::{Action, HoldTapAction, HoldTapConfig};
-use crate::key_code::KeyCode;
+use crate::action::{
+    Action, CapsWordAction, ConfirmAction, DebugDumpAction, HoldTapAction, HoldTapConfig,
+    LayerTapAction, LayerTapDanceAction, MouseAction, OneShotAction, RgbEvent, SequenceEvent,
+    StickyAction, TapDanceAction, UnicodeMode, DEFAULT_HOLD_TAP_TIMEOUT, MOUSE_MAX_SPEED,
+    MOUSE_MOVE_INTERVAL, MOUSE_WHEEL_INTERVAL, TAPPING_TERM,
+};
+use crate::clock::Clock;
+use crate::key_code::{KeyCode, MouseReport, UsagePage};
 use arraydeque::ArrayDeque;
-use heapless::Vec;
+use core::convert::TryFrom;
+use heapless::{Deque, Vec};
 
 use State::*;
 
@@ -68,6 +75,200 @@ pub type Layers<
     K = KeyCode,
 > = [[[Action<T, K>; C]; R]; L];
 
+/// A keymap owned by a [`LayoutOwned`], storing the actions by value.
+///
+/// Contrary to `&'static` [`Layers`], the keymap lives in RAM and can
+/// be modified at runtime. As the custom events reference a `'static`
+/// payload, an [`Action::Custom`] directly in the keymap does nothing:
+/// it must be nested, for example in an [`Action::MultipleActions`].
+#[derive(Debug, Clone)]
+pub struct OwnedLayers<
+    const C: usize,
+    const R: usize,
+    const L: usize,
+    T: 'static = core::convert::Infallible,
+    K: 'static = KeyCode,
+> {
+    layers: Layers<C, R, L, T, K>,
+}
+
+/// The storage of the actions used by a [`Layout`].
+///
+/// It is implemented by `&'static` [`Layers`], the usual static
+/// keymap, and by [`OwnedLayers`], a keymap that can be modified at
+/// runtime.
+pub trait Keymap<T: 'static, K: 'static> {
+    /// Returns the action at the given layer and coordinates, if it
+    /// exists.
+    fn action(&self, layer: usize, coord: (u8, u8)) -> Option<&Action<T, K>>;
+    /// Returns the action at the given layer and coordinates, if it
+    /// exists, as performed by a press, outliving the borrow of the
+    /// keymap.
+    fn pressed(&self, layer: usize, coord: (u8, u8)) -> Option<KeymapAction<T, K>>;
+    /// Returns the number of layers.
+    fn nb_layers(&self) -> usize;
+}
+
+/// An action performed by a press, see [`Keymap::pressed`].
+#[derive(Debug)]
+pub enum KeymapAction<T: 'static, K: 'static> {
+    /// An action of a `'static` keymap.
+    Static(&'static Action<T, K>),
+    /// A copy of an action of a keymap in RAM.
+    Copied(Action<T, K>),
+}
+
+impl<T, K> core::ops::Deref for KeymapAction<T, K> {
+    type Target = Action<T, K>;
+    fn deref(&self) -> &Action<T, K> {
+        match self {
+            KeymapAction::Static(action) => action,
+            KeymapAction::Copied(action) => action,
+        }
+    }
+}
+
+impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static> Keymap<T, K>
+    for &'static Layers<C, R, L, T, K>
+{
+    fn action(&self, layer: usize, coord: (u8, u8)) -> Option<&Action<T, K>> {
+        self.get(layer)
+            .and_then(|l| l.get(coord.0 as usize))
+            .and_then(|l| l.get(coord.1 as usize))
+    }
+    fn pressed(&self, layer: usize, coord: (u8, u8)) -> Option<KeymapAction<T, K>> {
+        let layers: &'static Layers<C, R, L, T, K> = self;
+        layers
+            .get(layer)
+            .and_then(|l| l.get(coord.0 as usize))
+            .and_then(|l| l.get(coord.1 as usize))
+            .map(KeymapAction::Static)
+    }
+    fn nb_layers(&self) -> usize {
+        L
+    }
+}
+
+impl<const C: usize, const R: usize, const L: usize, T: 'static + Copy, K: 'static + Copy>
+    Keymap<T, K> for OwnedLayers<C, R, L, T, K>
+{
+    fn action(&self, layer: usize, coord: (u8, u8)) -> Option<&Action<T, K>> {
+        self.layers
+            .get(layer)
+            .and_then(|l| l.get(coord.0 as usize))
+            .and_then(|l| l.get(coord.1 as usize))
+    }
+    fn pressed(&self, layer: usize, coord: (u8, u8)) -> Option<KeymapAction<T, K>> {
+        self.action(layer, coord).map(|&a| KeymapAction::Copied(a))
+    }
+    fn nb_layers(&self) -> usize {
+        L
+    }
+}
+
+/// The hand of a key, used by
+/// [`HoldTapConfig::HoldOnOppositeHandPress`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hand {
+    /// The key is on the left hand side of the keyboard.
+    Left,
+    /// The key is on the right hand side of the keyboard.
+    Right,
+}
+
+/// The hand of each key of the switch matrix, `hands[2][3]` being
+/// the hand of the key at row 2, column 3.
+pub type Hands<const C: usize, const R: usize> = [[Hand; C]; R];
+
+/// A layer activated while two keys are held, see
+/// [`Layout::set_gated_layers`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GatedLayer {
+    /// The coordinates of the first key, doing its usual action.
+    pub first: (u8, u8),
+    /// The coordinates of the second key, activating the layer when
+    /// pressed while the first key is held.
+    pub second: (u8, u8),
+    /// The layer activated.
+    pub layer: usize,
+}
+
+/// A combo: keys pressed together performing an action, see
+/// [`Layout::set_combos`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Combo<T: 'static = core::convert::Infallible, K: 'static = KeyCode> {
+    /// The coordinates of the keys of the combo. The action is
+    /// performed at the coordinates of the first key.
+    pub keys: &'static [(u8, u8)],
+    /// The action performed when all the keys are pressed.
+    pub action: Action<T, K>,
+    /// The duration, in ticks (usually milliseconds), from the first
+    /// press, in which all the keys must be pressed.
+    pub timeout: u16,
+}
+
+impl<T, K> Combo<T, K> {
+    fn contains_all(&self, keys: &[(u8, u8)]) -> bool {
+        keys.iter().all(|k| self.keys.contains(k))
+    }
+}
+
+/// A key override: a key code replaced by another one when some
+/// modifiers are active, see [`Layout::set_key_overrides`].
+///
+/// The modifiers are given as a USB HID modifier bitfield (see
+/// [`KeyCode::as_modifier_bit`]). A left modifier bit matches the
+/// modifier of both sides.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyOverride {
+    /// The overridden key code.
+    pub trigger: KeyCode,
+    /// The modifiers that must be active to override the trigger.
+    pub required_mods: u8,
+    /// The modifiers removed from the report while the trigger is
+    /// overridden.
+    pub suppressed_mods: u8,
+    /// The key code sent instead of the trigger.
+    pub replacement: KeyCode,
+}
+
+impl KeyOverride {
+    fn matches(&self, kc: KeyCode, mods: u8) -> bool {
+        let mods = mods | mods >> 4;
+        kc == self.trigger && mods & self.required_mods == self.required_mods
+    }
+}
+
+/// The priority of a press given the action of the key, see
+/// [`Layout::set_press_priority`].
+pub type PressPriority<T, K> = fn(&Action<T, K>) -> u8;
+
+/// A press priority giving the priority to the modifiers, see
+/// [`Layout::set_press_priority`].
+pub fn modifiers_first<T>(action: &Action<T, KeyCode>) -> u8 {
+    match action {
+        Action::KeyCode(kc) if kc.is_modifier() => 1,
+        _ => 0,
+    }
+}
+
+/// The keys of the combos being pressed.
+#[derive(Debug)]
+struct ComboState {
+    keys: Vec<(u8, u8), 8>,
+    elapsed: u16,
+}
+
+/// The number of dynamic macros, see [`Action::MacroRecord`].
+pub const DYNAMIC_MACROS: usize = 2;
+/// The maximum number of events of a dynamic macro, see
+/// [`Action::MacroRecord`].
+pub const DYNAMIC_MACRO_LEN: usize = 32;
+
+/// The elapsed ticks of a held mouse key after their overflow: a
+/// multiple of the move and wheel intervals, past the acceleration.
+const MOUSE_WRAP: u16 = 0x8000;
+
 /// The current event stack.
 ///
 /// Events can be retrieved by iterating over this struct and calling [Stacked::event].
@@ -75,22 +276,60 @@ type Stack = ArrayDeque<[Stacked; 16], arraydeque::behavior::Wrapping>;
 
 /// The layout manager. It takes `Event`s and `tick`s as input, and
 /// generate keyboard reports.
+///
+/// The last generic parameter is the storage of the actions, by
+/// default `&'static` [`Layers`]. See [`LayoutOwned`] for a layout
+/// that can be modified at runtime.
 pub struct Layout<
     const C: usize,
     const R: usize,
     const L: usize,
     T = core::convert::Infallible,
     K = KeyCode,
+    M = &'static Layers<C, R, L, T, K>,
 > where
     T: 'static,
     K: 'static + Copy,
 {
-    layers: &'static [[[Action<T, K>; C]; R]; L],
+    layers: M,
     default_layer: usize,
-    states: Vec<State<T, K>, 64>,
+    overlay_layer: Option<usize>,
+    states: States<T, K>,
     waiting: Option<WaitingState<T, K>>,
+    locked_layers: Vec<usize, 8>,
+    layer_locks: Vec<usize, 8>,
+    tap_dance: Option<TapDanceState<T, K>>,
+    toggle_tap: Option<ToggleTapState>,
+    caps_word: Option<&'static CapsWordAction<K>>,
+    unicode_mode: Option<&'static UnicodeMode<K>>,
+    macros: [Vec<SequenceEvent<K>, DYNAMIC_MACRO_LEN>; DYNAMIC_MACROS],
+    recording: Option<usize>,
+    recording_held: Vec<(u16, K, bool), 64>,
+    armed: Option<((u8, u8), u16)>,
     stacked: Stack,
+    tap_releases: Vec<(u8, u8), 4>,
     tap_hold_tracker: TapHoldTracker,
+    hands: Option<&'static Hands<C, R>>,
+    gated_layers: &'static [GatedLayer],
+    gates: Vec<(u8, u8), 8>,
+    combos: &'static [Combo<T, K>],
+    key_overrides: &'static [KeyOverride],
+    tapping_term: u16,
+    press_priority: Option<PressPriority<T, K>>,
+    combo: Option<ComboState>,
+    active_combos: Vec<&'static Combo<T, K>, 4>,
+    sequences: Vec<SequenceState<K>, 4>,
+    sequence_overflow: bool,
+    typing: Deque<SequenceEvent<K>, 64>,
+    enabled: bool,
+    dirty: bool,
+    reported: Vec<(u16, K), 64>,
+    activity: bool,
+    rgb_events: Vec<RgbEvent, 4>,
+    custom_events: Deque<CustomEvent<T>, 16>,
+    last_tick: Option<u32>,
+    #[cfg(any(test, feature = "hold-tap-log"))]
+    last_hold_tap: Option<HoldTapDecision>,
 }
 
 /// An event on the key matrix.
@@ -151,6 +390,101 @@ impl Event {
     }
 }
 
+/// Virtual rows, after the rows of the physical matrix, for the key
+/// sources that are not part of the matrix.
+///
+/// An [`Event`] is just a pair of coordinates: touch sensors,
+/// encoders, or any source producing clean press and release events
+/// can give their events directly to [`Layout::event`], without
+/// [`Matrix`](crate::matrix::Matrix) or
+/// [`Debouncer`](crate::debounce::Debouncer). The layers just need
+/// rows for these virtual keys, that must not collide with the
+/// physical keys.
+///
+/// # Example
+///
+/// ```
+/// use keyberon::layout::{Event, VirtualKeys};
+/// // a 3 rows matrix, the 4th row of the layers being virtual
+/// const ENCODER: VirtualKeys = VirtualKeys::after_rows(3);
+/// assert_eq!(Event::Press(3, 1), ENCODER.press(0, 1));
+/// assert!(!ENCODER.contains((2, 1)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualKeys {
+    first_row: u8,
+}
+impl VirtualKeys {
+    /// Creates the virtual rows following the `rows` rows of the
+    /// physical matrix.
+    pub const fn after_rows(rows: u8) -> Self {
+        Self { first_row: rows }
+    }
+
+    /// Returns the coordinates of the given virtual key, `row` being
+    /// relative to the first virtual row.
+    ///
+    /// Panics if the row is out of the `u8` range.
+    pub const fn coord(self, row: u8, col: u8) -> (u8, u8) {
+        (self.row(row), col)
+    }
+
+    /// Returns the press event of the given virtual key.
+    ///
+    /// Panics if the row is out of the `u8` range.
+    pub const fn press(self, row: u8, col: u8) -> Event {
+        Event::Press(self.row(row), col)
+    }
+
+    /// Returns the release event of the given virtual key.
+    ///
+    /// Panics if the row is out of the `u8` range.
+    pub const fn release(self, row: u8, col: u8) -> Event {
+        Event::Release(self.row(row), col)
+    }
+
+    const fn row(self, row: u8) -> u8 {
+        match self.first_row.checked_add(row) {
+            Some(row) => row,
+            None => panic!("virtual row out of range"),
+        }
+    }
+
+    /// Returns the press and release events of a step of a rotary
+    /// encoder, to give in turn to [`Layout::event`].
+    ///
+    /// The encoders are on the first virtual row: the clockwise step
+    /// of the encoder `id` is the virtual key `(0, 2 * id)`, and the
+    /// counter clockwise step the virtual key `(0, 2 * id + 1)`. The
+    /// action of a step is thus given by the active layers, as for
+    /// any key.
+    ///
+    /// Panics if `id` is 128 or more, its column being out of the
+    /// `u8` range.
+    ///
+    /// ```
+    /// use keyberon::layout::{Event, VirtualKeys};
+    /// const ENCODERS: VirtualKeys = VirtualKeys::after_rows(3);
+    /// assert_eq!(
+    ///     [Event::Press(3, 3), Event::Release(3, 3)],
+    ///     ENCODERS.encoder_step(1, false),
+    /// );
+    /// ```
+    pub const fn encoder_step(self, id: u8, clockwise: bool) -> [Event; 2] {
+        let col = match id.checked_mul(2) {
+            Some(col) => col + if clockwise { 0 } else { 1 },
+            None => panic!("encoder id out of range"),
+        };
+        [self.press(0, col), self.release(0, col)]
+    }
+
+    /// Returns `true` if the coordinates are the ones of a virtual
+    /// key.
+    pub fn contains(self, (i, _): (u8, u8)) -> bool {
+        i >= self.first_row
+    }
+}
+
 /// Event from custom action.
 #[derive(Debug, PartialEq, Eq, Default)]
 pub enum CustomEvent<T: 'static> {
@@ -177,11 +511,100 @@ impl<T> CustomEvent<T> {
     }
 }
 
+/// The phase of a one shot key.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum OneShotPhase {
+    /// Pressed, without any other key press.
+    Held,
+    /// Pressed, with another key pressed: a normal key.
+    Used,
+    /// Tapped: modifies the next key press.
+    Pending,
+    /// Tapped twice: active until pressed again.
+    Locked,
+}
+
+/// The phase of a key of a hold chord (see [`Action::HoldChord`]).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum ChordPhase {
+    /// The trailing key, pressed on the next tick.
+    Pending,
+    /// Pressed, released the given number of ticks after the release
+    /// of the chord.
+    Pressed(u16),
+    /// Released, the key code staying the given number of ticks.
+    Released(u16),
+}
+
+/// What a sticky key latches.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Latch<K> {
+    /// A key code (see [`Action::Sticky`] and [`Action::OneShot`]).
+    Key(K),
+    /// A layer (see [`Action::OneShotLayer`]).
+    Layer(usize),
+}
+
+/// The one shot part of a sticky key (see [`Action::OneShot`]).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct OneShotState {
+    phase: OneShotPhase,
+    /// The ticks before an unused tap expires.
+    remaining: u16,
+    timeout: u16,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 enum State<T: 'static, K: 'static + Copy> {
-    NormalKey { keycode: K, coord: (u8, u8) },
-    LayerModifier { value: usize, coord: (u8, u8) },
-    Custom { value: &'static T, coord: (u8, u8) },
+    NormalKey {
+        keycode: K,
+        coord: (u8, u8),
+    },
+    RepeatKey {
+        keycode: K,
+        coord: (u8, u8),
+        pressed: bool,
+        remaining: u16,
+        rate: u16,
+    },
+    StickyKey {
+        latch: Latch<K>,
+        coord: (u8, u8),
+        remaining: u16,
+        cancel: &'static [(u8, u8)],
+        one_shot: Option<OneShotState>,
+    },
+    FakeKey {
+        keycode: K,
+        slot: u8,
+    },
+    PagedKey {
+        keycode: K,
+        coord: (u8, u8),
+        page: UsagePage,
+    },
+    LayerModifier {
+        value: usize,
+        coord: (u8, u8),
+    },
+    RetroTap {
+        tap: &'static Action<T, K>,
+        coord: (u8, u8),
+    },
+    ChordKey {
+        keycode: K,
+        coord: (u8, u8),
+        phase: ChordPhase,
+    },
+    Custom {
+        value: &'static T,
+        coord: (u8, u8),
+    },
+    MouseKey {
+        action: MouseAction,
+        coord: (u8, u8),
+        elapsed: u16,
+    },
 }
 impl<T: 'static, K: 'static + Copy> Copy for State<T, K> {}
 impl<T: 'static, K: 'static + Copy> Clone for State<T, K> {
@@ -193,41 +616,468 @@ impl<T: 'static, K: 'static + Copy> Clone for State<T, K> {
 impl<T: 'static, K: 'static + Copy> State<T, K> {
     fn keycode(&self) -> Option<K> {
         match self {
-            NormalKey { keycode, .. } => Some(*keycode),
+            NormalKey { keycode, .. }
+            | StickyKey {
+                latch: Latch::Key(keycode),
+                ..
+            }
+            | FakeKey { keycode, .. }
+            | PagedKey { keycode, .. }
+            | ChordKey {
+                keycode,
+                phase: ChordPhase::Pressed(_) | ChordPhase::Released(_),
+                ..
+            } => Some(*keycode),
+            RepeatKey {
+                keycode,
+                pressed: true,
+                ..
+            } => Some(*keycode),
+            _ => None,
+        }
+    }
+    fn page(&self) -> Option<UsagePage> {
+        match self {
+            PagedKey { page, .. } => Some(*page),
             _ => None,
         }
     }
     fn tick(&self) -> Option<Self> {
-        Some(*self)
+        match *self {
+            RepeatKey {
+                keycode,
+                coord,
+                pressed,
+                remaining,
+                rate,
+            } => {
+                let remaining = remaining.saturating_sub(1);
+                Some(match (remaining, pressed) {
+                    (0, true) => RepeatKey {
+                        keycode,
+                        coord,
+                        pressed: false,
+                        remaining: 1,
+                        rate,
+                    },
+                    (0, false) => RepeatKey {
+                        keycode,
+                        coord,
+                        pressed: true,
+                        remaining: rate.saturating_sub(1).max(1),
+                        rate,
+                    },
+                    _ => RepeatKey {
+                        keycode,
+                        coord,
+                        pressed,
+                        remaining,
+                        rate,
+                    },
+                })
+            }
+            StickyKey {
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Pending,
+                        remaining,
+                        ..
+                    }),
+                ..
+            } => match remaining {
+                1 => None,
+                _ => Some(self.with_one_shot(OneShotPhase::Pending, remaining.saturating_sub(1))),
+            },
+            MouseKey {
+                action,
+                coord,
+                elapsed,
+            } => Some(MouseKey {
+                action,
+                coord,
+                elapsed: elapsed.checked_add(1).unwrap_or(MOUSE_WRAP),
+            }),
+            ChordKey {
+                keycode,
+                coord,
+                phase,
+            } => {
+                let phase = match phase {
+                    ChordPhase::Pending => ChordPhase::Pressed(1),
+                    ChordPhase::Released(1) => return None,
+                    ChordPhase::Released(remaining) => ChordPhase::Released(remaining - 1),
+                    phase => phase,
+                };
+                Some(ChordKey {
+                    keycode,
+                    coord,
+                    phase,
+                })
+            }
+            _ => Some(*self),
+        }
+    }
+    /// The number of ticks until the tick changing more than the
+    /// timers of the state, if any.
+    fn deadline(&self) -> Option<u16> {
+        match *self {
+            RepeatKey { remaining, .. } => Some(remaining),
+            StickyKey {
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Pending,
+                        remaining,
+                        ..
+                    }),
+                ..
+            } if remaining > 0 => Some(remaining),
+            MouseKey { elapsed, .. } => Some((u16::MAX - elapsed).saturating_add(1)),
+            ChordKey {
+                phase: ChordPhase::Pending,
+                ..
+            } => Some(1),
+            ChordKey {
+                phase: ChordPhase::Released(remaining),
+                ..
+            } => Some(remaining),
+            _ => None,
+        }
+    }
+    /// Advances the timers of the state by `ticks`, less than its
+    /// [`deadline`](Self::deadline), as `ticks` calls to `tick`.
+    fn advance(&mut self, ticks: u16) {
+        match self {
+            RepeatKey { remaining, .. }
+            | ChordKey {
+                phase: ChordPhase::Released(remaining),
+                ..
+            } => *remaining -= ticks,
+            StickyKey {
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Pending,
+                        remaining,
+                        ..
+                    }),
+                ..
+            } => *remaining = remaining.saturating_sub(ticks),
+            MouseKey { elapsed, .. } => *elapsed += ticks,
+            _ => (),
+        }
     }
     fn release(&self, c: (u8, u8), custom: &mut CustomEvent<T>) -> Option<Self> {
         match *self {
-            NormalKey { coord, .. } | LayerModifier { coord, .. } if coord == c => None,
+            NormalKey { coord, .. }
+            | RepeatKey { coord, .. }
+            | PagedKey { coord, .. }
+            | LayerModifier { coord, .. }
+            | RetroTap { coord, .. }
+            | MouseKey { coord, .. }
+            | ChordKey {
+                coord,
+                phase: ChordPhase::Pending,
+                ..
+            } if coord == c => None,
+            ChordKey {
+                keycode,
+                coord,
+                phase: ChordPhase::Pressed(remaining),
+            } if coord == c => Some(ChordKey {
+                keycode,
+                coord,
+                phase: ChordPhase::Released(remaining),
+            }),
             Custom { value, coord } if coord == c => {
                 custom.update(CustomEvent::Release(value));
                 None
             }
+            StickyKey {
+                coord,
+                one_shot: Some(OneShotState { phase, timeout, .. }),
+                ..
+            } if coord == c => match phase {
+                OneShotPhase::Held => Some(self.with_one_shot(OneShotPhase::Pending, timeout)),
+                OneShotPhase::Used => None,
+                _ => Some(*self),
+            },
+            _ => Some(*self),
+        }
+    }
+    fn is_sticky_cancelled_by(&self, c: (u8, u8)) -> bool {
+        matches!(self, StickyKey { cancel, .. } if cancel.contains(&c))
+    }
+    fn sticky_press(&self, c: (u8, u8)) -> Option<Self> {
+        match *self {
+            StickyKey { coord, .. } if coord == c => Some(*self),
+            StickyKey {
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Held,
+                        remaining,
+                        ..
+                    }),
+                ..
+            } => Some(self.with_one_shot(OneShotPhase::Used, remaining)),
+            StickyKey {
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Used | OneShotPhase::Locked,
+                        ..
+                    }),
+                ..
+            } => Some(*self),
+            StickyKey {
+                latch,
+                remaining: 1,
+                ..
+            } => match latch {
+                Latch::Key(keycode) => Some(NormalKey { keycode, coord: c }),
+                // the action of the key is already resolved on the layer
+                Latch::Layer(_) => None,
+            },
+            StickyKey {
+                latch,
+                coord,
+                remaining,
+                cancel,
+                one_shot,
+            } => Some(StickyKey {
+                latch,
+                coord,
+                remaining: remaining.saturating_sub(1),
+                cancel,
+                one_shot,
+            }),
+            _ => Some(*self),
+        }
+    }
+    /// Returns the state after a new press of its one shot key:
+    /// locked if tapped, else released.
+    fn one_shot_press(&self, c: (u8, u8)) -> Option<Self> {
+        match *self {
+            StickyKey {
+                coord,
+                one_shot:
+                    Some(OneShotState {
+                        phase: OneShotPhase::Pending,
+                        remaining,
+                        ..
+                    }),
+                ..
+            } if coord == c => Some(self.with_one_shot(OneShotPhase::Locked, remaining)),
+            StickyKey {
+                coord,
+                one_shot: Some(_),
+                ..
+            } if coord == c => None,
             _ => Some(*self),
         }
     }
+    /// Returns the sticky key with its one shot part updated.
+    fn with_one_shot(mut self, phase: OneShotPhase, remaining: u16) -> Self {
+        if let StickyKey {
+            one_shot: Some(one_shot),
+            ..
+        } = &mut self
+        {
+            one_shot.phase = phase;
+            one_shot.remaining = remaining;
+        }
+        self
+    }
+    fn is_one_shot(&self, c: (u8, u8)) -> bool {
+        matches!(
+            self,
+            StickyKey { coord, one_shot: Some(_), .. } if *coord == c
+        )
+    }
+    fn is_sticky(&self, c: (u8, u8)) -> bool {
+        matches!(self, StickyKey { coord, one_shot: None, .. } if *coord == c)
+    }
     fn get_layer(&self) -> Option<usize> {
         match self {
             LayerModifier { value, .. } => Some(*value),
             _ => None,
         }
     }
+    fn get_latched_layer(&self) -> Option<usize> {
+        match self {
+            StickyKey {
+                latch: Latch::Layer(value),
+                ..
+            } => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// The states of the layout, each with a serial number identifying
+/// it while it lives, allowing to follow the key codes from a tick
+/// to the other without comparing them.
+struct States<T: 'static, K: 'static + Copy> {
+    states: Vec<State<T, K>, 64>,
+    serials: Vec<u16, 64>,
+    next_serial: u16,
+}
+impl<T: 'static, K: 'static + Copy> States<T, K> {
+    fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            serials: Vec::new(),
+            next_serial: 0,
+        }
+    }
+    fn push(&mut self, state: State<T, K>) -> Result<(), State<T, K>> {
+        self.states.push(state)?;
+        let _ = self.serials.push(self.next_serial);
+        self.next_serial = self.next_serial.wrapping_add(1);
+        Ok(())
+    }
+    fn remove(&mut self, i: usize) -> State<T, K> {
+        self.serials.remove(i);
+        self.states.remove(i)
+    }
+    /// Replaces each state by the result of `f`, removing it on
+    /// `None`, keeping its serial.
+    fn filter_map(&mut self, mut f: impl FnMut(&State<T, K>) -> Option<State<T, K>>) {
+        let mut i = 0;
+        while i < self.states.len() {
+            match f(&self.states[i]) {
+                Some(state) => {
+                    self.states[i] = state;
+                    i += 1;
+                }
+                None => {
+                    self.remove(i);
+                }
+            }
+        }
+    }
+    fn retain(&mut self, mut f: impl FnMut(&State<T, K>) -> bool) {
+        self.filter_map(|s| Some(*s).filter(|_| f(s)));
+    }
+    /// Iterates on the key codes, with the serial of their state.
+    fn keycodes_with_serial(&self) -> impl Iterator<Item = (u16, K)> + '_ {
+        let keycodes = self.states.iter().map(State::keycode);
+        self.serials
+            .iter()
+            .zip(keycodes)
+            .filter_map(|(&n, k)| Some((n, k?)))
+    }
+}
+impl<T: 'static, K: 'static + Copy> core::ops::Deref for States<T, K> {
+    type Target = [State<T, K>];
+    fn deref(&self) -> &Self::Target {
+        &self.states
+    }
+}
+impl<T: 'static, K: 'static + Copy> core::ops::DerefMut for States<T, K> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.states
+    }
+}
+
+/// Iterates on the serials of key codes given with their serial.
+fn serials<K>(keycodes: &[(u16, K)]) -> impl Iterator<Item = u16> + '_ {
+    keycodes.iter().map(|&(n, _)| n)
+}
+
+/// A sequence being played.
+struct SequenceState<K: 'static> {
+    events: &'static [SequenceEvent<K>],
+    delay: u32,
+    /// Tags the fake keys pressed by the sequence, `0` being the
+    /// typed events.
+    slot: u8,
+}
+
+/// The action of a tap dance being counted.
+#[derive(Debug)]
+enum Dance<T: 'static, K: 'static> {
+    Layers(&'static LayerTapDanceAction),
+    Actions(&'static TapDanceAction<T, K>),
+}
+impl<T, K> Copy for Dance<T, K> {}
+impl<T, K> Clone for Dance<T, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T, K> Dance<T, K> {
+    fn timeout(self) -> u16 {
+        match self {
+            Dance::Layers(action) => action.timeout,
+            Dance::Actions(action) => action.timeout,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TapDanceState<T: 'static, K: 'static> {
+    coord: (u8, u8),
+    action: Dance<T, K>,
+    count: usize,
+    pressed: bool,
+    timeout: u16,
+}
+impl<T, K> Copy for TapDanceState<T, K> {}
+impl<T, K> Clone for TapDanceState<T, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ToggleTapState {
+    coord: (u8, u8),
+    layer: usize,
+    taps: u8,
+    term: u16,
+    count: u8,
+    pressed: bool,
+    timeout: u16,
 }
 
 #[derive(Debug)]
 struct WaitingState<T: 'static, K: 'static> {
     coord: (u8, u8),
     timeout: u16,
+    total: u16,
     delay: u16,
-    hold: &'static Action<T, K>,
-    tap: &'static Action<T, K>,
+    hold: WaitingHold<T, K>,
+    tap: WaitingTap<T, K>,
     config: HoldTapConfig,
 }
 
+/// What to do when a waiting state resolves to a hold.
+#[derive(Debug)]
+enum WaitingHold<T: 'static, K: 'static> {
+    Action(&'static Action<T, K>),
+    Layer(usize),
+    LockLayer(usize),
+    Shifted { key: K, shift: K },
+}
+impl<T: 'static, K: 'static + Copy> Copy for WaitingHold<T, K> {}
+impl<T: 'static, K: 'static + Copy> Clone for WaitingHold<T, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// What to do when a waiting state resolves to a tap.
+#[derive(Debug)]
+enum WaitingTap<T: 'static, K: 'static> {
+    Action(&'static Action<T, K>),
+    /// The bare key code of an auto shift key.
+    Key(K),
+}
+impl<T: 'static, K: 'static + Copy> Copy for WaitingTap<T, K> {}
+impl<T: 'static, K: 'static + Copy> Clone for WaitingTap<T, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
 /// Actions that can be triggered for a key configured for HoldTap.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum WaitingAction {
@@ -240,15 +1090,28 @@ pub enum WaitingAction {
 }
 
 impl<T, K> WaitingState<T, K> {
-    fn tick(&mut self, stacked: &Stack) -> Option<WaitingAction> {
+    fn tick(
+        &mut self,
+        stacked: &Stack,
+        hand: impl Fn((u8, u8)) -> Option<Hand>,
+    ) -> Option<WaitingAction> {
         self.timeout = self.timeout.saturating_sub(1);
         match self.config {
-            HoldTapConfig::Default => (),
+            HoldTapConfig::Default | HoldTapConfig::RetroTap => (),
             HoldTapConfig::HoldOnOtherKeyPress => {
                 if stacked.iter().any(|s| s.event.is_press()) {
                     return Some(WaitingAction::Hold);
                 }
             }
+            HoldTapConfig::HoldOnOppositeHandPress => {
+                if let Some(own) = hand(self.coord) {
+                    if stacked.iter().any(|s| {
+                        s.event.is_press() && hand(s.event.coord()).is_some_and(|h| h != own)
+                    }) {
+                        return Some(WaitingAction::Hold);
+                    }
+                }
+            }
             HoldTapConfig::PermissiveHold => {
                 for (x, s) in stacked.iter().enumerate() {
                     if s.event.is_press() {
@@ -286,39 +1149,142 @@ impl<T, K> WaitingState<T, K> {
     }
 }
 
-/// An iterator over the currently stacked events.
-///
-/// Events can be retrieved by iterating over this struct and calling [Stacked::event].
-pub struct StackedIter<'a>(arraydeque::Iter<'a, Stacked>);
+/// The keys of the matrix mapped by a layout, see
+/// [`Layout::coverage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport<const C: usize, const R: usize> {
+    mapped: [[bool; C]; R],
+}
 
-impl<'a> Iterator for StackedIter<'a> {
-    type Item = &'a Stacked;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+impl<const C: usize, const R: usize> CoverageReport<C, R> {
+    /// Returns `true` if the key at the given coordinates has an
+    /// action on at least one layer.
+    pub fn is_mapped(&self, (i, j): (u8, u8)) -> bool {
+        self.mapped
+            .get(i as usize)
+            .and_then(|r| r.get(j as usize))
+            .copied()
+            .unwrap_or(false)
     }
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.0.size_hint()
+
+    /// Returns `true` if every key of the matrix is mapped.
+    pub fn is_complete(&self) -> bool {
+        self.unmapped().next().is_none()
     }
-}
 
-/// An event, waiting in a stack to be processed.
-#[derive(Debug)]
-pub struct Stacked {
-    event: Event,
-    since: u16,
-}
-impl From<Event> for Stacked {
-    fn from(event: Event) -> Self {
-        Stacked { event, since: 0 }
+    /// Iterates on the coordinates of the keys that are `NoOp` or
+    /// `Trans` on every layer.
+    pub fn unmapped(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.mapped.iter().enumerate().flat_map(|(i, r)| {
+            r.iter()
+                .enumerate()
+                .filter(|(_, &m)| !m)
+                .map(move |(j, _)| (i as u8, j as u8))
+        })
     }
 }
-impl Stacked {
-    fn tick(&mut self) {
-        self.since = self.since.saturating_add(1);
-    }
 
-    /// Get the [Event] from this object.
-    pub fn event(&self) -> Event {
+/// A hold-tap decision, recorded for diagnostics.
+///
+/// Only available with the `hold-tap-log` feature.
+#[cfg(any(test, feature = "hold-tap-log"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoldTapDecision {
+    /// The coordinates of the hold-tap key.
+    pub coord: (u8, u8),
+    /// The resolution of the hold-tap.
+    pub action: WaitingAction,
+    /// The ticks elapsed between the press and the decision.
+    pub elapsed: u16,
+    /// The first event received while the decision was pending, if
+    /// any.
+    pub interrupted_by: Option<Event>,
+}
+
+/// A ring buffer of the last `N` hold-tap decisions, to tune the
+/// timeouts offline, for example by dumping it on a serial port.
+///
+/// Only available with the `hold-tap-log` feature.
+///
+/// ```
+/// # use keyberon::layout::{HoldTapLog, Layout};
+/// # pub static LAYERS: keyberon::layout::Layers<1, 1, 1> = keyberon::layout::layout! {{[A]}};
+/// # let mut layout = Layout::new(&LAYERS);
+/// let mut log = HoldTapLog::<8>::new();
+/// // every tick
+/// layout.tick();
+/// if let Some(decision) = layout.take_hold_tap_decision() {
+///     log.push(decision);
+/// }
+/// ```
+#[cfg(any(test, feature = "hold-tap-log"))]
+pub struct HoldTapLog<const N: usize>(heapless::HistoryBuffer<HoldTapDecision, N>);
+
+#[cfg(any(test, feature = "hold-tap-log"))]
+impl<const N: usize> Default for HoldTapLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "hold-tap-log"))]
+impl<const N: usize> HoldTapLog<N> {
+    /// Creates an empty log.
+    pub const fn new() -> Self {
+        Self(heapless::HistoryBuffer::new())
+    }
+
+    /// Records a decision, dropping the oldest one if the log is
+    /// full.
+    pub fn push(&mut self, decision: HoldTapDecision) {
+        self.0.write(decision);
+    }
+
+    /// Iterates on the recorded decisions, from the oldest to the
+    /// most recent.
+    pub fn iter(&self) -> impl Iterator<Item = &HoldTapDecision> + '_ {
+        self.0.oldest_ordered()
+    }
+
+    /// Removes all the recorded decisions.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// An iterator over the currently stacked events.
+///
+/// Events can be retrieved by iterating over this struct and calling [Stacked::event].
+pub struct StackedIter<'a>(arraydeque::Iter<'a, Stacked>);
+
+impl<'a> Iterator for StackedIter<'a> {
+    type Item = &'a Stacked;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// An event, waiting in a stack to be processed.
+#[derive(Debug)]
+pub struct Stacked {
+    event: Event,
+    since: u16,
+}
+impl From<Event> for Stacked {
+    fn from(event: Event) -> Self {
+        Stacked { event, since: 0 }
+    }
+}
+impl Stacked {
+    fn tick(&mut self) {
+        self.since = self.since.saturating_add(1);
+    }
+
+    /// Get the [Event] from this object.
+    pub fn event(&self) -> Event {
         self.event
     }
 }
@@ -335,33 +1301,438 @@ impl TapHoldTracker {
     }
 }
 
+/// A layout owning its keymap, allowing to modify it at runtime.
+///
+/// The actions are stored by value, see [`OwnedLayers`].
+///
+/// # Example
+///
+/// ```
+/// use keyberon::action::Action;
+/// use keyberon::key_code::KeyCode;
+/// use keyberon::layout::{Event, LayoutOwned};
+///
+/// let mut layout = LayoutOwned::<2, 1, 1>::with_layers([[[
+///     Action::KeyCode(KeyCode::A),
+///     Action::KeyCode(KeyCode::B),
+/// ]]]);
+/// assert!(layout.set_key(0, 0, 1, Action::KeyCode(KeyCode::C)));
+/// layout.event(Event::Press(0, 1));
+/// layout.tick();
+/// assert_eq!(Some(KeyCode::C), layout.keycodes().next());
+/// ```
+pub type LayoutOwned<
+    const C: usize,
+    const R: usize,
+    const L: usize,
+    T = core::convert::Infallible,
+    K = KeyCode,
+> = Layout<C, R, L, T, K, OwnedLayers<C, R, L, T, K>>;
+
 impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Copy>
     Layout<C, R, L, T, K>
 {
     /// Creates a new `Layout` object.
     pub fn new(layers: &'static [[[Action<T, K>; C]; R]; L]) -> Self {
+        Self::with_keymap(layers)
+    }
+}
+
+impl<const C: usize, const R: usize, const L: usize, T: 'static + Copy, K: 'static + Copy>
+    LayoutOwned<C, R, L, T, K>
+{
+    /// Creates a new `LayoutOwned` object, owning a copy of the given
+    /// layers.
+    pub fn with_layers(layers: Layers<C, R, L, T, K>) -> Self {
+        Self::with_keymap(OwnedLayers { layers })
+    }
+
+    /// Returns the layers of the keymap.
+    pub fn layers(&self) -> &Layers<C, R, L, T, K> {
+        &self.layers.layers
+    }
+
+    /// Sets the action of the key on the given layer, row and column.
+    /// Returns `false` if there is no such key.
+    pub fn set_key(&mut self, layer: usize, row: usize, col: usize, action: Action<T, K>) -> bool {
+        match self
+            .layers
+            .layers
+            .get_mut(layer)
+            .and_then(|l| l.get_mut(row))
+            .and_then(|l| l.get_mut(col))
+        {
+            Some(a) => {
+                *a = action;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The magic bytes starting a binary keymap.
+const BINARY_MAGIC: &[u8; 4] = b"KBRN";
+/// The version of the binary keymap format.
+const BINARY_VERSION: u8 = 1;
+
+/// An error while reading or writing a binary keymap, see
+/// [`LayoutOwned::load_binary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data doesn't start with the magic bytes.
+    BadMagic,
+    /// The version of the format is not supported.
+    UnsupportedVersion(u8),
+    /// The number of columns, rows or layers doesn't match the
+    /// layout.
+    BadDimensions,
+    /// The data is shorter than expected.
+    Truncated,
+    /// The data is longer than expected.
+    TrailingBytes,
+    /// The tag of an action is unknown.
+    UnknownAction(u8),
+    /// The value of a key code is invalid.
+    InvalidKeyCode(u8),
+    /// The layer doesn't exist.
+    InvalidLayer(u8),
+    /// The action can't be encoded (see [`LayoutOwned::to_binary`]).
+    UnsupportedAction,
+    /// The buffer is too small for the encoded keymap.
+    BufferTooSmall,
+}
+
+const TAG_NO_OP: u8 = 0;
+const TAG_TRANS: u8 = 1;
+const TAG_KEY_CODE: u8 = 2;
+const TAG_LAYER: u8 = 3;
+const TAG_DEFAULT_LAYER: u8 = 4;
+
+impl<const C: usize, const R: usize, const L: usize> LayoutOwned<C, R, L> {
+    /// Creates a new `LayoutOwned` object from a binary keymap, as
+    /// written by [`to_binary`](Self::to_binary), for example received
+    /// from a configuration tool.
+    ///
+    /// The format starts with a header: the magic bytes `KBRN`, the
+    /// version of the format (currently 1), and the numbers of
+    /// columns, rows and layers, each on a byte. Then, the actions
+    /// follow, layer by layer, row by row, each being a tag byte,
+    /// possibly followed by a payload byte:
+    /// - `0`: `NoOp`,
+    /// - `1`: `Trans`,
+    /// - `2`, key code: `KeyCode`,
+    /// - `3`, layer: `Layer`,
+    /// - `4`, layer: `DefaultLayer`.
+    pub fn load_binary(data: &[u8]) -> Result<Self, FormatError> {
+        let (magic, data) = split(data, 4)?;
+        if magic != BINARY_MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        let (header, mut data) = split(data, 4)?;
+        if header[0] != BINARY_VERSION {
+            return Err(FormatError::UnsupportedVersion(header[0]));
+        }
+        if header[1..].iter().map(|&n| usize::from(n)).ne([C, R, L]) {
+            return Err(FormatError::BadDimensions);
+        }
+        let mut layers = [[[Action::NoOp; C]; R]; L];
+        for action in layers.iter_mut().flatten().flatten() {
+            let (tag, rest) = split(data, 1)?;
+            let (payload, rest) = match tag[0] {
+                TAG_NO_OP | TAG_TRANS => (0, rest),
+                _ => split(rest, 1).map(|(p, rest)| (p[0], rest))?,
+            };
+            data = rest;
+            let layer = || {
+                if usize::from(payload) < L {
+                    Ok(usize::from(payload))
+                } else {
+                    Err(FormatError::InvalidLayer(payload))
+                }
+            };
+            *action = match tag[0] {
+                TAG_NO_OP => Action::NoOp,
+                TAG_TRANS => Action::Trans,
+                TAG_KEY_CODE => match KeyCode::from_u8(payload) {
+                    Some(kc) => Action::KeyCode(kc),
+                    None => return Err(FormatError::InvalidKeyCode(payload)),
+                },
+                TAG_LAYER => Action::Layer(layer()?),
+                TAG_DEFAULT_LAYER => Action::DefaultLayer(layer()?),
+                tag => return Err(FormatError::UnknownAction(tag)),
+            };
+        }
+        if !data.is_empty() {
+            return Err(FormatError::TrailingBytes);
+        }
+        Ok(Self::with_layers(layers))
+    }
+
+    /// Writes the keymap in the binary format read by
+    /// [`load_binary`](Self::load_binary), returning the number of
+    /// bytes written.
+    ///
+    /// Only the `NoOp`, `Trans`, `KeyCode`, `Layer` and
+    /// `DefaultLayer` actions can be encoded.
+    pub fn to_binary(&self, buf: &mut [u8]) -> Result<usize, FormatError> {
+        let dims = [C, R, L].map(|n| u8::try_from(n).map_err(|_| FormatError::BadDimensions));
+        let mut len = 0;
+        let mut write = |bytes: &[u8]| {
+            let dest = buf
+                .get_mut(len..len + bytes.len())
+                .ok_or(FormatError::BufferTooSmall)?;
+            dest.copy_from_slice(bytes);
+            len += bytes.len();
+            Ok(())
+        };
+        write(BINARY_MAGIC)?;
+        write(&[BINARY_VERSION, dims[0]?, dims[1]?, dims[2]?])?;
+        for action in self.layers().iter().flatten().flatten() {
+            let layer = |l: usize| u8::try_from(l).map_err(|_| FormatError::UnsupportedAction);
+            match *action {
+                Action::NoOp => write(&[TAG_NO_OP])?,
+                Action::Trans => write(&[TAG_TRANS])?,
+                Action::KeyCode(kc) => write(&[TAG_KEY_CODE, kc as u8])?,
+                Action::Layer(l) => write(&[TAG_LAYER, layer(l)?])?,
+                Action::DefaultLayer(l) => write(&[TAG_DEFAULT_LAYER, layer(l)?])?,
+                _ => return Err(FormatError::UnsupportedAction),
+            }
+        }
+        Ok(len)
+    }
+}
+
+fn split(data: &[u8], n: usize) -> Result<(&[u8], &[u8]), FormatError> {
+    if data.len() < n {
+        Err(FormatError::Truncated)
+    } else {
+        Ok(data.split_at(n))
+    }
+}
+
+impl<const C: usize, const R: usize, const L: usize, T: 'static, M: Keymap<T, KeyCode>>
+    Layout<C, R, L, T, KeyCode, M>
+{
+    /// Sets the key overrides, applied by
+    /// [`overridden_keycodes`](Layout::overridden_keycodes).
+    ///
+    /// When several key overrides match a key code, the first one is
+    /// used.
+    ///
+    /// ```
+    /// use keyberon::action::k;
+    /// use keyberon::key_code::KeyCode::*;
+    /// use keyberon::layout::{Event, KeyOverride, Layers, Layout};
+    /// static LAYERS: Layers<2, 1, 1> = [[[k(LShift), k(BSpace)]]];
+    /// static OVERRIDES: [KeyOverride; 1] = [KeyOverride {
+    ///     trigger: BSpace,
+    ///     required_mods: 0x02, // shift
+    ///     suppressed_mods: 0x02,
+    ///     replacement: Delete,
+    /// }];
+    /// let mut layout = Layout::new(&LAYERS);
+    /// layout.set_key_overrides(&OVERRIDES);
+    /// layout.event(Event::Press(0, 0));
+    /// layout.tick();
+    /// layout.event(Event::Press(0, 1));
+    /// layout.tick();
+    /// assert_eq!(vec![Delete], layout.overridden_keycodes().collect::<Vec<_>>());
+    /// ```
+    pub fn set_key_overrides(&mut self, key_overrides: &'static [KeyOverride]) {
+        self.key_overrides = key_overrides;
+    }
+
+    /// Iterates on the key codes of the current state, as
+    /// [`keycodes`](Layout::keycodes), with the key overrides
+    /// applied.
+    ///
+    /// As the key overrides are applied on the current state, the
+    /// suppressed modifiers are restored as soon as the trigger is
+    /// released.
+    pub fn overridden_keycodes(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        let mods = self.keycodes().fold(0, |m, kc| m | kc.as_modifier_bit());
+        let key_override = move |kc| {
+            self.key_overrides
+                .iter()
+                .find(move |o: &&KeyOverride| o.matches(kc, mods))
+        };
+        let suppressed = self
+            .keycodes()
+            .filter_map(key_override)
+            .fold(0, |m, o| m | o.suppressed_mods);
+        let suppressed = suppressed | suppressed << 4;
+        self.keycodes()
+            .filter(move |kc| kc.as_modifier_bit() & suppressed == 0)
+            .map(move |kc| key_override(kc).map_or(kc, |o| o.replacement))
+    }
+}
+
+impl<
+        const C: usize,
+        const R: usize,
+        const L: usize,
+        T: 'static,
+        K: 'static + Copy,
+        M: Keymap<T, K>,
+    > Layout<C, R, L, T, K, M>
+{
+    /// Creates a new `Layout` object using the given keymap.
+    pub fn with_keymap(layers: M) -> Self {
         Self {
             layers,
             default_layer: 0,
-            states: Vec::new(),
+            overlay_layer: None,
+            states: States::new(),
             waiting: None,
+            locked_layers: Vec::new(),
+            layer_locks: Vec::new(),
+            tap_dance: None,
+            toggle_tap: None,
+            caps_word: None,
+            unicode_mode: None,
+            macros: Default::default(),
+            recording: None,
+            recording_held: Vec::new(),
+            armed: None,
             stacked: ArrayDeque::new(),
+            tap_releases: Vec::new(),
             tap_hold_tracker: Default::default(),
+            hands: None,
+            gated_layers: &[],
+            gates: Vec::new(),
+            combos: &[],
+            key_overrides: &[],
+            tapping_term: DEFAULT_HOLD_TAP_TIMEOUT,
+            press_priority: None,
+            combo: None,
+            active_combos: Vec::new(),
+            sequences: Vec::new(),
+            sequence_overflow: false,
+            typing: Deque::new(),
+            enabled: true,
+            dirty: false,
+            reported: Vec::new(),
+            activity: false,
+            rgb_events: Vec::new(),
+            custom_events: Deque::new(),
+            last_tick: None,
+            #[cfg(any(test, feature = "hold-tap-log"))]
+            last_hold_tap: None,
         }
     }
     /// Iterates on the key codes of the current state.
+    ///
+    /// Empty if the layout is disabled, see
+    /// [`set_enabled`](Layout::set_enabled). The key overrides are
+    /// not applied, see
+    /// [`overridden_keycodes`](Layout::overridden_keycodes).
     pub fn keycodes(&self) -> impl Iterator<Item = K> + '_ {
+        let enabled = self.enabled;
+        self.states
+            .iter()
+            .filter(move |_| enabled)
+            .filter_map(State::keycode)
+    }
+    /// Iterates on all the key codes the layout considers held,
+    /// for debugging.
+    ///
+    /// Contrary to [`keycodes`](Layout::keycodes), they are given
+    /// even if the layout is disabled. As the layout doesn't limit
+    /// the number of key codes, this shows the keys of a chord
+    /// overflowing the 6 keys of a
+    /// [`KbHidReport`](crate::key_code::KbHidReport), reporting a
+    /// rollover error.
+    pub fn held_keycodes(&self) -> impl Iterator<Item = K> + '_ {
         self.states.iter().filter_map(State::keycode)
     }
+    /// Iterates on the key codes of the current state, with the
+    /// usage page on which they must be sent if forced by an
+    /// [`Action::OnPage`] action.
+    ///
+    /// Collecting them in a [`KeyReports`](crate::key_code::KeyReports)
+    /// gives the reports to send.
+    pub fn keycodes_with_page(&self) -> impl Iterator<Item = (K, Option<UsagePage>)> + '_ {
+        let enabled = self.enabled;
+        self.states
+            .iter()
+            .filter(move |_| enabled)
+            .filter_map(|s| s.keycode().map(|kc| (kc, s.page())))
+    }
+    /// Returns the mouse report of the current state, generated by
+    /// the [`Action::Mouse`] actions.
+    ///
+    /// The displacements are given for this tick: the report should
+    /// be sent after each tick if it is not empty, or if the buttons
+    /// changed.
+    pub fn mouse_report(&self) -> MouseReport {
+        let mut report = MouseReport::default();
+        let states = self.states.iter().filter(|_| self.enabled);
+        for &s in states {
+            match s {
+                MouseKey {
+                    action: MouseAction::Click(buttons),
+                    ..
+                } => report.buttons |= buttons,
+                MouseKey {
+                    action: MouseAction::Move { x, y },
+                    elapsed,
+                    ..
+                } if elapsed % MOUSE_MOVE_INTERVAL == 0 => {
+                    let speed = (1 + elapsed / MOUSE_MOVE_INTERVAL).min(MOUSE_MAX_SPEED);
+                    let scale = |v: i8| (i16::from(v) * speed as i16).clamp(-127, 127) as i8;
+                    report.x = report.x.saturating_add(scale(x));
+                    report.y = report.y.saturating_add(scale(y));
+                }
+                MouseKey {
+                    action: MouseAction::Wheel(wheel),
+                    elapsed,
+                    ..
+                } if elapsed % MOUSE_WHEEL_INTERVAL == 0 => {
+                    report.wheel = report.wheel.saturating_add(wheel)
+                }
+                _ => (),
+            }
+        }
+        report
+    }
     fn waiting_into_hold(&mut self) -> CustomEvent<T> {
         if let Some(w) = &self.waiting {
             let hold = w.hold;
             let coord = w.coord;
+            if let (HoldTapConfig::RetroTap, WaitingTap::Action(tap)) = (w.config, w.tap) {
+                let _ = self.states.push(RetroTap { tap, coord });
+            }
             self.waiting = None;
             if coord == self.tap_hold_tracker.coord {
                 self.tap_hold_tracker.timeout = 0;
             }
-            self.do_action(hold, coord, 0)
+            match hold {
+                WaitingHold::Action(action) => {
+                    self.consume_sticky(action, coord);
+                    self.do_action(action, coord, 0)
+                }
+                WaitingHold::Layer(value) => {
+                    let _ = self.states.push(LayerModifier { value, coord });
+                    CustomEvent::NoEvent
+                }
+                WaitingHold::Shifted { key, shift } => {
+                    self.consume_sticky(&Action::KeyCode(key), coord);
+                    let _ = self.states.push(NormalKey {
+                        coord,
+                        keycode: key,
+                    });
+                    let _ = self.states.push(NormalKey {
+                        coord,
+                        keycode: shift,
+                    });
+                    CustomEvent::NoEvent
+                }
+                WaitingHold::LockLayer(value) => {
+                    let _ = self.locked_layers.push(value);
+                    CustomEvent::NoEvent
+                }
+            }
         } else {
             CustomEvent::NoEvent
         }
@@ -371,11 +1742,36 @@ impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Co
             let tap = w.tap;
             let coord = w.coord;
             self.waiting = None;
-            self.do_action(tap, coord, 0)
+            match tap {
+                WaitingTap::Key(key) => {
+                    self.consume_sticky(&Action::KeyCode(key), coord);
+                    let _ = self.states.push(NormalKey {
+                        coord,
+                        keycode: key,
+                    });
+                    self.caps_word_press(key, coord);
+                    CustomEvent::NoEvent
+                }
+                WaitingTap::Action(tap) => {
+                    self.consume_sticky(tap, coord);
+                    self.do_action(tap, coord, 0)
+                }
+            }
         } else {
             CustomEvent::NoEvent
         }
     }
+    #[cfg(any(test, feature = "hold-tap-log"))]
+    fn log_hold_tap(&mut self, action: WaitingAction, interrupted_by: Option<Event>) {
+        if let Some(w) = &self.waiting {
+            self.last_hold_tap = Some(HoldTapDecision {
+                coord: w.coord,
+                action,
+                elapsed: w.total - w.timeout,
+                interrupted_by,
+            });
+        }
+    }
     fn drop_waiting(&mut self) -> CustomEvent<T> {
         self.waiting = None;
         CustomEvent::NoEvent
@@ -387,71 +1783,768 @@ impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Co
     /// Returns the corresponding `CustomEvent`, allowing to manage
     /// custom actions thanks to the `Action::Custom` variant.
     pub fn tick(&mut self) -> CustomEvent<T> {
-        self.states = self.states.iter().filter_map(State::tick).collect();
+        let custom = self.tick_states();
+        // compared to the key codes of the previous tick, to include
+        // the changes made by `event`, by the serial of their state
+        let enabled = self.enabled;
+        let keycodes = self.states.keycodes_with_serial();
+        let after: Vec<_, 64> = keycodes.filter(|_| enabled).collect();
+        if !serials(&after).eq(serials(&self.reported)) {
+            self.dirty = true;
+            let before = core::mem::replace(&mut self.reported, after);
+            self.record(&before);
+        }
+        custom
+    }
+    fn record(&mut self, before: &[(u16, K)]) {
+        let id = match self.recording {
+            Some(id) => id,
+            None => return,
+        };
+        let after = &self.reported;
+        let held = &mut self.recording_held;
+        let events = &mut self.macros[id];
+        let mut full = false;
+        for &(n, _) in before
+            .iter()
+            .filter(|&&(n, _)| !serials(after).any(|m| m == n))
+        {
+            // ignoring the key codes pressed before the recording
+            if let Some(h) = held.iter_mut().find(|h| h.0 == n) {
+                h.2 = true;
+            }
+            // released in the reverse order of their presses, as
+            // played by a sequence
+            while let Some(&(_, k, true)) = held.last() {
+                held.pop();
+                full |= events.push(SequenceEvent::Release(k)).is_err();
+            }
+        }
+        for &(n, k) in after
+            .iter()
+            .filter(|&&(n, _)| !serials(before).any(|m| m == n))
+        {
+            let _ = held.push((n, k, false));
+            full |= events.push(SequenceEvent::Press(k)).is_err();
+        }
+        if full {
+            self.stop_recording();
+        }
+    }
+    /// Stops the recording of the dynamic macro, releasing the key
+    /// codes still pressed, or dropping the events since all the key
+    /// codes were released if the macro is full.
+    fn stop_recording(&mut self) {
+        let id = match self.recording.take() {
+            Some(id) => id,
+            None => return,
+        };
+        let events = &mut self.macros[id];
+        let held = core::mem::take(&mut self.recording_held);
+        // each release matching the last press not released yet
+        let mut nb_held = 0;
+        let mut released_at = 0;
+        for (i, event) in events.iter().enumerate() {
+            match event {
+                SequenceEvent::Press(_) => nb_held += 1,
+                SequenceEvent::Release(_) => nb_held -= 1,
+                SequenceEvent::Delay { .. } => (),
+            }
+            if nb_held == 0 {
+                released_at = i + 1;
+            }
+        }
+        if events.len() + held.len() > DYNAMIC_MACRO_LEN {
+            events.truncate(released_at);
+        } else {
+            for &(_, k, _) in held.iter().rev() {
+                let _ = events.push(SequenceEvent::Release(k));
+            }
+        }
+    }
+    fn tick_states(&mut self) -> CustomEvent<T> {
+        self.states.filter_map(State::tick);
         self.stacked.iter_mut().for_each(Stacked::tick);
         self.tap_hold_tracker.tick();
-        match &mut self.waiting {
-            Some(w) => match w.tick(&self.stacked) {
-                Some(WaitingAction::Hold) => self.waiting_into_hold(),
-                Some(WaitingAction::Tap) => self.waiting_into_tap(),
-                Some(WaitingAction::NoOp) => self.drop_waiting(),
+        self.play_sequences();
+        if let Some((_, remaining)) = &mut self.armed {
+            *remaining = remaining.saturating_sub(1);
+            if *remaining == 0 {
+                self.armed = None;
+            }
+        }
+        if let Some(dance) = &mut self.tap_dance {
+            dance.timeout = dance.timeout.saturating_sub(1);
+        }
+        if let Some(toggle) = &mut self.toggle_tap {
+            toggle.timeout = toggle.timeout.saturating_sub(1);
+            if !toggle.pressed && toggle.timeout == 0 {
+                self.toggle_tap = None;
+            }
+        }
+        let mut custom = CustomEvent::NoEvent;
+        // the actions tapped on the previous tick
+        for coord in core::mem::take(&mut self.tap_releases) {
+            custom.update(self.release(coord));
+        }
+        if let Some(combo) = &mut self.combo {
+            combo.elapsed = combo.elapsed.saturating_add(1);
+            let (elapsed, keys) = (combo.elapsed, &combo.keys);
+            if !self
+                .combos
+                .iter()
+                .any(|c| elapsed < c.timeout && c.contains_all(keys))
+            {
+                custom = self.resolve_combo();
+            }
+        }
+        let hands = self.hands;
+        let hand = |(i, j): (u8, u8)| {
+            hands
+                .and_then(|h| h.get(i as usize))
+                .and_then(|h| h.get(j as usize))
+                .copied()
+        };
+        custom.update(match &mut self.waiting {
+            Some(w) => match w.tick(&self.stacked, hand) {
+                Some(action) => {
+                    #[cfg(any(test, feature = "hold-tap-log"))]
+                    self.log_hold_tap(action, self.stacked.front().map(Stacked::event));
+                    match action {
+                        WaitingAction::Hold => self.waiting_into_hold(),
+                        WaitingAction::Tap => self.waiting_into_tap(),
+                        WaitingAction::NoOp => self.drop_waiting(),
+                    }
+                }
                 None => CustomEvent::NoEvent,
             },
             None => match self.stacked.pop_front() {
                 Some(s) => self.unstack(s),
                 None => CustomEvent::NoEvent,
             },
+        });
+        // resolved after the events, a tapped action is pressed
+        // during this tick and released on the next one
+        if self.tap_dance.is_some_and(|d| d.timeout == 0) {
+            custom.update(self.resolve_tap_dance());
+        }
+        custom
+    }
+    /// Several time events at once, for a main loop not running at
+    /// the tick rate, equivalent to calling [`tick`](Layout::tick)
+    /// `elapsed` times.
+    ///
+    /// The ticks are only done one by one while events are being
+    /// processed (the events registered before are processed one per
+    /// tick) or a hold tap is waiting. Else, the timers are advanced
+    /// at once up to the next deadline (a one shot timeout, a
+    /// sequence delay, a tap dance timeout...), that is then ticked
+    /// as usual. Thus, the timers resolve at the tick they would have
+    /// without coalescing, and the cost does not depend on `elapsed`
+    /// while the layout is idle. The first custom event is returned,
+    /// the following ones being queued for
+    /// [`take_custom_event`](Layout::take_custom_event).
+    pub fn tick_n(&mut self, elapsed: u16) -> CustomEvent<T> {
+        self.tick_many(elapsed.into())
+    }
+    /// A time event, using the given clock.
+    ///
+    /// The layout is ticked once per millisecond elapsed since the
+    /// previous call (once on the first call), as with
+    /// [`tick_n`](Layout::tick_n), allowing to call this method at a
+    /// variable rate. The number of ticks is clamped to `u16::MAX`,
+    /// the longest timeout of the layout, as after a long suspend.
+    pub fn tick_with_clock(&mut self, clock: &Clock) -> CustomEvent<T> {
+        let now = clock.now();
+        let nb_ticks = match self.last_tick {
+            Some(last) => now.wrapping_sub(last),
+            None => 1,
+        };
+        self.last_tick = Some(now);
+        self.tick_many(nb_ticks)
+    }
+    /// Ticks the layout `nb_ticks` times, at most `u16::MAX`,
+    /// returning the first custom event and queuing the others.
+    fn tick_many(&mut self, nb_ticks: u32) -> CustomEvent<T> {
+        let mut first = CustomEvent::NoEvent;
+        let mut remaining = nb_ticks.min(u16::MAX.into());
+        while remaining > 0 {
+            // the first tick also takes the changes made since the
+            // previous one into account
+            match self.tick() {
+                CustomEvent::NoEvent => (),
+                custom if matches!(first, CustomEvent::NoEvent) => first = custom,
+                custom => {
+                    let _ = self.custom_events.push_back(custom);
+                }
+            }
+            remaining -= 1;
+            let skipped = u32::from(self.next_deadline() - 1).min(remaining);
+            self.advance(skipped as u16);
+            remaining -= skipped;
+        }
+        first
+    }
+    /// The number of ticks until the next tick changing more than the
+    /// timers, at least 1.
+    fn next_deadline(&self) -> u16 {
+        if self.waiting.is_some()
+            || !self.stacked.is_empty()
+            || !self.tap_releases.is_empty()
+            || !self.typing.is_empty()
+        {
+            return 1;
+        }
+        let states = self.states.iter().filter_map(State::deadline);
+        let sequences = self
+            .sequences
+            .iter()
+            .map(|s| u16::try_from(s.delay).unwrap_or(u16::MAX));
+        let armed = self.armed.map(|(_, remaining)| remaining);
+        let tap_dance = self.tap_dance.map(|d| d.timeout);
+        let toggle_tap = self.toggle_tap.filter(|t| !t.pressed).map(|t| t.timeout);
+        // resolved when no combo can be completed anymore
+        let combo = self.combo.as_ref().map(|combo| {
+            self.combos
+                .iter()
+                .filter(|c| c.contains_all(&combo.keys))
+                .map(|c| c.timeout.saturating_sub(combo.elapsed))
+                .max()
+                .unwrap_or(0)
+        });
+        states
+            .chain(sequences)
+            .chain(armed)
+            .chain(tap_dance)
+            .chain(toggle_tap)
+            .chain(combo)
+            .min()
+            .unwrap_or(u16::MAX)
+            .max(1)
+    }
+    /// Advances the timers by `ticks`, less than
+    /// [`next_deadline`](Self::next_deadline), as `ticks` calls to
+    /// `tick`.
+    fn advance(&mut self, ticks: u16) {
+        self.states.iter_mut().for_each(|s| s.advance(ticks));
+        self.tap_hold_tracker.timeout = self.tap_hold_tracker.timeout.saturating_sub(ticks);
+        for seq in &mut self.sequences {
+            seq.delay -= u32::from(ticks);
+        }
+        if let Some((_, remaining)) = &mut self.armed {
+            *remaining -= ticks;
+        }
+        if let Some(dance) = &mut self.tap_dance {
+            dance.timeout -= ticks;
+        }
+        if let Some(toggle) = &mut self.toggle_tap {
+            toggle.timeout = toggle.timeout.saturating_sub(ticks);
+        }
+        if let Some(combo) = &mut self.combo {
+            combo.elapsed = combo.elapsed.saturating_add(ticks);
+        }
+    }
+    fn play_sequences(&mut self) {
+        if let Some(event) = self.typing.pop_front() {
+            self.play_sequence_event(event, 0);
+        }
+        let mut sequences = core::mem::take(&mut self.sequences);
+        for seq in &mut sequences {
+            if seq.delay > 0 {
+                seq.delay -= 1;
+                if seq.delay > 0 {
+                    continue;
+                }
+            }
+            if let Some((event, events)) = seq.events.split_first() {
+                seq.events = events;
+                match *event {
+                    SequenceEvent::Delay { duration } => seq.delay = duration,
+                    event => self.play_sequence_event(event, seq.slot),
+                }
+            }
+        }
+        sequences.retain(|s| s.delay > 0 || !s.events.is_empty());
+        self.sequences = sequences;
+    }
+    /// Stops the sequences being played, releasing the key codes they
+    /// pressed.
+    fn cancel_sequences(&mut self) {
+        if self.sequences.is_empty() && self.typing.is_empty() {
+            return;
+        }
+        self.sequences.clear();
+        self.typing.clear();
+        self.states.retain(|s| !matches!(s, FakeKey { .. }));
+    }
+    fn play_sequence_event(&mut self, event: SequenceEvent<K>, slot: u8) {
+        match event {
+            SequenceEvent::Press(keycode) => {
+                let _ = self.states.push(FakeKey { keycode, slot });
+            }
+            SequenceEvent::Release(_) => {
+                // the key codes being released in the reverse order
+                // of their presses, the last one pressed by this
+                // sequence is released, without comparing them
+                if let Some(i) = self
+                    .states
+                    .iter()
+                    .rposition(|s| matches!(*s, FakeKey { slot: s, .. } if s == slot))
+                {
+                    self.states.remove(i);
+                }
+            }
+            SequenceEvent::Delay { .. } => (),
+        }
+    }
+    fn type_status(&mut self, dump: &DebugDumpAction<K>) {
+        use core::fmt::Write;
+        let mut status = heapless::String::<32>::new();
+        let _ = write!(status, "L{}", self.current_layer());
+        let mut separator = Some(b' ');
+        let held = self.states.iter().filter_map(State::keycode);
+        for c in held.filter_map(dump.modifier) {
+            if !status.as_bytes().contains(&c) {
+                for c in separator.take().into_iter().chain(Some(c)) {
+                    let _ = status.push(char::from(c));
+                }
+            }
+        }
+        let events = status
+            .bytes()
+            .filter_map(dump.from_ascii)
+            .flat_map(|(keycode, bits)| {
+                let modifiers = (0..8)
+                    .filter(move |i| bits & (1 << i) != 0)
+                    .map(move |i| dump.modifier_keys[i]);
+                modifiers
+                    .clone()
+                    .map(SequenceEvent::Press)
+                    .chain([
+                        SequenceEvent::Press(keycode),
+                        SequenceEvent::Release(keycode),
+                    ])
+                    .chain(modifiers.rev().map(SequenceEvent::Release))
+            });
+        self.type_events(events);
+    }
+    fn type_unicode(&mut self, mode: &UnicodeMode<K>, c: char) {
+        let mut units = Vec::<u32, 2>::new();
+        if mode.utf16 {
+            for &unit in c.encode_utf16(&mut [0; 2]).iter() {
+                let _ = units.push(u32::from(unit));
+            }
+        } else {
+            let _ = units.push(u32::from(c));
+        }
+        let digits = units.iter().flat_map(|&unit| {
+            let len = (8 - unit.leading_zeros() / 4).max(4);
+            (0..len)
+                .rev()
+                .map(move |i| mode.digits[(unit >> (4 * i)) as usize & 0xf])
+        });
+        let events = mode
+            .start
+            .iter()
+            .copied()
+            .chain(digits.flat_map(|d| {
+                IntoIterator::into_iter([SequenceEvent::Press(d), SequenceEvent::Release(d)])
+            }))
+            .chain(mode.end.iter().copied());
+        self.type_events(events);
+    }
+    /// Queues the events to type, all of them or none if they don't
+    /// fit, as a partially typed text can't be undone.
+    fn type_events(&mut self, events: impl Iterator<Item = SequenceEvent<K>> + Clone) {
+        if events.clone().count() > self.typing.capacity() - self.typing.len() {
+            return;
+        }
+        for event in events {
+            let _ = self.typing.push_back(event);
         }
     }
     fn unstack(&mut self, stacked: Stacked) -> CustomEvent<T> {
         use Event::*;
         match stacked.event {
             Release(i, j) => {
+                if self
+                    .combo
+                    .as_ref()
+                    .is_some_and(|c| c.keys.contains(&(i, j)))
+                {
+                    // the release is done on the next tick, after the
+                    // presses of the combo
+                    let mut custom = self.resolve_combo();
+                    custom.update(self.restack(stacked));
+                    return custom;
+                }
                 let mut custom = CustomEvent::NoEvent;
-                self.states = self
-                    .states
+                if let Some(pos) = self
+                    .active_combos
                     .iter()
-                    .filter_map(|s| s.release((i, j), &mut custom))
-                    .collect();
+                    .position(|c| c.keys.contains(&(i, j)))
+                {
+                    let (i, j) = self.active_combos.swap_remove(pos).keys[0];
+                    custom = self.release((i, j));
+                }
+                custom.update(self.release((i, j)));
                 custom
             }
-            Press(i, j) => {
-                let action = self.press_as_action((i, j), self.current_layer());
-                self.do_action(action, (i, j), stacked.since)
-            }
+            Press(i, j) => match self.combo_press((i, j), stacked) {
+                Ok(custom) => custom,
+                Err(stacked) => self.press((i, j), stacked.since),
+            },
         }
     }
-    /// Register a key event.
-    pub fn event(&mut self, event: Event) {
-        if let Some(stacked) = self.stacked.push_back(event.into()) {
-            self.waiting_into_hold();
-            self.unstack(stacked);
+    /// Buffers a press of a key of the combos, giving back the event
+    /// if the key is not part of a combo.
+    fn combo_press(
+        &mut self,
+        coord: (u8, u8),
+        stacked: Stacked,
+    ) -> Result<CustomEvent<T>, Stacked> {
+        let (mut keys, elapsed) = match self.combo.take() {
+            Some(ComboState { keys, elapsed }) => (keys, elapsed),
+            None => (Vec::new(), 0),
+        };
+        let is_new = keys.is_empty();
+        let viable =
+            |c: &&Combo<T, K>, keys: &[(u8, u8)]| elapsed < c.timeout && c.contains_all(keys);
+        if keys.push(coord).is_ok() && self.combos.iter().any(|c| viable(&c, &keys)) {
+            let complete = self
+                .combos
+                .iter()
+                .filter(|c| viable(c, &keys))
+                .all(|c| c.keys.len() == keys.len());
+            self.combo = Some(ComboState { keys, elapsed });
+            return Ok(if complete {
+                self.resolve_combo()
+            } else {
+                CustomEvent::NoEvent
+            });
+        }
+        if is_new {
+            return Err(stacked);
         }
+        // not a combo: the press is done on the next tick, after the
+        // pending ones
+        keys.pop();
+        self.combo = Some(ComboState { keys, elapsed });
+        let mut custom = self.resolve_combo();
+        custom.update(self.restack(stacked));
+        Ok(custom)
     }
-    fn press_as_action(&self, coord: (u8, u8), layer: usize) -> &'static Action<T, K> {
-        use crate::action::Action::*;
-        let action = self
-            .layers
-            .get(layer)
-            .and_then(|l| l.get(coord.0 as usize))
-            .and_then(|l| l.get(coord.1 as usize));
-        match action {
-            None => &NoOp,
-            Some(Trans) => {
-                if layer != self.default_layer {
-                    self.press_as_action(coord, self.default_layer)
-                } else {
-                    &NoOp
-                }
+    /// Puts back the event in front of the stack, to be processed on
+    /// the next tick, or processes it right away if the stack is full,
+    /// as pushing it would drop the newest event.
+    fn restack(&mut self, stacked: Stacked) -> CustomEvent<T> {
+        if self.stacked.is_full() {
+            #[cfg(any(test, feature = "hold-tap-log"))]
+            self.log_hold_tap(WaitingAction::Hold, Some(stacked.event));
+            let mut custom = self.waiting_into_hold();
+            custom.update(self.unstack(stacked));
+            return custom;
+        }
+        self.stacked.push_front(stacked);
+        CustomEvent::NoEvent
+    }
+    /// Performs the longest combo of the pressed keys, if any, and
+    /// presses the other keys.
+    fn resolve_combo(&mut self) -> CustomEvent<T> {
+        let keys = match self.combo.take() {
+            Some(combo) => combo.keys,
+            None => return CustomEvent::NoEvent,
+        };
+        let combo = self
+            .combos
+            .iter()
+            .filter(|c| !c.keys.is_empty() && c.keys.iter().all(|k| keys.contains(k)))
+            .fold(None, |best: Option<&'static Combo<T, K>>, c| match best {
+                Some(best) if best.keys.len() >= c.keys.len() => Some(best),
+                _ => Some(c),
+            });
+        let mut custom = CustomEvent::NoEvent;
+        if let Some(combo) = combo {
+            if self.active_combos.push(combo).is_ok() {
+                custom = self.do_action(&combo.action, combo.keys[0], 0);
             }
-            Some(action) => action,
         }
+        for &k in keys
+            .iter()
+            .filter(|k| !combo.is_some_and(|c| c.keys.contains(k)))
+        {
+            custom.update(self.press(k, 0));
+        }
+        custom
     }
-    fn do_action(
-        &mut self,
+    fn release(&mut self, (i, j): (u8, u8)) -> CustomEvent<T> {
+        self.gates.retain(|&c| c != (i, j));
+        if let Some(dance) = &mut self.tap_dance {
+            if dance.coord == (i, j) {
+                dance.pressed = false;
+                dance.timeout = dance.action.timeout();
+            }
+        }
+        if let Some(toggle) = self.toggle_tap.filter(|t| t.coord == (i, j)) {
+            self.toggle_tap = None;
+            if toggle.timeout != 0 {
+                let count = toggle.count + 1;
+                if count >= toggle.taps {
+                    self.toggle_layer(toggle.layer);
+                } else {
+                    self.toggle_tap = Some(ToggleTapState {
+                        count,
+                        pressed: false,
+                        timeout: toggle.term,
+                        ..toggle
+                    });
+                }
+            }
+        }
+        let retro_tap = self.states.iter().find_map(|s| match *s {
+            RetroTap { tap, coord } if coord == (i, j) => Some(tap),
+            _ => None,
+        });
+        let mut custom = CustomEvent::NoEvent;
+        self.states.filter_map(|s| s.release((i, j), &mut custom));
+        if let Some(tap) = retro_tap {
+            // the tap action is released on the next tick
+            custom.update(self.do_action(tap, (i, j), 0));
+            let _ = self.tap_releases.push((i, j));
+        }
+        custom
+    }
+    fn caps_word_press(&mut self, keycode: K, coord: (u8, u8)) {
+        if let Some(caps_word) = self.caps_word {
+            if (caps_word.shifted)(keycode) {
+                let keycode = caps_word.shift;
+                let _ = self.states.push(NormalKey { coord, keycode });
+            } else if !(caps_word.continues)(keycode) {
+                self.caps_word = None;
+            }
+        }
+    }
+    fn toggle_layer(&mut self, layer: usize) {
+        if let Some(i) = self.locked_layers.iter().position(|&l| l == layer) {
+            self.locked_layers.remove(i);
+        } else {
+            let _ = self.locked_layers.push(layer);
+        }
+    }
+    fn press(&mut self, (i, j): (u8, u8), since: u16) -> CustomEvent<T> {
+        // first, as the tap dance interrupted below can start a sequence
+        self.cancel_sequences();
+        let mut custom = CustomEvent::NoEvent;
+        if self.tap_dance.is_some_and(|d| d.coord != (i, j)) {
+            custom = self.resolve_tap_dance();
+        }
+        if self.toggle_tap.is_some_and(|t| t.coord != (i, j)) {
+            self.toggle_tap = None;
+        }
+        self.states
+            .retain(|s| !s.is_sticky_cancelled_by((i, j)) && !matches!(s, RetroTap { .. }));
+        let gates = &self.gates;
+        if let Some(gated) = self
+            .gated_layers
+            .iter()
+            .find(|g| g.second == (i, j) && gates.contains(&g.first))
+        {
+            let value = gated.layer;
+            let _ = self.states.push(LayerModifier {
+                value,
+                coord: (i, j),
+            });
+            return custom;
+        }
+        if self.gated_layers.iter().any(|g| g.first == (i, j)) {
+            let _ = self.gates.push((i, j));
+        }
+        let action = self
+            .press_layer((i, j))
+            .and_then(|l| self.layers.pressed(l, (i, j)))
+            .unwrap_or(KeymapAction::Static(&Action::NoOp));
+        self.consume_sticky(&action, (i, j));
+        custom.update(match action {
+            KeymapAction::Static(action) => self.do_action(action, (i, j), since),
+            KeymapAction::Copied(action) => self.perform_action(&action, (i, j), since),
+        });
+        custom
+    }
+    /// Presses a one shot key, latching the given key codes or layer,
+    /// or locks or releases it if already latched.
+    fn one_shot(&mut self, latches: impl Iterator<Item = Latch<K>>, coord: (u8, u8), timeout: u16) {
+        self.tap_hold_tracker.coord = coord;
+        if self.states.iter().any(|s| s.is_one_shot(coord)) {
+            self.states.filter_map(|s| s.one_shot_press(coord));
+            return;
+        }
+        for latch in latches {
+            let _ = self.states.push(StickyKey {
+                latch,
+                coord,
+                remaining: 1,
+                cancel: &[],
+                one_shot: Some(OneShotState {
+                    phase: OneShotPhase::Held,
+                    remaining: timeout,
+                    timeout,
+                }),
+            });
+        }
+    }
+    fn tap_dance(&mut self, action: Dance<T, K>, coord: (u8, u8)) {
+        self.tap_hold_tracker.coord = coord;
+        let count = match self.tap_dance {
+            Some(dance) if dance.coord == coord => dance.count + 1,
+            _ => 1,
+        };
+        self.tap_dance = Some(TapDanceState {
+            coord,
+            action,
+            count,
+            pressed: true,
+            timeout: action.timeout(),
+        });
+    }
+    fn resolve_tap_dance(&mut self) -> CustomEvent<T> {
+        let dance = match self.tap_dance.take() {
+            Some(dance) => dance,
+            None => return CustomEvent::NoEvent,
+        };
+        match dance.action {
+            Dance::Layers(action) => {
+                if dance.pressed {
+                    let _ = self.states.push(LayerModifier {
+                        value: action.hold,
+                        coord: dance.coord,
+                    });
+                } else if !action.layers.is_empty() {
+                    let layer = action.layers[(dance.count - 1) % action.layers.len()];
+                    let was_locked = self.locked_layers.contains(&layer);
+                    self.locked_layers.retain(|l| !action.layers.contains(l));
+                    if !was_locked {
+                        let _ = self.locked_layers.push(layer);
+                    }
+                }
+                CustomEvent::NoEvent
+            }
+            Dance::Actions(TapDanceAction { actions, .. }) => {
+                let i = dance.count.min(actions.len());
+                let action = match i.checked_sub(1).and_then(|i| actions.get(i)) {
+                    Some(action) => action,
+                    None => return CustomEvent::NoEvent,
+                };
+                let custom = self.do_action(action, dance.coord, 0);
+                if !dance.pressed {
+                    // the key is already released: tap the action
+                    let _ = self.tap_releases.push(dance.coord);
+                }
+                custom
+            }
+        }
+    }
+    /// Consumes the sticky latches, except if the action only changes
+    /// the layers: the latches then apply to the key pressed on the
+    /// new layer. The hold tap actions are consumed once resolved.
+    fn consume_sticky(&mut self, action: &Action<T, K>, coord: (u8, u8)) {
+        use Action::*;
+        if !matches!(
+            action,
+            NoOp | Trans
+                | Layer(_)
+                | DefaultLayer(_)
+                | ToggleLayer(_)
+                | LayerLock
+                | ToggleTap { .. }
+                | HoldTap(_)
+                | LayerTap(_)
+                | LayerTapDance(_)
+                | OneShot(_)
+                | OneShotLayer { .. }
+        ) {
+            self.states.filter_map(|s| s.sticky_press(coord));
+        }
+    }
+    /// Register a key event.
+    ///
+    /// The events can come from any source, see [`VirtualKeys`].
+    ///
+    /// The events are processed in order, one per tick, except for
+    /// the presses registered between two ticks, as a single scan,
+    /// that are ordered by decreasing priority if a press priority is
+    /// set (see [`set_press_priority`](Layout::set_press_priority)).
+    pub fn event(&mut self, event: Event) {
+        self.activity |= event.is_press();
+        if let Some(stacked) = self.stacked.push_back(event.into()) {
+            #[cfg(any(test, feature = "hold-tap-log"))]
+            self.log_hold_tap(WaitingAction::Hold, Some(stacked.event));
+            self.waiting_into_hold();
+            self.unstack(stacked);
+        }
+        if let (Some(priority), Event::Press(i, j)) = (self.press_priority, event) {
+            let cur = priority(self.press_as_action((i, j)));
+            let mut idx = self.stacked.len() - 1;
+            while idx > 0 {
+                let (prev, since) = (self.stacked[idx - 1].event, self.stacked[idx - 1].since);
+                match prev {
+                    Event::Press(i, j)
+                        if since == 0 && priority(self.press_as_action((i, j))) < cur =>
+                    {
+                        self.stacked.swap(idx - 1, idx);
+                        idx -= 1;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    /// Sets the priority of the presses registered between two ticks,
+    /// the higher being processed first, or `None` (the default) to
+    /// process them in their registration order. The priority is
+    /// given by the action of the key on the current layers.
+    ///
+    /// The presses of a chord detected on the same scan are
+    /// registered in the matrix order. [`modifiers_first`] processes
+    /// the modifiers first, sending `Shift+A` instead of `a` followed
+    /// by `Shift`, whatever the position of the keys.
+    pub fn set_press_priority(&mut self, priority: Option<PressPriority<T, K>>) {
+        self.press_priority = priority;
+    }
+    /// The first active layer where the key isn't transparent.
+    fn press_layer(&self, coord: (u8, u8)) -> Option<usize> {
+        self.active_layers()
+            .find(|&l| !matches!(self.layers.action(l, coord), Some(Action::Trans)))
+    }
+    fn press_as_action(&self, coord: (u8, u8)) -> &Action<T, K> {
+        self.press_layer(coord)
+            .and_then(|l| self.layers.action(l, coord))
+            .unwrap_or(&Action::NoOp)
+    }
+    fn do_action(
+        &mut self,
         action: &'static Action<T, K>,
         coord: (u8, u8),
         delay: u16,
+    ) -> CustomEvent<T> {
+        match action {
+            Action::Custom(value) => {
+                assert!(self.waiting.is_none());
+                self.tap_hold_tracker.coord = coord;
+                if self.states.push(State::Custom { value, coord }).is_ok() {
+                    return CustomEvent::Press(value);
+                }
+                CustomEvent::NoEvent
+            }
+            _ => self.perform_action(action, coord, delay),
+        }
+    }
+    /// Performs an action, except a custom one, needing a `'static`
+    /// payload (see [`do_action`](Self::do_action)).
+    fn perform_action(
+        &mut self,
+        action: &Action<T, K>,
+        coord: (u8, u8),
+        delay: u16,
     ) -> CustomEvent<T> {
         assert!(self.waiting.is_none());
         use Action::*;
@@ -468,18 +2561,21 @@ impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Co
                     || coord != self.tap_hold_tracker.coord
                     || self.tap_hold_tracker.timeout == 0
                 {
+                    let timeout = self.timeout_or_term(*timeout);
                     let waiting: WaitingState<T, K> = WaitingState {
                         coord,
-                        timeout: *timeout,
+                        timeout,
+                        total: timeout,
                         delay,
-                        hold,
-                        tap,
+                        hold: WaitingHold::Action(hold),
+                        tap: WaitingTap::Action(tap),
                         config: *config,
                     };
                     self.waiting = Some(waiting);
                     self.tap_hold_tracker.timeout = *tap_hold_interval;
                 } else {
                     self.tap_hold_tracker.timeout = 0;
+                    self.consume_sticky(tap, coord);
                     self.do_action(tap, coord, delay);
                 }
                 // Need to set tap_hold_tracker coord AFTER the checks.
@@ -488,12 +2584,235 @@ impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Co
             &KeyCode(keycode) => {
                 self.tap_hold_tracker.coord = coord;
                 let _ = self.states.push(NormalKey { coord, keycode });
+                self.caps_word_press(keycode, coord);
+            }
+            LayerTap(LayerTapAction {
+                timeout,
+                layer,
+                tap,
+                lock_interval,
+            }) => {
+                if let Some(i) = self.locked_layers.iter().position(|l| l == layer) {
+                    self.locked_layers.remove(i);
+                    self.tap_hold_tracker.timeout = 0;
+                } else {
+                    let double_tap = *lock_interval != 0
+                        && coord == self.tap_hold_tracker.coord
+                        && self.tap_hold_tracker.timeout != 0;
+                    let timeout = self.timeout_or_term(*timeout);
+                    self.waiting = Some(WaitingState {
+                        coord,
+                        timeout,
+                        total: timeout,
+                        delay,
+                        hold: if double_tap {
+                            WaitingHold::LockLayer(*layer)
+                        } else {
+                            WaitingHold::Layer(*layer)
+                        },
+                        tap: WaitingTap::Action(tap),
+                        config: HoldTapConfig::Default,
+                    });
+                    self.tap_hold_tracker.timeout = if double_tap { 0 } else { *lock_interval };
+                }
+                self.tap_hold_tracker.coord = coord;
+            }
+            Sticky(StickyAction {
+                keys,
+                count,
+                cancel,
+            }) => {
+                self.tap_hold_tracker.coord = coord;
+                if self.states.iter().any(|s| s.is_sticky(coord)) {
+                    self.states.retain(|s| !s.is_sticky(coord));
+                } else {
+                    for &keycode in *keys {
+                        let _ = self.states.push(StickyKey {
+                            latch: Latch::Key(keycode),
+                            coord,
+                            remaining: *count,
+                            cancel,
+                            one_shot: None,
+                        });
+                    }
+                }
+            }
+            OneShot(OneShotAction { keys, timeout }) => {
+                self.one_shot(keys.iter().map(|&k| Latch::Key(k)), coord, *timeout)
+            }
+            &OneShotLayer { layer, timeout } => {
+                self.one_shot(core::iter::once(Latch::Layer(layer)), coord, timeout)
+            }
+            &OnPage { key, page } => {
+                self.tap_hold_tracker.coord = coord;
+                let _ = self.states.push(PagedKey {
+                    keycode: key,
+                    coord,
+                    page,
+                });
+            }
+            Confirm(ConfirmAction { action, window }) => {
+                self.tap_hold_tracker.coord = coord;
+                if self.armed.take().is_some_and(|(c, _)| c == coord) {
+                    return self.do_action(action, coord, delay);
+                }
+                self.armed = Some((coord, *window));
+            }
+            LayerTapDance(action) => self.tap_dance(Dance::Layers(action), coord),
+            TapDance(action) => self.tap_dance(Dance::Actions(action), coord),
+            DebugDump(dump) => {
+                self.tap_hold_tracker.coord = coord;
+                self.type_status(dump);
+            }
+            &MacroRecord(id) => {
+                self.tap_hold_tracker.coord = coord;
+                let id = usize::from(id);
+                let recording = self.recording;
+                self.stop_recording();
+                if recording != Some(id) && id < DYNAMIC_MACROS {
+                    self.macros[id].clear();
+                    self.recording = Some(id);
+                }
+            }
+            MacroStop => {
+                self.tap_hold_tracker.coord = coord;
+                self.stop_recording();
+            }
+            &MacroPlay(id) => {
+                self.tap_hold_tracker.coord = coord;
+                if self.recording.is_none() {
+                    if let Some(events) = self.macros.get(usize::from(id)).cloned() {
+                        self.type_events(events.into_iter());
+                    }
+                }
+            }
+            &Unicode(c) => {
+                self.tap_hold_tracker.coord = coord;
+                if let Some(mode) = self.unicode_mode {
+                    self.type_unicode(mode, c);
+                }
+            }
+            &Rgb(event) => {
+                self.tap_hold_tracker.coord = coord;
+                let _ = self.rgb_events.push(event);
+            }
+            &Sequence(events) => {
+                self.tap_hold_tracker.coord = coord;
+                // the first slot not used by the playing sequences
+                let mut slot = 1;
+                while self.sequences.iter().any(|seq| seq.slot == slot) {
+                    slot += 1;
+                }
+                let seq = SequenceState {
+                    events,
+                    delay: 0,
+                    slot,
+                };
+                self.sequence_overflow |= self.sequences.push(seq).is_err();
+            }
+            &ToggleTap {
+                layer,
+                taps,
+                timeout,
+            } => {
+                self.tap_hold_tracker.coord = coord;
+                let count = match self.toggle_tap {
+                    Some(t) if t.coord == coord => t.count,
+                    _ => 0,
+                };
+                self.toggle_tap = Some(ToggleTapState {
+                    coord,
+                    layer,
+                    taps,
+                    term: timeout,
+                    count,
+                    pressed: true,
+                    timeout,
+                });
+                let _ = self.states.push(LayerModifier {
+                    value: layer,
+                    coord,
+                });
+            }
+            &AutoShift {
+                key,
+                shift,
+                timeout,
+            } => {
+                self.tap_hold_tracker.coord = coord;
+                self.waiting = Some(WaitingState {
+                    coord,
+                    timeout,
+                    total: timeout,
+                    delay,
+                    hold: WaitingHold::Shifted { key, shift },
+                    tap: WaitingTap::Key(key),
+                    config: HoldTapConfig::Default,
+                });
+            }
+            &Mouse(action) => {
+                self.tap_hold_tracker.coord = coord;
+                let _ = self.states.push(MouseKey {
+                    action,
+                    coord,
+                    elapsed: 0,
+                });
+            }
+            &HoldRepeat { key, delay, rate } => {
+                self.tap_hold_tracker.coord = coord;
+                let _ = self.states.push(RepeatKey {
+                    keycode: key,
+                    coord,
+                    pressed: true,
+                    remaining: delay,
+                    rate,
+                });
             }
             &MultipleKeyCodes(v) => {
                 self.tap_hold_tracker.coord = coord;
                 for &keycode in *v {
                     let _ = self.states.push(NormalKey { coord, keycode });
+                    self.caps_word_press(keycode, coord);
+                }
+            }
+            &HoldChord(chord) => {
+                // Contrary to `MultipleKeyCodes`, pressing and
+                // releasing all the keys at once, the keys are pressed
+                // and released in order, one per tick, bound to the
+                // key as normal keys.
+                self.tap_hold_tracker.coord = coord;
+                let nb_held = chord.held.len() as u16;
+                for (i, &keycode) in (0..).zip(chord.held) {
+                    // released in reverse order, after the trailing key
+                    let phase = ChordPhase::Pressed(nb_held + 1 - i);
+                    let _ = self.states.push(ChordKey {
+                        keycode,
+                        coord,
+                        phase,
+                    });
                 }
+                let _ = self.states.push(ChordKey {
+                    keycode: chord.key,
+                    coord,
+                    phase: ChordPhase::Pending,
+                });
+            }
+            &GraveEscape(gesc) => {
+                self.tap_hold_tracker.coord = coord;
+                let held = |f: fn(K) -> bool| self.keycodes().any(f);
+                let keycode = if held(gesc.is_shift) && !held(gesc.forces_escape) {
+                    gesc.grave
+                } else {
+                    gesc.escape
+                };
+                let _ = self.states.push(NormalKey { coord, keycode });
+            }
+            &CapsWord(caps_word) => {
+                self.tap_hold_tracker.coord = coord;
+                self.caps_word = match self.caps_word {
+                    Some(_) => None,
+                    None => Some(caps_word),
+                };
             }
             &MultipleActions(v) => {
                 self.tap_hold_tracker.coord = coord;
@@ -511,31 +2830,361 @@ impl<const C: usize, const R: usize, const L: usize, T: 'static, K: 'static + Co
                 self.tap_hold_tracker.coord = coord;
                 self.set_default_layer(*value);
             }
-            Custom(value) => {
+            &ToggleLayer(layer) => {
                 self.tap_hold_tracker.coord = coord;
-                if self.states.push(State::Custom { value, coord }).is_ok() {
-                    return CustomEvent::Press(value);
+                self.toggle_layer(layer);
+            }
+            LayerLock => {
+                self.tap_hold_tracker.coord = coord;
+                let held = self.states.iter().rev().find_map(|s| match s {
+                    LayerModifier { value, .. } => Some(*value),
+                    _ => None,
+                });
+                match held {
+                    Some(layer) => {
+                        self.toggle_layer(layer);
+                        self.layer_locks.retain(|&l| l != layer);
+                        if self.locked_layers.contains(&layer) {
+                            let _ = self.layer_locks.push(layer);
+                        }
+                    }
+                    // the last layer locked by a layer lock still
+                    // locked, the toggled layers staying active
+                    None => {
+                        while let Some(layer) = self.layer_locks.pop() {
+                            if let Some(i) = self.locked_layers.iter().position(|&l| l == layer) {
+                                self.locked_layers.remove(i);
+                                break;
+                            }
+                        }
+                    }
                 }
             }
+            Custom(_) => self.tap_hold_tracker.coord = coord,
         }
         CustomEvent::NoEvent
     }
 
+    /// Enables or disables the layout.
+    ///
+    /// While disabled, the events are processed as usual, but no key
+    /// code is emitted: [`keycodes`](Layout::keycodes) is empty. The
+    /// custom events are still returned by [`tick`](Layout::tick),
+    /// allowing a custom action, typically on a chord (see
+    /// [`chording`](crate::chording)), to re-enable the layout. Useful
+    /// for a lock state or a firmware update mode.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.dirty |= self.enabled != enabled && self.states.iter().any(|s| s.keycode().is_some());
+        self.enabled = enabled;
+    }
+
+    /// Returns `true` if a key was pressed since the last call, and
+    /// clears the flag.
+    ///
+    /// Allows to manage an idle timeout, for example to put the
+    /// keyboard to sleep.
+    pub fn activity_since_reset(&mut self) -> bool {
+        core::mem::take(&mut self.activity)
+    }
+
+    /// Returns `true` if a sequence was dropped since the last call,
+    /// because 4 sequences were already playing, and clears the flag.
+    pub fn sequence_overflowed(&mut self) -> bool {
+        core::mem::take(&mut self.sequence_overflow)
+    }
+
+    /// Returns `true` if the key codes, and thus the report, changed
+    /// since the last call, and clears the flag.
+    ///
+    /// Allows to send the report only when needed, without comparing
+    /// it with the previous one.
+    pub fn take_dirty(&mut self) -> bool {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// Sets the input method of the host used to type the
+    /// [`Action::Unicode`] actions, or disables them with `None`.
+    pub fn set_unicode_mode(&mut self, mode: Option<&'static UnicodeMode<K>>) {
+        self.unicode_mode = mode;
+    }
+
+    /// Returns `true` if the caps word mode is active, see
+    /// [`Action::CapsWord`]. Useful to drive a LED.
+    pub fn is_caps_word(&self) -> bool {
+        self.caps_word.is_some()
+    }
+
+    /// Returns `true` if the layout is enabled, see
+    /// [`set_enabled`](Layout::set_enabled).
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets the hand of each key, used by
+    /// [`HoldTapConfig::HoldOnOppositeHandPress`].
+    pub fn set_hands(&mut self, hands: &'static Hands<C, R>) {
+        self.hands = Some(hands);
+    }
+
+    /// Sets the layers activated by pressing two keys in order.
+    ///
+    /// The first key alone does its usual action. If the second key
+    /// is pressed while the first one is held, the layer is
+    /// activated, instead of the action of the second key, until the
+    /// second key is released. Useful for a thumb cluster where a
+    /// dedicated layer key is awkward.
+    pub fn set_gated_layers(&mut self, gated_layers: &'static [GatedLayer]) {
+        self.gated_layers = gated_layers;
+    }
+
+    /// Sets the combos: keys pressed together performing an action.
+    ///
+    /// When all the keys of a combo are pressed in its `timeout`, the
+    /// action of the combo is performed instead of the actions of the
+    /// keys, until one of the keys is released. If the keys of a
+    /// combo are part of a longer one, the longest combo pressed in
+    /// its timeout wins. Otherwise, as after the timeout, the key
+    /// presses are performed as usual, delayed until the combos are
+    /// resolved.
+    ///
+    /// ```
+    /// use keyberon::action::k;
+    /// use keyberon::key_code::KeyCode::*;
+    /// use keyberon::layout::{Combo, Event, Layers, Layout};
+    /// static LAYERS: Layers<2, 1, 1> = [[[k(J), k(K)]]];
+    /// static COMBOS: [Combo; 1] = [Combo {
+    ///     keys: &[(0, 0), (0, 1)],
+    ///     action: k(Escape),
+    ///     timeout: 50,
+    /// }];
+    /// let mut layout = Layout::new(&LAYERS);
+    /// layout.set_combos(&COMBOS);
+    /// layout.event(Event::Press(0, 0));
+    /// layout.event(Event::Press(0, 1));
+    /// layout.tick();
+    /// layout.tick();
+    /// assert_eq!(vec![Escape], layout.keycodes().collect::<Vec<_>>());
+    /// ```
+    pub fn set_combos(&mut self, combos: &'static [Combo<T, K>]) {
+        self.combos = combos;
+    }
+
+    /// Sets the tapping term, in ticks, used by the hold tap and
+    /// layer tap actions with a `timeout` of
+    /// [`TAPPING_TERM`](crate::action::TAPPING_TERM). It can be
+    /// changed at runtime, for example from a custom action.
+    /// Defaults to [`DEFAULT_HOLD_TAP_TIMEOUT`].
+    ///
+    /// A pending hold tap decision keeps the timeout it started with.
+    pub fn set_tapping_term(&mut self, ticks: u16) {
+        self.tapping_term = ticks;
+    }
+
+    /// Returns the tapping term, see
+    /// [`set_tapping_term`](Layout::set_tapping_term).
+    pub fn tapping_term(&self) -> u16 {
+        self.tapping_term
+    }
+
+    fn timeout_or_term(&self, timeout: u16) -> u16 {
+        if timeout == TAPPING_TERM {
+            self.tapping_term
+        } else {
+            timeout
+        }
+    }
+
+    /// Returns the progress of the pending hold-tap decision, if any.
+    ///
+    /// The result is `(elapsed, total)` in ticks, `total` being the
+    /// timeout of the hold-tap action. It can be used, for example,
+    /// to display a hold progress indicator.
+    pub fn pending_progress(&self) -> Option<(u16, u16)> {
+        self.waiting
+            .as_ref()
+            .map(|w| (w.total - w.timeout, w.total))
+    }
+
+    /// Takes the last hold-tap decision, if not already taken.
+    ///
+    /// Calling this method after each tick and pushing the result in
+    /// a [`HoldTapLog`] allows to record the hold-tap decisions.
+    ///
+    /// Only available with the `hold-tap-log` feature.
+    #[cfg(any(test, feature = "hold-tap-log"))]
+    pub fn take_hold_tap_decision(&mut self) -> Option<HoldTapDecision> {
+        self.last_hold_tap.take()
+    }
+
+    /// Takes the oldest RGB event not already taken, triggered by an
+    /// [`Action::Rgb`] press. Up to 4 events are queued.
+    pub fn take_rgb_event(&mut self) -> Option<RgbEvent> {
+        if self.rgb_events.is_empty() {
+            None
+        } else {
+            Some(self.rgb_events.remove(0))
+        }
+    }
+
+    /// Takes the oldest custom event not already taken, queued when
+    /// several ticks are done at once, see [`tick_n`](Layout::tick_n)
+    /// and [`tick_with_clock`](Layout::tick_with_clock). Up to 16
+    /// events are queued.
+    pub fn take_custom_event(&mut self) -> CustomEvent<T> {
+        self.custom_events.pop_front().unwrap_or_default()
+    }
+
+    /// Returns the action that a press of the key at the given
+    /// coordinates would trigger: the action of the first layer of
+    /// [`active_layers`](Layout::active_layers) where the key is not
+    /// `Trans`, or `NoOp` if there is none.
+    ///
+    /// Useful to display the meaning of the keys, for example the
+    /// payload of the [`Action::Custom`] actions used as user
+    /// defined key codes.
+    pub fn action_at(&self, coord: (u8, u8)) -> &Action<T, K> {
+        self.press_as_action(coord)
+    }
+
+    /// Returns the keys of the matrix that have an action on at least
+    /// one layer.
+    ///
+    /// A key that is `NoOp` or `Trans` on every layer does nothing,
+    /// and is likely a wiring or keymap mistake. A test of the
+    /// firmware can assert that the layout covers every key:
+    ///
+    /// ```
+    /// # use keyberon::layout::Layout;
+    /// pub static LAYERS: keyberon::layout::Layers<2, 1, 2> = keyberon::layout::layout! {
+    ///     { [ A n ] }
+    ///     { [ t B ] }
+    /// };
+    /// assert!(Layout::new(&LAYERS).coverage().is_complete());
+    /// ```
+    pub fn coverage(&self) -> CoverageReport<C, R> {
+        let mut mapped = [[false; C]; R];
+        for (i, row) in mapped.iter_mut().enumerate() {
+            for (j, m) in row.iter_mut().enumerate() {
+                *m = (0..self.layers.nb_layers()).any(|l| {
+                    !matches!(
+                        self.layers.action(l, (i as u8, j as u8)),
+                        None | Some(Action::NoOp) | Some(Action::Trans)
+                    )
+                });
+            }
+        }
+        CoverageReport { mapped }
+    }
+
+    /// Returns the key code that the key of the event would produce
+    /// on the current layer, without modifying the layout.
+    ///
+    /// Returns `None` if the action is not an [`Action::KeyCode`].
+    /// Useful for logging or analytics.
+    pub fn peek_keycode(&self, event: Event) -> Option<K> {
+        match *self.action_at(event.coord()) {
+            Action::KeyCode(kc) => Some(kc),
+            _ => None,
+        }
+    }
+
+    /// Iterates on the payloads of the [`Action::Custom`] actions of
+    /// the keys currently held.
+    pub fn active_customs(&self) -> impl Iterator<Item = &'static T> + '_ {
+        self.states.iter().filter_map(|s| match s {
+            Custom { value, .. } => Some(*value),
+            _ => None,
+        })
+    }
+
+    /// Iterates on the active layers, by decreasing priority:
+    /// - the one shot and sticky layers, the last latched first,
+    /// - the momentary layers, the last pressed first,
+    /// - the locked layers, the last locked first,
+    /// - the overlay layer (see
+    ///   [`set_overlay_layer`](Layout::set_overlay_layer)),
+    /// - the default layer.
+    ///
+    /// The action of a key is the one of the first layer in this
+    /// order that is not `Trans` at its coordinates. Thus, `Trans`
+    /// falls through the stack, and `NoOp` stops it. A key that is
+    /// `Trans` on all the active layers does nothing, as `NoOp`.
+    pub fn active_layers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.state_layers()
+            .chain(self.locked_layers.iter().rev().copied())
+            .chain(self.overlay_layer)
+            .chain(Some(self.default_layer))
+    }
+
     /// Obtain the index of the current active layer
+    ///
+    /// The active layer is the last latched one shot or sticky layer
+    /// if any, else the last pressed layer action if any, else the
+    /// last locked layer if any, else the default layer. It is the
+    /// first layer of [`active_layers`](Layout::active_layers), the
+    /// overlay layer excepted.
+    ///
+    /// The layers are updated when the events are processed, i.e. in
+    /// [`tick`](Layout::tick): calling this method after `tick` gives
+    /// the layer of the report of this tick, as for a status display.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use keyberon::action::{k, l};
+    /// use keyberon::key_code::KeyCode::*;
+    /// use keyberon::layout::{Event, Layers, Layout};
+    /// static LAYERS: Layers<2, 1, 2> = [[[l(1), k(A)]], [[k(B), k(C)]]];
+    /// let mut layout = Layout::new(&LAYERS);
+    /// layout.event(Event::Press(0, 0));
+    /// assert_eq!(0, layout.current_layer());
+    /// layout.tick();
+    /// assert_eq!(1, layout.current_layer());
+    /// assert!(layout.active_layers().eq([1, 0]));
+    /// ```
     pub fn current_layer(&self) -> usize {
-        self.states
-            .iter()
-            .rev()
-            .find_map(State::get_layer)
+        self.state_layers()
+            .next()
+            .or_else(|| self.locked_layers.last().copied())
             .unwrap_or(self.default_layer)
     }
 
+    /// The layers of the states, the latched ones first, the last
+    /// pressed first.
+    fn state_layers(&self) -> impl Iterator<Item = usize> + '_ {
+        let states = || self.states.iter().rev();
+        states()
+            .filter_map(State::get_latched_layer)
+            .chain(states().filter_map(State::get_layer))
+    }
+
     /// Sets the default layer for the layout
     pub fn set_default_layer(&mut self, value: usize) {
-        if value < self.layers.len() {
+        if value < self.layers.nb_layers() {
             self.default_layer = value
         }
     }
+
+    /// Sets the overlay layer, or removes it with `None`.
+    ///
+    /// The overlay layer sits just above the default layer, whatever
+    /// the default layer is, and whatever the other active layers
+    /// are: its non transparent keys win over the default layer, and
+    /// `Trans` on the other layers reveals the overlay before the
+    /// default layer. Useful for a symbol layer shared by several
+    /// base layouts. It doesn't change
+    /// [`current_layer`](Layout::current_layer).
+    pub fn set_overlay_layer(&mut self, value: Option<usize>) {
+        match value {
+            Some(value) if value >= self.layers.nb_layers() => (),
+            _ => self.overlay_layer = value,
+        }
+    }
+
+    /// Returns the overlay layer, if any.
+    pub fn overlay_layer(&self) -> Option<usize> {
+        self.overlay_layer
+    }
 }
 
 #[cfg(test)]
@@ -543,8 +3192,8 @@ mod test {
     extern crate std;
     use super::{Event::*, Layout, *};
     use crate::action::Action::*;
-    use crate::action::HoldTapConfig;
-    use crate::action::{k, l, m};
+    use crate::action::{auto_shift, d, k, l, m, n, td, tg};
+    use crate::action::{GraveEscapeAction, HoldTapConfig};
     use crate::key_code::KeyCode;
     use crate::key_code::KeyCode::*;
     use std::collections::BTreeSet;
@@ -556,6 +3205,21 @@ mod test {
         assert_eq!(expected, tested);
     }
 
+    /// Gives the event, if any, to the layout, ticks it, and checks
+    /// the key codes.
+    #[track_caller]
+    fn step<const NB_COLS: usize, const NB_ROWS: usize, const NB_LAYERS: usize>(
+        layout: &mut Layout<NB_COLS, NB_ROWS, NB_LAYERS>,
+        event: Option<Event>,
+        keys: &[KeyCode],
+    ) {
+        if let Some(e) = event {
+            layout.event(e);
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(keys, layout.keycodes());
+    }
+
     #[test]
     fn basic_hold_tap() {
         static LAYERS: Layers<2, 1, 2> = [
@@ -625,128 +3289,2629 @@ mod test {
             }),
         ]]];
         let mut layout = Layout::new(&LAYERS);
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
-        layout.event(Press(0, 0));
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
+        for _ in 0..15 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Space], layout.keycodes());
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[Space], layout.keycodes());
+        }
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Space, LCtrl], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LCtrl], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn hold_on_press() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction {
+                timeout: 200,
+                hold: k(LAlt),
+                tap: k(Space),
+                config: HoldTapConfig::HoldOnOtherKeyPress,
+                tap_hold_interval: 0,
+            }),
+            k(Enter),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // Press another key before timeout
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt, Enter], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Enter], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        // Press another key after timeout
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 0));
+        for _ in 0..200 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt, Enter], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Enter], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn permissive_hold() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction {
+                timeout: 200,
+                hold: k(LAlt),
+                tap: k(Space),
+                config: HoldTapConfig::PermissiveHold,
+                tap_hold_interval: 0,
+            }),
+            k(Enter),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // Press and release another key before timeout
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt, Enter], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn hold_tap_actions() {
+        static LAYERS: Layers<2, 1, 3> = [
+            [[
+                HoldTap(&HoldTapAction::new(l(2), k(Escape)).with_timeout(10)),
+                HoldTap(&HoldTapAction::new(m(&[LCtrl, C].as_slice()), k(A)).with_timeout(10)),
+            ]],
+            [[Trans, k(B)]],
+            [[Trans, k(D)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // tap
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[Escape]);
+        step(&mut layout, None, &[]);
+
+        // hold: layer 2
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..10 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, Some(Press(0, 1)), &[D]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // hold: several key codes
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        for _ in 0..9 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[LCtrl, C]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn retro_tap() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LCtrl), k(A)).with_config(HoldTapConfig::RetroTap)),
+            k(B),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // held alone: tapped on release
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..199 {
+            step(&mut layout, None, &[]);
+        }
+        for _ in 0..100 {
+            step(&mut layout, None, &[LCtrl]);
+        }
+        step(&mut layout, Some(Release(0, 0)), &[A]);
+        step(&mut layout, None, &[]);
+        step(&mut layout, None, &[]);
+
+        // another key pressed during the hold: no tap
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..199 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[LCtrl]);
+        step(&mut layout, Some(Press(0, 1)), &[LCtrl, B]);
+        step(&mut layout, Some(Release(0, 1)), &[LCtrl]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, None, &[]);
+
+        // the tap is released on the next tick, before the following
+        // events
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..199 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[LCtrl]);
+        layout.event(Release(0, 0));
+        step(&mut layout, Some(Press(0, 1)), &[A]);
+        step(&mut layout, None, &[B]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn tick_n() {
+        static LAYERS: Layers<3, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LCtrl), k(A)).with_timeout(10)),
+            k(B),
+            k(C),
+        ]]];
+        let mut ticked = Layout::new(&LAYERS);
+        let mut coalesced = Layout::new(&LAYERS);
+        let events = [
+            [Press(0, 0), Press(0, 1)],
+            [Release(0, 1), Release(0, 0)],
+            [Press(0, 0), Press(0, 2)],
+            [Release(0, 0), Release(0, 2)],
+        ];
+        for e in events.iter().flatten() {
+            ticked.event(*e);
+            coalesced.event(*e);
+            for _ in 0..3 {
+                ticked.tick();
+            }
+            coalesced.tick_n(3);
+            assert!(ticked.keycodes().eq(coalesced.keycodes()));
+        }
+        for _ in 0..20 {
+            for _ in 0..3 {
+                ticked.tick();
+            }
+            coalesced.tick_n(3);
+            assert!(ticked.keycodes().eq(coalesced.keycodes()));
+        }
+        assert_keys(&[], coalesced.keycodes());
+        assert_eq!(CustomEvent::NoEvent, coalesced.tick_n(0));
+    }
+
+    #[test]
+    fn tick_n_custom_events() {
+        static LAYERS: Layers<2, 1, 1, u8> = [[[Action::Custom(1), Action::Custom(2)]]];
+        let mut layout = Layout::new(&LAYERS);
+        layout.event(Press(0, 0));
+        layout.event(Press(0, 1));
+        layout.event(Release(0, 0));
+        layout.event(Release(0, 1));
+
+        // all the custom events, in order
+        assert_eq!(CustomEvent::Press(&1), layout.tick_n(5));
+        assert_eq!(CustomEvent::Press(&2), layout.take_custom_event());
+        assert_eq!(CustomEvent::Release(&1), layout.take_custom_event());
+        assert_eq!(CustomEvent::Release(&2), layout.take_custom_event());
+        assert_eq!(CustomEvent::NoEvent, layout.take_custom_event());
+    }
+
+    #[test]
+    fn tick_n_deadlines() {
+        use crate::action::{MouseAction, OneShotAction, TapDanceAction};
+        static LAYERS: Layers<5, 1, 1> = [[[
+            OneShot(&OneShotAction {
+                keys: &[LShift],
+                timeout: 100,
+            }),
+            HoldRepeat {
+                key: B,
+                delay: 200,
+                rate: 30,
+            },
+            Sequence(
+                &[
+                    SequenceEvent::Press(C),
+                    SequenceEvent::Delay { duration: 500 },
+                    SequenceEvent::Release(C),
+                    SequenceEvent::Press(D),
+                    SequenceEvent::Release(D),
+                ]
+                .as_slice(),
+            ),
+            Mouse(MouseAction::Move { x: 1, y: 0 }),
+            TapDance(&TapDanceAction {
+                actions: &[k(E), k(F)],
+                timeout: 150,
+            }),
+        ]]];
+        let mut ticked = Layout::new(&LAYERS);
+        let mut coalesced = Layout::new(&LAYERS);
+        let mut step = |events: &[Event], n: u16| {
+            for e in events {
+                ticked.event(*e);
+                coalesced.event(*e);
+            }
+            for _ in 0..n {
+                ticked.tick();
+            }
+            coalesced.tick_n(n);
+            assert!(ticked.keycodes().eq(coalesced.keycodes()));
+            assert_eq!(ticked.mouse_report(), coalesced.mouse_report());
+            coalesced.keycodes().collect::<std::vec::Vec<_>>()
+        };
+        assert_eq!([LShift].as_slice(), step(&[Press(0, 0), Release(0, 0)], 37));
+        assert!(step(&[], 80).is_empty());
+        assert!(step(&[Press(0, 1)], 251).contains(&B));
+        step(&[], 1000);
+        assert!(step(&[Press(0, 2), Release(0, 2)], 333).contains(&C));
+        assert!(!step(&[], 400).contains(&C));
+        step(&[Press(0, 3)], 5000);
+        assert!(!step(&[Press(0, 4)], 149).contains(&E));
+        assert!(step(&[], 7).contains(&E));
+        step(
+            &[Release(0, 4), Press(0, 4), Release(0, 4), Press(0, 4)],
+            200,
+        );
+        assert!(step(&[], 200).contains(&F));
+        step(&[Release(0, 4)], 10);
+        // the mouse moves every 16 ticks, its timer wrapping
+        for _ in 0..16 {
+            step(&[], 1001);
+        }
+        step(&[], u16::MAX);
+        assert!(step(&[Release(0, 1), Release(0, 3)], 10).is_empty());
+    }
+
+    #[test]
+    fn tapping_term() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LCtrl), k(A)).with_layout_term()),
+            HoldTap(&HoldTapAction::new(k(LAlt), k(B)).with_timeout(0)),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let hold = |layout: &mut Layout<2, 1, 1>| {
+            let mut keys = std::vec::Vec::new();
+            layout.event(Press(0, 0));
+            for _ in 0..150 {
+                layout.tick();
+                keys.extend(layout.keycodes());
+            }
+            layout.event(Release(0, 0));
+            layout.tick();
+            keys.extend(layout.keycodes());
+            layout.tick();
+            keys
+        };
+
+        assert_eq!(200, layout.tapping_term());
+        layout.set_tapping_term(100);
+        let keys = hold(&mut layout);
+        assert!(keys.contains(&LCtrl) && !keys.contains(&A));
+        layout.set_tapping_term(250);
+        assert_eq!(&[A], &hold(&mut layout)[..]);
+
+        // a timeout of 0 is not the tapping term: held on the first
+        // tick
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        step(&mut layout, None, &[LAlt]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn hold_tap_configs() {
+        static LAYERS: Layers<4, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LGui), k(A))),
+            HoldTap(&HoldTapAction::new(k(LGui), k(A)).with_config(HoldTapConfig::PermissiveHold)),
+            HoldTap(
+                &HoldTapAction::new(k(LGui), k(A)).with_config(HoldTapConfig::HoldOnOtherKeyPress),
+            ),
+            k(S),
+        ]]];
+        // Plays the events, one per tick, and returns the successive reports.
+        let run = |events: &[Event]| {
+            let mut layout = Layout::new(&LAYERS);
+            let mut reports = std::vec::Vec::<std::vec::Vec<KeyCode>>::new();
+            for e in events.iter().map(Some).chain([None; 3]) {
+                if let Some(&e) = e {
+                    layout.event(e);
+                }
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                let keys = layout.keycodes().collect();
+                if reports.last() != Some(&keys) {
+                    reports.push(keys);
+                }
+            }
+            reports
+        };
+        let nested = |j| run(&[Press(0, j), Press(0, 3), Release(0, 3), Release(0, j)]);
+        let rolled = |j| run(&[Press(0, j), Press(0, 3), Release(0, j), Release(0, 3)]);
+        let tap_nested = [&[][..], &[A], &[A, S], &[A], &[]];
+        let hold_nested = [&[][..], &[LGui], &[LGui, S], &[LGui], &[]];
+        let tap_rolled = [&[][..], &[A], &[A, S], &[S], &[]];
+        let hold_rolled = [&[][..], &[LGui], &[LGui, S], &[S], &[]];
+
+        // Default: only the timeout gives a hold.
+        assert_eq!(tap_nested, &nested(0)[..]);
+        assert_eq!(tap_rolled, &rolled(0)[..]);
+        // PermissiveHold: a nested tap gives a hold, a roll stays a tap.
+        assert_eq!(hold_nested, &nested(1)[..]);
+        assert_eq!(tap_rolled, &rolled(1)[..]);
+        // HoldOnOtherKeyPress: any other key press gives a hold.
+        assert_eq!(hold_nested, &nested(2)[..]);
+        assert_eq!(hold_rolled, &rolled(2)[..]);
+    }
+
+    #[test]
+    fn auto_shift_key() {
+        static LAYERS: Layers<2, 1, 1> = [[[auto_shift(Kb1), k(B)]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // tap: the bare key code
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[Kb1]);
+        step(&mut layout, None, &[]);
+
+        // hold: shifted after the timeout
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..174 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[Kb1, LShift]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // the following keys are delayed until the resolution
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[Kb1]);
+        step(&mut layout, None, &[Kb1, B]);
+        step(&mut layout, None, &[B]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn mouse_keys() {
+        use crate::action::{MouseAction::*, MOUSE_WHEEL_INTERVAL};
+        use crate::key_code::MouseReport;
+        static LAYERS: Layers<4, 1, 2> = [
+            [[l(1), k(D), k(W), k(Q)]],
+            [[
+                Trans,
+                Mouse(Move { x: 2, y: 0 }),
+                Mouse(Click(1)),
+                Mouse(Wheel(-1)),
+            ]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // moving right, accelerating up to the max speed
+        layout.event(Press(0, 1));
+        let mut moves = std::vec::Vec::new();
+        for _ in 0..200 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            let report = layout.mouse_report();
+            assert_eq!(0, report.y);
+            if report.x != 0 {
+                moves.push(report.x);
+            }
+        }
+        assert_eq!(
+            &[2, 4, 6, 8, 10, 12, 14, 16, 16, 16, 16, 16, 16],
+            &moves[..]
+        );
+        assert_keys(&[], layout.keycodes());
+
+        // still moving after the overflow of the elapsed ticks
+        for s in layout.states.iter_mut() {
+            if let MouseKey { elapsed, .. } = s {
+                *elapsed = u16::MAX - 15;
+            }
+        }
+        assert_eq!(16, layout.mouse_report().x);
+        for _ in 0..15 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(0, layout.mouse_report().x);
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(16, layout.mouse_report().x);
+
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(MouseReport::default(), layout.mouse_report());
+
+        // clicking and scrolling together
+        layout.event(Press(0, 2));
+        layout.event(Press(0, 3));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.mouse_report().buttons);
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        let report = layout.mouse_report();
+        assert_eq!((1, -1), (report.buttons, report.wheel));
+        for _ in 0..MOUSE_WHEEL_INTERVAL - 1 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(0, layout.mouse_report().wheel);
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(-1, layout.mouse_report().wheel);
+        layout.event(Release(0, 2));
+        layout.event(Release(0, 3));
+        layout.event(Release(0, 0));
+        for _ in 0..3 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!(MouseReport::default(), layout.mouse_report());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn unicode() {
+        use crate::action::UnicodeMode;
+        static LAYERS: Layers<3, 1, 1> = [[[
+            Unicode('→'),
+            Unicode('😀'),
+            MultipleActions(
+                &[Unicode('😀'), Unicode('😀'), Unicode('😀'), Unicode('😀')].as_slice(),
+            ),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let typed = |layout: &mut Layout<3, 1, 1>, j| {
+            let mut reports = std::vec::Vec::<std::vec::Vec<KeyCode>>::new();
+            layout.event(Press(0, j));
+            layout.event(Release(0, j));
+            for _ in 0..40 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                let keys = layout.keycodes().collect();
+                if reports.last() != Some(&keys) {
+                    reports.push(keys);
+                }
+            }
+            reports
+        };
+
+        // no input method: nothing typed
+        assert!(typed(&mut layout, 0).concat().is_empty());
+
+        layout.set_unicode_mode(Some(&UnicodeMode::LINUX));
+        #[rustfmt::skip]
+        let expected = [
+            &[][..], &[LCtrl], &[LCtrl, LShift], &[LCtrl, LShift, U], &[LCtrl, LShift],
+            &[LCtrl], &[], &[Kb2], &[], &[Kb1], &[], &[Kb9], &[], &[Kb2], &[],
+            &[Space], &[],
+        ];
+        assert_eq!(&expected, &typed(&mut layout, 0)[..]);
+
+        // U+1F600: all the digits
+        let digits: std::vec::Vec<_> = typed(&mut layout, 1)
+            .into_iter()
+            .filter(|keys| keys.len() == 1 && !keys[0].is_modifier() && keys[0] != Space)
+            .collect();
+        let expected = [Kb1, F, Kb6, Kb0, Kb0];
+        assert_eq!(&expected[..], &digits.concat()[..]);
+
+        // macOS: surrogate pair
+        layout.set_unicode_mode(Some(&UnicodeMode::MAC_OS));
+        let digits: std::vec::Vec<_> = typed(&mut layout, 1)
+            .into_iter()
+            .filter(|keys| keys.len() == 2)
+            .map(|keys| keys[1])
+            .collect();
+        let expected = [D, Kb8, Kb3, D, D, E, Kb0, Kb0];
+        assert_eq!(&expected[..], &digits[..]);
+
+        // not enough room: the codes that don't fit are not typed,
+        // rather than partially typed
+        layout.set_unicode_mode(Some(&UnicodeMode::LINUX));
+        layout.event(Press(0, 2));
+        layout.event(Release(0, 2));
+        let mut starts = 0;
+        let mut ends = 0;
+        let mut prev = std::vec::Vec::new();
+        for _ in 0..100 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            let keys: std::vec::Vec<_> = layout.keycodes().collect();
+            if keys != prev {
+                starts += usize::from(keys == [LCtrl, LShift, U]);
+                ends += usize::from(keys == [Space]);
+            }
+            prev = keys;
+        }
+        assert_eq!((3, 3), (starts, ends));
+    }
+
+    #[test]
+    fn dynamic_macro() {
+        const PLAY: Action = MacroPlay(0);
+        static LAYERS: Layers<6, 1, 1> = [[[
+            MacroRecord(0),
+            MacroStop,
+            PLAY,
+            k(F),
+            k(O),
+            MultipleActions(&[PLAY; 11].as_slice()),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let tap = |layout: &mut Layout<6, 1, 1>, j, keys: &[KeyCode]| {
+            layout.event(Press(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.keycodes());
+            layout.event(Release(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        };
+
+        // recording "foo"
+        tap(&mut layout, 0, &[]);
+        tap(&mut layout, 3, &[F]);
+        tap(&mut layout, 4, &[O]);
+        tap(&mut layout, 4, &[O]);
+        tap(&mut layout, 1, &[]);
+
+        // playing it, one event per tick
+        layout.event(Press(0, 2));
+        layout.event(Release(0, 2));
+        for keys in [&[][..], &[F], &[], &[O], &[], &[O], &[], &[]] {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.keycodes());
+        }
+
+        // not enough room: the macros that don't fit are not played,
+        // rather than partially played
+        layout.event(Press(0, 5));
+        layout.event(Release(0, 5));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(10 * 6, layout.typing.len());
+        layout.typing.clear();
+
+        // recording a press done by `event` on overflow of the stack
+        tap(&mut layout, 0, &[]);
+        layout.event(Press(0, 3));
+        for _ in 0..16 {
+            layout.event(Release(0, 4));
+        }
+        assert_keys(&[F], layout.keycodes());
+        for _ in 0..17 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        step(&mut layout, Some(Release(0, 3)), &[]);
+        tap(&mut layout, 1, &[]);
+        assert_eq!(
+            &[SequenceEvent::Press(F), SequenceEvent::Release(F)],
+            &layout.macros[0][..]
+        );
+
+        // recording a roll: F, released first, is released with O
+        tap(&mut layout, 0, &[]);
+        step(&mut layout, Some(Press(0, 3)), &[F]);
+        step(&mut layout, Some(Press(0, 4)), &[F, O]);
+        step(&mut layout, Some(Release(0, 3)), &[O]);
+        step(&mut layout, Some(Release(0, 4)), &[]);
+        tap(&mut layout, 1, &[]);
+        let roll = [
+            SequenceEvent::Press(F),
+            SequenceEvent::Press(O),
+            SequenceEvent::Release(O),
+            SequenceEvent::Release(F),
+        ];
+        assert_eq!(&roll, &layout.macros[0][..]);
+    }
+
+    #[test]
+    fn dynamic_macro_full() {
+        static LAYERS: Layers<4, 1, 1> = [[[MacroRecord(1), MacroPlay(1), k(A), k(B)]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut step = |e| {
+            layout.event(e);
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        };
+        step(Press(0, 0));
+        step(Release(0, 0));
+        for _ in 0..DYNAMIC_MACRO_LEN / 2 - 1 {
+            step(Press(0, 2));
+            step(Release(0, 2));
+        }
+        // the macro is full while B is held: B is dropped
+        step(Press(0, 2));
+        step(Press(0, 3));
+        step(Release(0, 3));
+        step(Release(0, 2));
+        assert_eq!(None, layout.recording);
+        let events = &layout.macros[1];
+        assert_eq!(DYNAMIC_MACRO_LEN - 2, events.len());
+        assert!(events
+            .iter()
+            .all(|e| matches!(e, SequenceEvent::Press(A) | SequenceEvent::Release(A))));
+    }
+
+    #[test]
+    fn one_shot_layer() {
+        static LAYERS: Layers<3, 1, 2> = [
+            [[
+                OneShotLayer {
+                    layer: 1,
+                    timeout: 100,
+                },
+                k(A),
+                k(B),
+            ]],
+            [[Trans, k(Kb1), k(Kb2)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        let tap = |layout: &mut Layout<3, 1, 2>, j, keys: &[KeyCode]| {
+            step(layout, Some(Press(0, j)), keys);
+            step(layout, Some(Release(0, j)), &[]);
+        };
+
+        // tap: the next key press only is on the layer
+        tap(&mut layout, 0, &[]);
+        tap(&mut layout, 1, &[Kb1]);
+        tap(&mut layout, 1, &[A]);
+
+        // hold: momentary layer
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        tap(&mut layout, 1, &[Kb1]);
+        tap(&mut layout, 2, &[Kb2]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        tap(&mut layout, 1, &[A]);
+
+        // unused tap: expires
+        tap(&mut layout, 0, &[]);
+        for _ in 0..100 {
+            step(&mut layout, None, &[]);
+        }
+        tap(&mut layout, 1, &[A]);
+
+        // double tap: locked until pressed again
+        tap(&mut layout, 0, &[]);
+        tap(&mut layout, 0, &[]);
+        tap(&mut layout, 1, &[Kb1]);
+        tap(&mut layout, 2, &[Kb2]);
+        tap(&mut layout, 0, &[]);
+        tap(&mut layout, 1, &[A]);
+    }
+
+    #[test]
+    fn caps_word() {
+        use crate::action::CapsWordAction;
+        static LAYERS: Layers<8, 1, 1> = [[[
+            CapsWord(&CapsWordAction::KEY_CODES),
+            k(F),
+            k(O),
+            k(Space),
+            k(B),
+            k(Minus),
+            k(Kb1),
+            k(Enter),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut tap = |j, keys: &[KeyCode]| {
+            step(&mut layout, Some(Press(0, j)), keys);
+            step(&mut layout, Some(Release(0, j)), &[]);
+        };
+
+        // "FOO bar": shifted until space
+        tap(0, &[]);
+        tap(1, &[F, LShift]);
+        tap(2, &[O, LShift]);
+        tap(2, &[O, LShift]);
+        tap(3, &[Space]);
+        tap(4, &[B]);
+
+        // "B-1" with digits and minus passing through, ended by enter
+        tap(0, &[]);
+        tap(4, &[B, LShift]);
+        tap(5, &[Minus]);
+        tap(6, &[Kb1]);
+        tap(4, &[B, LShift]);
+        tap(7, &[Enter]);
+        tap(4, &[B]);
+
+        // toggled off by the caps word key
+        tap(0, &[]);
+        tap(0, &[]);
+        tap(4, &[B]);
+    }
+
+    #[test]
+    fn layer_lock() {
+        static LAYERS: Layers<4, 1, 3> = [
+            [[l(1), LayerLock, k(A), tg(2)]],
+            [[Trans, Trans, k(B), Trans]],
+            [[Trans, Trans, k(C), Trans]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        let mut step = |e: Option<Event>, keys: &[KeyCode], layer: usize| {
+            step(&mut layout, e, keys);
+            assert_eq!(layer, layout.current_layer());
+        };
+
+        // no momentary layer held: no-op
+        step(Some(Press(0, 1)), &[], 0);
+        step(Some(Release(0, 1)), &[], 0);
+        step(Some(Press(0, 2)), &[A], 0);
+        step(Some(Release(0, 2)), &[], 0);
+
+        // hold layer 1, lock it, release the layer key
+        step(Some(Press(0, 0)), &[], 1);
+        step(Some(Press(0, 1)), &[], 1);
+        step(Some(Release(0, 1)), &[], 1);
+        step(Some(Release(0, 0)), &[], 1);
+        step(Some(Press(0, 2)), &[B], 1);
+        step(Some(Release(0, 2)), &[], 1);
+
+        // pressed again, unlocked
+        step(Some(Press(0, 1)), &[], 0);
+        step(Some(Release(0, 1)), &[], 0);
+        step(Some(Press(0, 2)), &[A], 0);
+        step(Some(Release(0, 2)), &[], 0);
+
+        // a toggled layer is not unlocked
+        step(Some(Press(0, 3)), &[], 2);
+        step(Some(Release(0, 3)), &[], 2);
+        step(Some(Press(0, 1)), &[], 2);
+        step(Some(Release(0, 1)), &[], 2);
+
+        // only the locked layer is unlocked
+        step(Some(Press(0, 0)), &[], 1);
+        step(Some(Press(0, 1)), &[], 1);
+        step(Some(Release(0, 1)), &[], 1);
+        step(Some(Release(0, 0)), &[], 1);
+        step(Some(Press(0, 3)), &[], 1);
+        step(Some(Release(0, 3)), &[], 1);
+        step(Some(Press(0, 3)), &[], 2);
+        step(Some(Release(0, 3)), &[], 2);
+        step(Some(Press(0, 1)), &[], 2);
+        step(Some(Release(0, 1)), &[], 2);
+        step(Some(Press(0, 3)), &[], 0);
+        step(Some(Release(0, 3)), &[], 0);
+    }
+
+    #[test]
+    fn toggle_layer() {
+        static LAYERS: Layers<3, 1, 2> = [[[l(1), tg(1), k(A)]], [[Trans, Trans, k(B)]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut step = |e: Option<Event>, keys: &[KeyCode], layer: usize| {
+            step(&mut layout, e, keys);
+            assert_eq!(layer, layout.current_layer());
+        };
+
+        // momentary layer, popped on release
+        step(Some(Press(0, 0)), &[], 1);
+        step(Some(Press(0, 2)), &[B], 1);
+        step(Some(Release(0, 2)), &[], 1);
+        step(Some(Release(0, 0)), &[], 0);
+        step(Some(Press(0, 2)), &[A], 0);
+        step(Some(Release(0, 2)), &[], 0);
+
+        // toggled layer, kept after release
+        step(Some(Press(0, 1)), &[], 1);
+        step(Some(Release(0, 1)), &[], 1);
+        step(Some(Press(0, 2)), &[B], 1);
+        step(Some(Release(0, 2)), &[], 1);
+        step(Some(Press(0, 1)), &[], 0);
+        step(Some(Release(0, 1)), &[], 0);
+        step(Some(Press(0, 2)), &[A], 0);
+        step(Some(Release(0, 2)), &[], 0);
+    }
+
+    #[test]
+    fn toggle_tap() {
+        static LAYERS: Layers<2, 1, 2> = [
+            [[
+                ToggleTap {
+                    layer: 1,
+                    taps: 2,
+                    timeout: 200,
+                },
+                k(A),
+            ]],
+            [[Trans, k(B)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        let tap_other = |layout: &mut Layout<2, 1, 2>, kc| {
+            step(layout, Some(Press(0, 1)), &[kc]);
+            step(layout, Some(Release(0, 1)), &[]);
+        };
+
+        // momentary layer on hold
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        tap_other(&mut layout, B);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        tap_other(&mut layout, A);
+
+        // toggled on double tap
+        for _ in 0..2 {
+            step(&mut layout, Some(Press(0, 0)), &[]);
+            step(&mut layout, Some(Release(0, 0)), &[]);
+        }
+        tap_other(&mut layout, B);
+
+        // toggled back on double tap
+        for _ in 0..2 {
+            step(&mut layout, Some(Press(0, 0)), &[]);
+            step(&mut layout, Some(Release(0, 0)), &[]);
+        }
+        tap_other(&mut layout, A);
+
+        // a hold past the tapping term is not a tap
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..250 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        tap_other(&mut layout, A);
+
+        // too slow taps don't toggle
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        for _ in 0..250 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        tap_other(&mut layout, A);
+    }
+
+    #[test]
+    fn coverage() {
+        static LAYERS: Layers<3, 2, 2> = [
+            [[k(A), NoOp, Trans], [l(1), NoOp, k(B)]],
+            [[Trans, NoOp, k(C)], [Trans, Trans, NoOp]],
+        ];
+        let report = Layout::new(&LAYERS).coverage();
+        assert!(!report.is_complete());
+        assert!(report.is_mapped((0, 0)));
+        assert!(report.is_mapped((0, 2)));
+        assert!(!report.is_mapped((0, 1)));
+        assert!(!report.is_mapped((5, 0)));
+        let unmapped: Vec<_, 6> = report.unmapped().collect();
+        assert_eq!(&[(0, 1), (1, 1)], unmapped.as_slice());
+    }
+
+    #[test]
+    fn on_page() {
+        use crate::key_code::{KeyReports, UsagePage};
+        static LAYERS: Layers<3, 1, 1> = [[[
+            OnPage {
+                key: A,
+                page: UsagePage::Consumer,
+            },
+            OnPage {
+                key: VolUp,
+                page: UsagePage::Keyboard,
+            },
+            k(B),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        // A has no consumer usage: it is dropped
+        let reports: KeyReports = layout.keycodes_with_page().collect();
+        assert_eq!(0, reports.consumer());
+        assert_eq!(
+            &[0, 0, B as u8, 0, 0, 0, 0, 0],
+            reports.keyboard().as_bytes()
+        );
+
+        layout.event(Release(0, 0));
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        let reports: KeyReports = layout.keycodes_with_page().collect();
+        assert_eq!(0, reports.consumer());
+        assert_eq!(
+            &[0, 0, B as u8, VolUp as u8, 0, 0, 0, 0],
+            reports.keyboard().as_bytes()
+        );
+    }
+
+    #[test]
+    fn peek_keycode() {
+        static LAYERS: Layers<2, 1, 2> = [[[k(A), l(1)]], [[k(B), Trans]]];
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(Some(A), layout.peek_keycode(Press(0, 0)));
+        assert_eq!(None, layout.peek_keycode(Press(0, 1)));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(Some(B), layout.peek_keycode(Press(0, 0)));
+        assert_eq!(Some(B), layout.peek_keycode(Release(0, 0)));
+        assert_eq!(None, layout.peek_keycode(Press(0, 1)));
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn dirty() {
+        static LAYERS: Layers<2, 1, 1> = [[[k(A), NoOp]]];
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(!layout.take_dirty());
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(layout.take_dirty());
+        assert!(!layout.take_dirty());
+
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(!layout.take_dirty());
+
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(layout.take_dirty());
+    }
+
+    #[test]
+    fn dirty_on_event() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LCtrl), k(A)).with_timeout(200)),
+            NoOp,
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(!layout.take_dirty());
+
+        // the hold is resolved by `event` on overflow of the stack
+        for _ in 0..8 {
+            layout.event(Press(0, 1));
+            layout.event(Release(0, 1));
+        }
+        layout.event(Press(0, 1));
+        assert_keys(&[LCtrl], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(layout.take_dirty());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(!layout.take_dirty());
+    }
+
+    #[test]
+    fn activity() {
+        static LAYERS: Layers<1, 1, 1> = [[[k(A)]]];
+        let mut layout = Layout::new(&LAYERS);
+        assert!(!layout.activity_since_reset());
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(layout.activity_since_reset());
+        assert!(!layout.activity_since_reset());
+
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert!(!layout.activity_since_reset());
+    }
+
+    #[test]
+    fn rgb_events() {
+        use crate::action::RgbEvent::*;
+        static ALL: [crate::action::RgbEvent; 6] = [
+            Toggle,
+            HueUp,
+            HueDown,
+            BrightnessUp,
+            BrightnessDown,
+            ModeNext,
+        ];
+        static LAYERS: Layers<6, 1, 1> = [[[
+            Rgb(Toggle),
+            Rgb(HueUp),
+            Rgb(HueDown),
+            Rgb(BrightnessUp),
+            Rgb(BrightnessDown),
+            Rgb(ModeNext),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        for (j, &event) in ALL.iter().enumerate() {
+            layout.event(Press(0, j as u8));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(Some(event), layout.take_rgb_event());
+            assert_eq!(None, layout.take_rgb_event());
+            layout.event(Release(0, j as u8));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(None, layout.take_rgb_event());
+            assert_keys(&[], layout.keycodes());
+        }
+    }
+
+    #[test]
+    fn disabled() {
+        use crate::chording::{ChordDef, Chording};
+        const UNLOCK: u8 = 1;
+        static CHORDS: [ChordDef; 1] = [((0, 2), &[(0, 0), (0, 1)])];
+        static LAYERS: Layers<3, 1, 1, u8> = [[[k(A), k(B), Action::Custom(UNLOCK)]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut chording = Chording::new(&CHORDS);
+        let mut events = |layout: &mut Layout<3, 1, 1, u8>, events: &[Event]| {
+            for &e in chording.tick(events.iter().copied().collect()).iter() {
+                layout.event(e);
+            }
+        };
+
+        layout.set_enabled(false);
+        assert!(!layout.is_enabled());
+        events(&mut layout, &[Press(0, 0)]);
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        events(&mut layout, &[Release(0, 0)]);
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        // unlock combo
+        events(&mut layout, &[Press(0, 0), Press(0, 1)]);
+        match layout.tick() {
+            CustomEvent::Press(&UNLOCK) => layout.set_enabled(true),
+            e => panic!("unexpected {:?}", e),
+        }
+        events(&mut layout, &[Release(0, 0), Release(0, 1)]);
+        assert_eq!(CustomEvent::Release(&UNLOCK), layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        events(&mut layout, &[Press(0, 0)]);
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[A], layout.keycodes());
+    }
+
+    #[test]
+    fn hold_tap_log() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction {
+                timeout: 200,
+                hold: k(LCtrl),
+                tap: k(Space),
+                config: HoldTapConfig::Default,
+                tap_hold_interval: 0,
+            }),
+            k(A),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut log = HoldTapLog::<4>::new();
+        let mut tick = |layout: &mut Layout<2, 1, 1>| {
+            layout.tick();
+            if let Some(d) = layout.take_hold_tap_decision() {
+                log.push(d);
+            }
+        };
+
+        // tap
+        layout.event(Press(0, 0));
+        for _ in 0..30 {
+            tick(&mut layout);
+        }
+        layout.event(Release(0, 0));
+        for _ in 0..10 {
+            tick(&mut layout);
+        }
+
+        // hold, interrupted by another key
+        layout.event(Press(0, 0));
+        for _ in 0..50 {
+            tick(&mut layout);
+        }
+        layout.event(Press(0, 1));
+        for _ in 0..200 {
+            tick(&mut layout);
+        }
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 0));
+        for _ in 0..10 {
+            tick(&mut layout);
+        }
+
+        // hold, the events overflowing the stack
+        layout.event(Press(0, 0));
+        for _ in 0..5 {
+            tick(&mut layout);
+        }
+        for _ in 0..8 {
+            layout.event(Press(0, 1));
+            layout.event(Release(0, 1));
+        }
+        layout.event(Press(0, 1));
+        tick(&mut layout);
+
+        let mut decisions = log.iter();
+        assert_eq!(
+            Some(&HoldTapDecision {
+                coord: (0, 0),
+                action: WaitingAction::Tap,
+                elapsed: 30,
+                interrupted_by: Some(Release(0, 0)),
+            }),
+            decisions.next()
+        );
+        assert_eq!(
+            Some(&HoldTapDecision {
+                coord: (0, 0),
+                action: WaitingAction::Hold,
+                elapsed: 200,
+                interrupted_by: Some(Press(0, 1)),
+            }),
+            decisions.next()
+        );
+        assert_eq!(
+            Some(&HoldTapDecision {
+                coord: (0, 0),
+                action: WaitingAction::Hold,
+                elapsed: 4,
+                interrupted_by: Some(Press(0, 1)),
+            }),
+            decisions.next()
+        );
+        assert_eq!(None, decisions.next());
+    }
+
+    #[test]
+    fn pending_progress() {
+        static LAYERS: Layers<1, 1, 1> = [[[HoldTap(&HoldTapAction {
+            timeout: 10,
+            hold: k(LAlt),
+            tap: k(Space),
+            config: HoldTapConfig::Default,
+            tap_hold_interval: 0,
+        })]]];
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(None, layout.pending_progress());
+        layout.event(Press(0, 0));
+        assert_eq!(None, layout.pending_progress());
+        for elapsed in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(Some((elapsed, 10)), layout.pending_progress());
+            assert_keys(&[], layout.keycodes());
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(None, layout.pending_progress());
+        assert_keys(&[LAlt], layout.keycodes());
+    }
+
+    #[test]
+    fn hold_repeat() {
+        static LAYERS: Layers<1, 1, 1> = [[[HoldRepeat {
+            key: BSpace,
+            delay: 10,
+            rate: 4,
+        }]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // quick tap: a single press
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[BSpace], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        for _ in 0..20 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+
+        // long hold: repeat after the delay
+        layout.event(Press(0, 0));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[BSpace], layout.keycodes());
+        }
+        for _ in 0..3 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+            for _ in 0..3 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                assert_keys(&[BSpace], layout.keycodes());
+            }
+        }
+        layout.event(Release(0, 0));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+
+        // released between two pulses: no trailing press
+        layout.event(Press(0, 0));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[BSpace], layout.keycodes());
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Release(0, 0));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+    }
+
+    #[test]
+    fn sequence_interrupted() {
+        use crate::action::SequenceEvent::*;
+        static LAYERS: Layers<2, 1, 1> = [[[
+            Sequence(
+                &[
+                    Press(LShift),
+                    Press(H),
+                    Release(H),
+                    Release(LShift),
+                    Press(E),
+                    Release(E),
+                    Press(L),
+                    Release(L),
+                    Press(L),
+                    Release(L),
+                    Press(O),
+                    Release(O),
+                ]
+                .as_slice(),
+            ),
+            k(Space),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut reports = std::vec::Vec::new();
+
+        layout.event(Event::Press(0, 0));
+        layout.event(Event::Release(0, 0));
+        for _ in 0..14 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            let report: crate::key_code::KbHidReport = layout.keycodes().collect();
+            if reports.last() != Some(&report) {
+                reports.push(report);
+            }
+        }
+        let expected: std::vec::Vec<crate::key_code::KbHidReport> = [
+            &[][..],
+            &[LShift],
+            &[LShift, H],
+            &[LShift],
+            &[],
+            &[E],
+            &[],
+            &[L],
+            &[],
+            &[L],
+            &[],
+            &[O],
+            &[],
+        ]
+        .iter()
+        .map(|keys| keys.iter().copied().collect())
+        .collect();
+        assert_eq!(expected, reports);
+
+        // pressing another key interrupts the sequence
+        layout.event(Event::Press(0, 0));
+        layout.event(Event::Release(0, 0));
+        for _ in 0..3 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_keys(&[LShift, H], layout.keycodes());
+        layout.event(Event::Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Space], layout.keycodes());
+        assert!(layout.sequences.is_empty());
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[Space], layout.keycodes());
+        }
+    }
+
+    #[test]
+    fn sequence_slots() {
+        use crate::action::SequenceEvent::*;
+        // key codes that can't be compared
+        #[derive(Debug, Clone, Copy)]
+        enum Key {
+            A,
+            B,
+        }
+        const SEQ_A: Action<core::convert::Infallible, Key> =
+            Sequence(&[Press(Key::A), Release(Key::A)].as_slice());
+        const SEQ_B: Action<core::convert::Infallible, Key> =
+            Sequence(&[Press(Key::B), Delay { duration: 2 }, Release(Key::B)].as_slice());
+        static LAYERS: Layers<1, 1, 1, core::convert::Infallible, Key> =
+            [[[MultipleActions(&[SEQ_A, SEQ_B].as_slice())]]];
+        let mut layout = Layout::new(&LAYERS);
+        layout.event(Event::Press(0, 0));
+        layout.event(Event::Release(0, 0));
+        let mut step = |keys: &[u8]| {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert!(layout.keycodes().map(|k| k as u8).eq(keys.iter().copied()));
+        };
+
+        // each sequence releases its own key codes
+        step(&[]);
+        step(&[Key::A as u8, Key::B as u8]);
+        step(&[Key::B as u8]);
+        step(&[Key::B as u8]);
+        step(&[]);
+    }
+
+    #[test]
+    fn sequence_delay() {
+        use crate::action::SequenceEvent;
+        static LAYERS: Layers<1, 1, 1> = [[[Sequence(
+            &[
+                SequenceEvent::Press(LAlt),
+                SequenceEvent::Press(Tab),
+                SequenceEvent::Release(Tab),
+                SequenceEvent::Release(LAlt),
+                SequenceEvent::Delay { duration: 50 },
+                SequenceEvent::Press(A),
+                SequenceEvent::Release(A),
+            ]
+            .as_slice(),
+        )]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt, Tab], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LAlt], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        // the delay starts on the next tick
+        for _ in 0..50 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[A], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert!(layout.sequences.is_empty());
+    }
+
+    #[test]
+    fn sequence_overflow() {
+        use crate::action::SequenceEvent;
+        const SEQ: Action =
+            Sequence(&[SequenceEvent::Press(A), SequenceEvent::Release(A)].as_slice());
+        static LAYERS: Layers<2, 1, 1> = [[[
+            MultipleActions(&[SEQ, SEQ, SEQ, SEQ].as_slice()),
+            MultipleActions(&[SEQ, SEQ, SEQ, SEQ, SEQ].as_slice()),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(4, layout.sequences.len());
+        assert!(!layout.sequence_overflowed());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(4, layout.sequences.len());
+        assert!(layout.sequence_overflowed());
+        assert!(!layout.sequence_overflowed());
+    }
+
+    #[test]
+    fn debug_dump() {
+        use crate::action::DebugDumpAction;
+        const DUMP: Action = DebugDump(&DebugDumpAction::KEY_CODES);
+        static LAYERS: Layers<4, 1, 3> = [
+            [[l(2), k(LShift), k(A), NoOp]],
+            [[Trans, Trans, Trans, Trans]],
+            [[
+                Trans,
+                Trans,
+                DUMP,
+                MultipleActions(&[DUMP, DUMP, DUMP, DUMP, DUMP, DUMP].as_slice()),
+            ]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Press(0, 1));
+        layout.event(Press(0, 2));
+        for _ in 0..3 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!(2, layout.current_layer());
+        assert_keys(&[LShift], layout.keycodes());
+        layout.event(Release(0, 2));
+
+        // types "L2 S", shift being held
+        let typed = [
+            None,
+            Some(L),
+            None,
+            None,
+            Some(Kb2),
+            None,
+            Some(Space),
+            None,
+            None,
+            Some(S),
+            None,
+            None,
+        ];
+        for key in typed {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            let expected: std::vec::Vec<_> = Some(LShift).into_iter().chain(key).collect();
+            assert_keys(&expected, layout.keycodes());
+        }
+        assert!(layout.typing.is_empty());
+
+        // not enough room: the statuses that don't fit are not typed,
+        // rather than partially typed
+        layout.event(Press(0, 3));
+        layout.event(Release(0, 3));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(5 * 12, layout.typing.len());
+    }
+
+    #[test]
+    fn debug_dump_locale() {
+        use crate::action::DebugDumpAction;
+        use crate::key_code::KeymapLocale;
+        // the digits are shifted on a french layout
+        const FR: KeymapLocale = KeymapLocale {
+            overrides: &[(b'1', Kb1, 0x02)],
+        };
+        fn fr_key(c: u8) -> Option<(KeyCode, u8)> {
+            FR.key(c)
+        }
+        static DEBUG_DUMP: DebugDumpAction<KeyCode> = DebugDumpAction {
+            from_ascii: fr_key,
+            ..DebugDumpAction::KEY_CODES
+        };
+        static LAYERS: Layers<2, 1, 2> = [[[l(1), NoOp]], [[Trans, DebugDump(&DEBUG_DUMP)]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Press(0, 1));
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 0));
+        for _ in 0..2 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+
+        // types "L1"
+        let typed: [&[KeyCode]; 8] = [
+            &[LShift],
+            &[LShift, L],
+            &[LShift],
+            &[],
+            &[LShift],
+            &[LShift, Kb1],
+            &[LShift],
+            &[],
+        ];
+        for keys in typed {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.keycodes());
+        }
+        assert!(layout.typing.is_empty());
+    }
+
+    #[test]
+    fn sticky_cancel() {
+        static LAYERS: Layers<4, 1, 1> = [[[
+            Sticky(&crate::action::StickyAction {
+                keys: &[LCtrl],
+                count: 0,
+                cancel: &[(0, 3)],
+            }),
+            k(A),
+            k(B),
+            k(C),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // latch
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LCtrl], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LCtrl], layout.keycodes());
+
+        // persists across several keys
+        for &(j, kc) in &[(1, A), (2, B), (1, A)] {
+            layout.event(Press(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[LCtrl, kc], layout.keycodes());
+            layout.event(Release(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[LCtrl], layout.keycodes());
+        }
+
+        // cleared by a cancel set key
+        layout.event(Press(0, 3));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[C], layout.keycodes());
+        layout.event(Release(0, 3));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[A], layout.keycodes());
+    }
+
+    #[test]
+    fn sticky_count() {
+        static LAYERS: Layers<3, 1, 1> = [[[
+            Sticky(&crate::action::StickyAction {
+                keys: &[LShift],
+                count: 1,
+                cancel: &[],
+            }),
+            k(A),
+            k(B),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Release(0, 0));
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift, A], layout.keycodes());
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift, A, B], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+    }
+
+    #[test]
+    fn one_shot() {
+        static LAYERS: Layers<3, 1, 1> = [[[
+            OneShot(&crate::action::OneShotAction {
+                keys: &[LShift],
+                timeout: 100,
+            }),
+            k(A),
+            k(B),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // tap: the next key is shifted
+        step(&mut layout, Some(Press(0, 0)), &[LShift]);
+        step(&mut layout, Some(Release(0, 0)), &[LShift]);
+        step(&mut layout, Some(Press(0, 1)), &[LShift, A]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        step(&mut layout, Some(Press(0, 2)), &[B]);
+        step(&mut layout, Some(Release(0, 2)), &[]);
+
+        // hold: a normal key
+        step(&mut layout, Some(Press(0, 0)), &[LShift]);
+        step(&mut layout, Some(Press(0, 1)), &[LShift, A]);
+        step(&mut layout, Some(Release(0, 1)), &[LShift]);
+        step(&mut layout, Some(Press(0, 2)), &[LShift, B]);
+        step(&mut layout, Some(Release(0, 0)), &[B]);
+        step(&mut layout, Some(Release(0, 2)), &[]);
+
+        // unused tap: expires
+        step(&mut layout, Some(Press(0, 0)), &[LShift]);
+        step(&mut layout, Some(Release(0, 0)), &[LShift]);
+        for _ in 0..99 {
+            step(&mut layout, None, &[LShift]);
+        }
+        step(&mut layout, None, &[]);
+        step(&mut layout, Some(Press(0, 1)), &[A]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+
+        // double tap: locked until pressed again
+        step(&mut layout, Some(Press(0, 0)), &[LShift]);
+        step(&mut layout, Some(Release(0, 0)), &[LShift]);
+        step(&mut layout, Some(Press(0, 0)), &[LShift]);
+        step(&mut layout, Some(Release(0, 0)), &[LShift]);
+        for _ in 0..200 {
+            step(&mut layout, None, &[LShift]);
+        }
+        step(&mut layout, Some(Press(0, 1)), &[LShift, A]);
+        step(&mut layout, Some(Release(0, 1)), &[LShift]);
+        step(&mut layout, Some(Press(0, 2)), &[LShift, B]);
+        step(&mut layout, Some(Release(0, 2)), &[LShift]);
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[A]);
+    }
+
+    #[test]
+    fn sticky_through_layer() {
+        static LAYERS: Layers<3, 1, 2> = [
+            [[
+                Sticky(&crate::action::StickyAction {
+                    keys: &[LShift],
+                    count: 1,
+                    cancel: &[],
+                }),
+                l(1),
+                k(A),
+            ]],
+            [[Trans, Trans, k(B)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift], layout.keycodes());
+
+        // the layer key doesn't consume the latch
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.current_layer());
+        assert_keys(&[LShift], layout.keycodes());
+
+        // the key of the new layer does
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LShift, B], layout.keycodes());
+        layout.event(Release(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+    }
+
+    #[test]
+    fn layer_tap_lock() {
+        static LAYERS: Layers<2, 1, 2> = [
+            [[
+                LayerTap(&LayerTapAction {
+                    timeout: 200,
+                    layer: 1,
+                    tap: k(Space),
+                    lock_interval: 200,
+                }),
+                k(A),
+            ]],
+            [[Trans, k(B)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // tap: key code
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Space], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        for _ in 0..200 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+
+        // hold: momentary layer
+        layout.event(Press(0, 0));
+        for _ in 0..200 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(0, layout.current_layer());
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        assert_keys(&[], layout.keycodes());
+
+        // double tap and hold: lock
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Space], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Press(0, 0));
+        for _ in 0..201 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        assert_eq!(1, layout.current_layer());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // tap again: unlock
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[A], layout.keycodes());
+    }
+
+    #[test]
+    fn layer_tap_lock_no_tap() {
+        static LAYERS: Layers<2, 1, 2> = [
+            [[
+                LayerTap(&LayerTapAction {
+                    timeout: 100,
+                    layer: 1,
+                    tap: NoOp,
+                    lock_interval: 150,
+                }),
+                k(A),
+            ]],
+            [[Trans, k(B)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // hold: momentary
+        layout.event(Press(0, 0));
+        for _ in 0..101 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!(1, layout.current_layer());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        for _ in 0..150 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+
+        // tap: nothing
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Release(0, 0));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+            assert_eq!(0, layout.current_layer());
+        }
+
+        // then hold: lock
+        layout.event(Press(0, 0));
+        for _ in 0..101 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // tap: unlock
+        layout.event(Press(0, 0));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn overlay_layer() {
+        static LAYERS: Layers<4, 1, 4> = [
+            [[l(3), k(Q), k(W), k(E)]],
+            [[l(3), k(Q), k(W), k(F)]],
+            [[Trans, k(Kb1), Trans, Trans]],
+            [[Trans, Trans, Trans, k(Kb9)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        layout.set_overlay_layer(Some(2));
+        assert_eq!(Some(2), layout.overlay_layer());
+
+        for (default, third) in [(0, E), (1, F)] {
+            layout.set_default_layer(default);
+            layout.event(Press(0, 1));
+            layout.event(Press(0, 3));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[Kb1, third], layout.keycodes());
+            layout.event(Release(0, 1));
+            layout.event(Release(0, 3));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+
+            // the overlay stays above the default layer while a
+            // momentary layer is active
+            layout.event(Press(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_eq!(3, layout.current_layer());
+            layout.event(Press(0, 1));
+            layout.event(Press(0, 2));
+            layout.event(Press(0, 3));
+            for _ in 0..3 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+            }
+            assert_keys(&[Kb1, W, Kb9], layout.keycodes());
+            for e in [Release(0, 0), Release(0, 1), Release(0, 2), Release(0, 3)] {
+                layout.event(e);
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+            }
+            assert_keys(&[], layout.keycodes());
+        }
+
+        layout.set_overlay_layer(None);
+        assert_eq!(Some(Q), layout.peek_keycode(Press(0, 1)));
+    }
+
+    #[test]
+    fn tap_dance() {
+        static LAYERS: Layers<2, 1, 2> = [
+            [[
+                td(&crate::action::TapDanceAction {
+                    actions: &[k(Escape), k(CapsLock), l(1)],
+                    timeout: 200,
+                }),
+                k(A),
+            ]],
+            [[Trans, k(B)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        let tap = |layout: &mut Layout<2, 1, 2>| {
+            layout.event(Press(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            layout.event(Release(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        };
+        let wait = |layout: &mut Layout<2, 1, 2>, keys: &[KeyCode]| {
+            for _ in 0..199 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                assert_keys(&[], layout.keycodes());
+            }
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.keycodes());
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        };
+
+        // one tap
+        tap(&mut layout);
+        wait(&mut layout, &[Escape]);
+
+        // two taps
+        tap(&mut layout);
+        tap(&mut layout);
+        wait(&mut layout, &[CapsLock]);
+
+        // more taps than actions: the last action
+        for _ in 0..4 {
+            tap(&mut layout);
+        }
+        wait(&mut layout, &[]);
+        assert_eq!(0, layout.current_layer());
+
+        // interrupted by another key
+        tap(&mut layout);
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Escape, A], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[A], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // the tap is released on the next tick, before the following
+        // events
+        tap(&mut layout);
+        layout.event(Press(0, 1));
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[Escape, A], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        // held after the last tap: the action is held
+        tap(&mut layout);
+        tap(&mut layout);
+        layout.event(Press(0, 0));
+        for _ in 0..201 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!(1, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+    }
+
+    #[test]
+    fn tap_dance_sequence() {
+        use crate::action::SequenceEvent;
+        static LAYERS: Layers<2, 1, 1> = [[[
+            td(&crate::action::TapDanceAction {
+                actions: &[Sequence(
+                    &[SequenceEvent::Press(H), SequenceEvent::Release(H)].as_slice(),
+                )],
+                timeout: 200,
+            }),
+            k(A),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        // the press interrupting the tap dance doesn't cancel its sequence
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[A]);
+        step(&mut layout, None, &[A, H]);
+        step(&mut layout, None, &[A]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn layer_tap_dance() {
+        static LAYERS: Layers<2, 1, 4> = [
+            [[
+                LayerTapDance(&crate::action::LayerTapDanceAction {
+                    layers: &[1, 2],
+                    timeout: 200,
+                    hold: 3,
+                }),
+                k(A),
+            ]],
+            [[Trans, k(B)]],
+            [[Trans, k(C)]],
+            [[Trans, k(D)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        let tap = |layout: &mut Layout<2, 1, 4>| {
+            layout.event(Press(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            layout.event(Release(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        };
+        let wait = |layout: &mut Layout<2, 1, 4>| {
+            for _ in 0..200 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+            }
+        };
+
+        // single tap: first layer
+        tap(&mut layout);
+        assert_eq!(0, layout.current_layer());
+        wait(&mut layout);
+        assert_eq!(1, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // double tap: second layer, replacing the first one
+        tap(&mut layout);
+        tap(&mut layout);
+        wait(&mut layout);
+        assert_eq!(2, layout.current_layer());
+
+        // tapping the locked layer unlocks it
+        tap(&mut layout);
+        tap(&mut layout);
+        wait(&mut layout);
+        assert_eq!(0, layout.current_layer());
+
+        // hold: momentary layer
+        layout.event(Press(0, 0));
+        wait(&mut layout);
+        assert_eq!(0, layout.current_layer());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(3, layout.current_layer());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[D], layout.keycodes());
+        layout.event(Release(0, 1));
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        assert_keys(&[], layout.keycodes());
+
+        // pressing another key resolves the taps
+        tap(&mut layout);
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+    }
+
+    #[test]
+    fn no_op_and_trans() {
+        static LAYERS: Layers<3, 1, 2> = [[[l(1), k(A), k(B)]], [[Trans, n(), Trans]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(1, layout.current_layer());
+
+        // `NoOp` blocks the lower layers
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+
+        // `Trans` reveals them
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[B], layout.keycodes());
+
+        // the key disabled by `NoOp` is back on the base layer
+        for e in [Release(0, 1), Release(0, 2), Release(0, 0), Press(0, 1)] {
+            layout.event(e);
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!(0, layout.current_layer());
+        assert_keys(&[A], layout.keycodes());
+    }
+
+    #[test]
+    fn layer_priority() {
+        static LAYERS: Layers<8, 1, 4> = [
+            [[
+                l(2),
+                l(3),
+                LayerTapDance(&crate::action::LayerTapDanceAction {
+                    layers: &[1],
+                    timeout: 10,
+                    hold: 1,
+                }),
+                k(A),
+                k(A),
+                k(A),
+                k(D),
+                Trans,
+            ]],
+            [[Trans, Trans, Trans, k(B), k(B), k(B), Trans, Trans]],
+            [[Trans, Trans, Trans, Trans, k(C), Trans, Trans, Trans]],
+            [[Trans, Trans, Trans, Trans, Trans, NoOp, Trans, Trans]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // locked layer 1, then momentary layers 2 and 3
+        layout.event(Press(0, 2));
+        layout.event(Release(0, 2));
+        for _ in 0..12 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        layout.event(Press(0, 0));
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(
+            &[3, 2, 1, 0],
+            layout.active_layers().collect::<Vec<_, 4>>().as_slice()
+        );
+
+        // `Trans` falls through the stack, `NoOp` stops it
+        assert_eq!(&k(B), layout.action_at((0, 3)));
+        assert_eq!(&k(C), layout.action_at((0, 4)));
+        assert_eq!(&NoOp, layout.action_at((0, 5)));
+        assert_eq!(&k(D), layout.action_at((0, 6)));
+        assert_eq!(&NoOp, layout.action_at((0, 7)));
+        layout.event(Press(0, 3));
+        layout.event(Press(0, 4));
+        layout.event(Press(0, 5));
+        layout.event(Press(0, 6));
+        layout.event(Press(0, 7));
+        for _ in 0..5 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_keys(&[B, C, D], layout.keycodes());
+    }
+
+    #[test]
+    fn one_shot_layer_priority() {
+        static LAYERS: Layers<4, 1, 4> = [
+            [[
+                OneShotLayer {
+                    layer: 1,
+                    timeout: 100,
+                },
+                l(2),
+                ToggleLayer(3),
+                k(A),
+            ]],
+            [[Trans, Trans, Trans, k(B)]],
+            [[Trans, Trans, Trans, k(C)]],
+            [[Trans, Trans, Trans, k(D)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // toggled layer 3, one shot layer 1, then momentary layer 2
+        step(&mut layout, Some(Press(0, 2)), &[]);
+        step(&mut layout, Some(Release(0, 2)), &[]);
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        assert_eq!(
+            &[1, 2, 3, 0],
+            layout.active_layers().collect::<Vec<_, 4>>().as_slice()
+        );
+        assert_eq!(1, layout.current_layer());
+
+        // the one shot layer wins, then the momentary one, then the
+        // toggled one
+        assert_eq!(&k(B), layout.action_at((0, 3)));
+        step(&mut layout, Some(Press(0, 3)), &[B]);
+        step(&mut layout, Some(Release(0, 3)), &[]);
+        assert_eq!(&k(C), layout.action_at((0, 3)));
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        assert_eq!(&k(D), layout.action_at((0, 3)));
+        step(&mut layout, Some(Press(0, 3)), &[D]);
+    }
+
+    #[test]
+    fn hold_tap_timeout_per_key() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction::new(k(LGui), k(A))),
+            HoldTap(&HoldTapAction::new(k(LCtrl), k(Space)).with_timeout(150)),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        for (coord, tap, hold) in [((0, 0), A, LGui), ((0, 1), Space, LCtrl)] {
+            layout.event(Press(coord.0, coord.1));
+            let mut held = false;
+            for _ in 0..180 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                held |= layout.keycodes().any(|kc| kc == hold);
+            }
+            layout.event(Release(coord.0, coord.1));
+            let mut tapped = false;
+            for _ in 0..3 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+                tapped |= layout.keycodes().any(|kc| kc == tap);
+            }
+            assert_eq!(coord == (0, 1), held);
+            assert_eq!(coord == (0, 0), tapped);
+        }
+    }
+
+    #[test]
+    fn combos() {
+        static LAYERS: Layers<4, 1, 1> = [[[k(J), k(K), k(L), k(B)]]];
+        static COMBOS: [Combo; 2] = [
+            Combo {
+                keys: &[(0, 0), (0, 1)],
+                action: k(Escape),
+                timeout: 50,
+            },
+            Combo {
+                keys: &[(0, 0), (0, 1), (0, 2)],
+                action: k(Enter),
+                timeout: 50,
+            },
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        layout.set_combos(&COMBOS);
+
+        // longest combo
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        step(&mut layout, Some(Press(0, 2)), &[Enter]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 2)), &[]);
+
+        // shorter combo, resolved at the timeout of the longer one
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        for _ in 0..48 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[Escape]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+
+        // standalone tap
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[J]);
+        step(&mut layout, None, &[]);
+
+        // standalone hold
+        step(&mut layout, Some(Press(0, 1)), &[]);
+        for _ in 0..49 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, None, &[K]);
+        step(&mut layout, None, &[K]);
+        step(&mut layout, Some(Press(0, 0)), &[K]);
+        for _ in 0..49 {
+            step(&mut layout, None, &[K]);
+        }
+        step(&mut layout, None, &[J, K]);
+        step(&mut layout, Some(Release(0, 1)), &[J]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // interrupted by another key
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 3)), &[J]);
+        step(&mut layout, None, &[J, B]);
+        step(&mut layout, Some(Release(0, 0)), &[B]);
+        step(&mut layout, Some(Release(0, 3)), &[]);
+
+        // a pending combo resolved on overflow of the stack keeps the
+        // newest event
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        layout.event(Press(0, 3));
+        for _ in 0..7 {
+            layout.event(Release(0, 3));
+            layout.event(Press(0, 3));
+        }
+        layout.event(Release(0, 3));
+        layout.event(Release(0, 0));
+        assert_keys(&[J, B], layout.keycodes());
+        for _ in 0..15 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_keys(&[J], layout.keycodes());
+        step(&mut layout, None, &[]);
+    }
+
+    #[test]
+    fn press_priority() {
+        static LAYERS: Layers<3, 1, 1> = [[[k(A), k(B), k(LShift)]]];
+        let mut layout = Layout::new(&LAYERS);
+        layout.set_press_priority(Some(modifiers_first));
+        let mut step = |e: &[Event], keys: &[KeyCode]| {
+            for &e in e {
+                layout.event(e);
+            }
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.keycodes());
+        };
+
+        // same scan: shift first
+        step(&[Press(0, 0), Press(0, 2)], &[LShift]);
+        step(&[], &[LShift, A]);
+        step(&[Release(0, 0), Release(0, 2)], &[LShift]);
+        step(&[], &[]);
+
+        // the order of the regular keys is kept
+        step(&[Press(0, 1), Press(0, 0), Press(0, 2)], &[LShift]);
+        step(&[], &[LShift, B]);
+        step(&[], &[LShift, B, A]);
+        step(&[Release(0, 1), Release(0, 0), Release(0, 2)], &[LShift, A]);
+        step(&[], &[LShift]);
+        step(&[], &[]);
+
+        // different scans: registration order
+        step(&[Press(0, 0)], &[A]);
+        step(&[Press(0, 2)], &[A, LShift]);
+        step(&[Release(0, 0), Release(0, 2)], &[LShift]);
+        step(&[], &[]);
+    }
+
+    #[test]
+    fn held_keycodes() {
+        static LAYERS: Layers<7, 1, 1> = [[[k(A), k(B), k(C), k(D), k(E), k(F), k(G)]]];
+        let mut layout = Layout::new(&LAYERS);
+        for j in 0..7 {
+            layout.event(Press(0, j));
+            layout.tick();
+        }
+        assert_keys(&[A, B, C, D, E, F, G], layout.held_keycodes());
+        let report: crate::key_code::KbHidReport = layout.keycodes().collect();
+        assert_eq!(&[ErrorRollOver as u8; 6], &report.as_bytes()[2..]);
+
+        layout.set_enabled(false);
+        assert_eq!(7, layout.held_keycodes().count());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn grave_escape() {
+        static LAYERS: Layers<3, 1, 1> = [[[
+            GraveEscape(&GraveEscapeAction::KEY_CODES),
+            k(RShift),
+            k(LGui),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        step(&mut layout, Some(Press(0, 0)), &[Escape]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // shift: `~`
+        step(&mut layout, Some(Press(0, 1)), &[RShift]);
+        step(&mut layout, Some(Press(0, 0)), &[RShift, Grave]);
+        step(&mut layout, Some(Release(0, 0)), &[RShift]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+
+        // chosen on press, the shift being released
+        step(&mut layout, Some(Press(0, 1)), &[RShift]);
+        step(&mut layout, Some(Press(0, 0)), &[RShift, Grave]);
+        step(&mut layout, Some(Release(0, 1)), &[Grave]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+        step(&mut layout, Some(Press(0, 0)), &[Escape]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // GUI and shift: escape
+        step(&mut layout, Some(Press(0, 2)), &[LGui]);
+        step(&mut layout, Some(Press(0, 1)), &[LGui, RShift]);
+        step(&mut layout, Some(Press(0, 0)), &[LGui, RShift, Escape]);
+        step(&mut layout, Some(Release(0, 0)), &[LGui, RShift]);
+        step(&mut layout, Some(Release(0, 1)), &[LGui]);
+        step(&mut layout, Some(Release(0, 2)), &[]);
+    }
+
+    #[test]
+    fn hold_chord() {
+        use crate::action::HoldChordAction;
+
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldChord(&HoldChordAction {
+                held: &[LCtrl, LAlt],
+                key: Delete,
+            }),
+            k(A),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let mut step = |e: Option<Event>, keys: &[KeyCode]| {
+            if let Some(e) = e {
+                layout.event(e);
+            }
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert!(layout.keycodes().eq(keys.iter().copied()));
+        };
+
+        step(Some(Press(0, 0)), &[LCtrl, LAlt]);
+        step(None, &[LCtrl, LAlt, Delete]);
+        step(None, &[LCtrl, LAlt, Delete]);
+        step(Some(Release(0, 0)), &[LCtrl, LAlt, Delete]);
+        step(None, &[LCtrl, LAlt]);
+        step(None, &[LCtrl]);
+        step(None, &[]);
+        step(Some(Press(0, 1)), &[A]);
+        step(Some(Release(0, 1)), &[]);
+
+        // pressing a key while the chord is released does not
+        // interrupt the release of its keys
+        step(Some(Press(0, 0)), &[LCtrl, LAlt]);
+        step(None, &[LCtrl, LAlt, Delete]);
+        step(Some(Release(0, 0)), &[LCtrl, LAlt, Delete]);
+        step(Some(Press(0, 1)), &[LCtrl, LAlt, A]);
+        step(None, &[LCtrl, A]);
+        step(None, &[A]);
+        step(Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn key_overrides() {
+        static LAYERS: Layers<4, 1, 1> = [[[k(LShift), k(RShift), k(BSpace), k(Dot)]]];
+        static OVERRIDES: [KeyOverride; 2] = [
+            KeyOverride {
+                trigger: BSpace,
+                required_mods: 0x02,
+                suppressed_mods: 0x02,
+                replacement: Delete,
+            },
+            KeyOverride {
+                trigger: Dot,
+                required_mods: 0x02,
+                suppressed_mods: 0,
+                replacement: Comma,
+            },
+        ];
+        let mut layout = Layout::new(&LAYERS);
+        layout.set_key_overrides(&OVERRIDES);
+        let mut step = |e: Option<Event>, keys: &[KeyCode]| {
+            if let Some(e) = e {
+                layout.event(e);
+            }
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(keys, layout.overridden_keycodes());
+        };
+
+        // no shift, no override
+        step(Some(Press(0, 2)), &[BSpace]);
+        step(Some(Release(0, 2)), &[]);
+
+        // shift cleared while overridden, restored on release
+        step(Some(Press(0, 0)), &[LShift]);
+        step(Some(Press(0, 2)), &[Delete]);
+        step(Some(Release(0, 2)), &[LShift]);
+        step(Some(Release(0, 0)), &[]);
+
+        // the right shift also matches
+        step(Some(Press(0, 1)), &[RShift]);
+        step(Some(Press(0, 2)), &[Delete]);
+        step(Some(Press(0, 3)), &[Delete, Comma]);
+        step(Some(Release(0, 2)), &[RShift, Comma]);
+        step(Some(Release(0, 3)), &[RShift]);
+        step(Some(Release(0, 1)), &[]);
+    }
+
+    #[test]
+    fn gated_layer() {
+        static LAYERS: Layers<3, 1, 2> = [[[k(LCtrl), k(Space), k(A)]], [[Trans, Trans, k(B)]]];
+        static GATED: [GatedLayer; 1] = [GatedLayer {
+            first: (0, 0),
+            second: (0, 1),
+            layer: 1,
+        }];
+        let mut layout = Layout::new(&LAYERS);
+        layout.set_gated_layers(&GATED);
+
+        // the keys alone do their usual action
         layout.event(Press(0, 1));
-        for _ in 0..15 {
-            assert_eq!(CustomEvent::NoEvent, layout.tick());
-            assert_keys(&[], layout.keycodes());
-        }
-        layout.event(Release(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
         assert_keys(&[Space], layout.keycodes());
-        for _ in 0..10 {
-            assert_eq!(CustomEvent::NoEvent, layout.tick());
-            assert_keys(&[Space], layout.keycodes());
-        }
         layout.event(Release(0, 1));
+        layout.event(Press(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[Space, LCtrl], layout.keycodes());
         assert_eq!(CustomEvent::NoEvent, layout.tick());
         assert_keys(&[LCtrl], layout.keycodes());
+
+        // the first then the second key engages the layer
+        layout.event(Press(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        assert_eq!(1, layout.current_layer());
+        assert_keys(&[LCtrl], layout.keycodes());
+        layout.event(Press(0, 2));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        assert_keys(&[LCtrl, B], layout.keycodes());
+        layout.event(Release(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+
+        // releasing the second key disengages it
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(0, layout.current_layer());
+        layout.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[LCtrl, A], layout.keycodes());
     }
 
     #[test]
-    fn hold_on_press() {
-        static LAYERS: Layers<2, 1, 1> = [[[
+    fn hold_on_opposite_hand_press() {
+        static LAYERS: Layers<3, 1, 1> = [[[
             HoldTap(&HoldTapAction {
                 timeout: 200,
-                hold: k(LAlt),
+                hold: k(LCtrl),
                 tap: k(Space),
-                config: HoldTapConfig::HoldOnOtherKeyPress,
+                config: HoldTapConfig::HoldOnOppositeHandPress,
                 tap_hold_interval: 0,
             }),
-            k(Enter),
+            k(A),
+            k(B),
         ]]];
+        static HANDS: Hands<3, 1> = [[Hand::Left, Hand::Left, Hand::Right]];
         let mut layout = Layout::new(&LAYERS);
+        layout.set_hands(&HANDS);
 
-        // Press another key before timeout
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        // same hand: roll, tap
         layout.event(Press(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
         layout.event(Press(0, 1));
+        for _ in 0..10 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        layout.event(Release(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt], layout.keycodes());
+        assert_keys(&[Space], layout.keycodes());
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt, Enter], layout.keycodes());
-        layout.event(Release(0, 0));
+        assert_keys(&[Space, A], layout.keycodes());
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[Enter], layout.keycodes());
+        assert_keys(&[A], layout.keycodes());
         layout.event(Release(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
         assert_keys(&[], layout.keycodes());
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
 
-        // Press another key after timeout
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        // opposite hand: immediate hold
         layout.event(Press(0, 0));
-        for _ in 0..200 {
-            assert_eq!(CustomEvent::NoEvent, layout.tick());
-            assert_keys(&[], layout.keycodes());
-        }
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt], layout.keycodes());
-        layout.event(Press(0, 1));
+        layout.event(Press(0, 2));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt, Enter], layout.keycodes());
-        layout.event(Release(0, 0));
+        assert_keys(&[LCtrl], layout.keycodes());
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[Enter], layout.keycodes());
-        layout.event(Release(0, 1));
+        assert_keys(&[LCtrl, B], layout.keycodes());
+        layout.event(Release(0, 2));
+        layout.event(Release(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
         assert_eq!(CustomEvent::NoEvent, layout.tick());
         assert_keys(&[], layout.keycodes());
     }
 
     #[test]
-    fn permissive_hold() {
-        static LAYERS: Layers<2, 1, 1> = [[[
-            HoldTap(&HoldTapAction {
-                timeout: 200,
-                hold: k(LAlt),
-                tap: k(Space),
-                config: HoldTapConfig::PermissiveHold,
-                tap_hold_interval: 0,
-            }),
-            k(Enter),
-        ]]];
-        let mut layout = Layout::new(&LAYERS);
-
-        // Press and release another key before timeout
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
-        layout.event(Press(0, 0));
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+    fn owned_layout() {
+        let mut layout = LayoutOwned::<2, 1, 2>::with_layers([[[l(1), k(A)]], [[Trans, k(B)]]]);
         layout.event(Press(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        assert_keys(&[A], layout.keycodes());
         layout.event(Release(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt], layout.keycodes());
-        assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt, Enter], layout.keycodes());
+
+        // a key code built at runtime, not existing in the keymap
+        let kc = KeyCode::from_u8(C as u8).unwrap();
+        assert!(layout.set_key(0, 0, 1, k(kc)));
+        assert!(!layout.set_key(2, 0, 1, k(kc)));
+        assert!(!layout.set_key(0, 1, 0, k(kc)));
+        assert_eq!(&k(C), layout.action_at((0, 1)));
+        layout.event(Press(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt], layout.keycodes());
+        assert_keys(&[C], layout.keycodes());
+        layout.event(Release(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[LAlt], layout.keycodes());
-        layout.event(Release(0, 0));
+
+        // other layers are unchanged
+        layout.event(Press(0, 0));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        layout.event(Press(0, 1));
         assert_eq!(CustomEvent::NoEvent, layout.tick());
-        assert_keys(&[], layout.keycodes());
+        assert_keys(&[B], layout.keycodes());
     }
 
     #[test]
@@ -772,6 +5937,199 @@ mod test {
         assert_keys(&[], layout.keycodes());
     }
 
+    #[test]
+    fn modifier_side() {
+        use crate::key_code::ModifierSide;
+        static LAYERS: Layers<1, 1, 1> = [[[m(&[RShift, A].as_slice())]]];
+        let mut layout = Layout::new(&LAYERS);
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(
+            &[LShift, A],
+            layout
+                .keycodes()
+                .map(|kc| kc.with_modifier_side(ModifierSide::Left)),
+        );
+        assert_keys(
+            &[RShift, A],
+            layout
+                .keycodes()
+                .map(|kc| kc.with_modifier_side(ModifierSide::Right)),
+        );
+    }
+
+    #[test]
+    fn virtual_keys() {
+        static LAYERS: Layers<2, 2, 1> = [[[k(A), k(B)], [k(VolDown), k(VolUp)]]];
+        const ENCODER: VirtualKeys = VirtualKeys::after_rows(1);
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(ENCODER.press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[VolUp], layout.keycodes());
+        layout.event(ENCODER.release(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert!(ENCODER.contains(ENCODER.coord(0, 0)));
+        assert!(!ENCODER.contains((0, 1)));
+        assert_eq!((255, 0), VirtualKeys::after_rows(254).coord(1, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "virtual row out of range")]
+    fn virtual_row_overflow() {
+        VirtualKeys::after_rows(254).press(2, 0);
+    }
+
+    #[test]
+    fn encoder_steps() {
+        static LAYERS: Layers<4, 2, 2> = [
+            [
+                [l(1), n(), n(), n()],
+                [k(VolUp), k(VolDown), k(Right), k(Left)],
+            ],
+            [[Trans, n(), n(), n()], [Trans, Trans, k(Down), k(Up)]],
+        ];
+        const ENCODERS: VirtualKeys = VirtualKeys::after_rows(1);
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        for _ in 0..3 {
+            let [press, release] = ENCODERS.encoder_step(1, true);
+            layout.event(press);
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[Down], layout.keycodes());
+            layout.event(release);
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+        for e in ENCODERS.encoder_step(0, false) {
+            layout.event(e);
+        }
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[VolDown], layout.keycodes());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+        assert_eq!(Press(1, 255), ENCODERS.encoder_step(127, false)[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "encoder id out of range")]
+    fn encoder_id_overflow() {
+        VirtualKeys::after_rows(1).encoder_step(128, true);
+    }
+
+    #[test]
+    fn confirm() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            Confirm(&crate::action::ConfirmAction {
+                action: m(&[LAlt, F4].as_slice()),
+                window: 100,
+            }),
+            k(A),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+        let tap = |layout: &mut Layout<2, 1, 1>| {
+            layout.event(Press(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            let keys: std::vec::Vec<_> = layout.keycodes().collect();
+            layout.event(Release(0, 0));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+            keys
+        };
+
+        // a lone press does nothing
+        assert!(tap(&mut layout).is_empty());
+        for _ in 0..100 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert!(tap(&mut layout).is_empty());
+
+        // a double press within the window does the action
+        for _ in 0..50 {
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+        }
+        assert_eq!([LAlt, F4].as_slice(), tap(&mut layout));
+        assert!(tap(&mut layout).is_empty());
+    }
+
+    #[test]
+    fn owned_layout_custom() {
+        static NESTED: &[Action<u8>] = &[Action::Custom(42)];
+        let mut layout = LayoutOwned::<2, 1, 1, u8>::with_layers([[[
+            Action::Custom(41),
+            Action::MultipleActions(&NESTED),
+        ]]]);
+        // a custom action needs a 'static payload: nested only
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::Press(&42), layout.tick());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::Release(&42), layout.tick());
+    }
+
+    #[test]
+    fn binary_keymap() {
+        static LAYERS: Layers<3, 2, 2> = [
+            [[k(A), l(1), NoOp], [k(LShift), k(MediaCalc), d(1)]],
+            [[Trans, Trans, k(B)], [k(Kb1), Trans, d(0)]],
+        ];
+        let layout = LayoutOwned::<3, 2, 2>::with_layers(LAYERS);
+        let mut buf = [0; 64];
+        let len = layout.to_binary(&mut buf).unwrap();
+        assert_eq!(8 + 12 + 8, len);
+        assert_eq!(b"KBRN\x01\x03\x02\x02", &buf[..8]);
+
+        let mut loaded = LayoutOwned::<3, 2, 2>::load_binary(&buf[..len]).unwrap();
+        assert_eq!(&LAYERS, loaded.layers());
+        for (l, layer) in LAYERS.iter().enumerate() {
+            for (r, row) in layer.iter().enumerate() {
+                for (c, action) in row.iter().enumerate() {
+                    let coord = (r as u8, c as u8);
+                    assert_eq!(Some(action), loaded.layers.action(l, coord));
+                }
+            }
+        }
+        loaded.event(Press(0, 1));
+        loaded.event(Press(0, 2));
+        assert_eq!(CustomEvent::NoEvent, loaded.tick());
+        assert_eq!(CustomEvent::NoEvent, loaded.tick());
+        assert_keys(&[B], loaded.keycodes());
+
+        let load = |data: &[u8]| LayoutOwned::<3, 2, 2>::load_binary(data).map(|_| ());
+        assert_eq!(Err(FormatError::BadMagic), load(b"KBRM"));
+        assert_eq!(
+            Err(FormatError::UnsupportedVersion(2)),
+            load(b"KBRN\x02\x03\x02\x02")
+        );
+        assert_eq!(
+            Err(FormatError::BadDimensions),
+            load(b"KBRN\x01\x02\x02\x02")
+        );
+        assert_eq!(Err(FormatError::Truncated), load(&buf[..len - 1]));
+        assert_eq!(Err(FormatError::TrailingBytes), load(&buf[..len + 1]));
+        buf[8] = 5;
+        assert_eq!(Err(FormatError::UnknownAction(5)), load(&buf[..len]));
+        buf[8..10].copy_from_slice(&[2, 0xA5]);
+        assert_eq!(Err(FormatError::InvalidKeyCode(0xA5)), load(&buf[..len]));
+        buf[8..10].copy_from_slice(&[3, 2]);
+        assert_eq!(Err(FormatError::InvalidLayer(2)), load(&buf[..len]));
+
+        static UNSUPPORTED: Action = m(&[A, B].as_slice());
+        let unsupported = LayoutOwned::<1, 1, 1>::with_layers([[[UNSUPPORTED]]]);
+        assert_eq!(
+            Err(FormatError::UnsupportedAction),
+            unsupported.to_binary(&mut buf)
+        );
+        assert_eq!(
+            Err(FormatError::BufferTooSmall),
+            layout.to_binary(&mut buf[..len - 1])
+        );
+    }
+
     #[test]
     fn custom() {
         static LAYERS: Layers<1, 1, 1, u8> = [[[Action::Custom(42)]]];
@@ -794,6 +6152,45 @@ mod test {
         assert_keys(&[], layout.keycodes());
     }
 
+    #[test]
+    fn custom_payloads() {
+        static LAYERS: Layers<2, 1, 1, u16> = [[[Action::Custom(0x100), Action::Custom(0x201)]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::Press(&0x100), layout.tick());
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::Press(&0x201), layout.tick());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::Release(&0x100), layout.tick());
+        layout.event(Release(0, 1));
+        assert_eq!(CustomEvent::Release(&0x201), layout.tick());
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_keys(&[], layout.keycodes());
+    }
+
+    #[test]
+    fn user_keycodes() {
+        const FN_LOCK: u16 = 0x8001;
+        static LAYERS: Layers<2, 1, 2, u16> = [[[k(A), l(1)]], [[Action::Custom(FN_LOCK), Trans]]];
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(&k(A), layout.action_at((0, 0)));
+
+        layout.event(Press(0, 1));
+        assert_eq!(CustomEvent::NoEvent, layout.tick());
+        assert_eq!(&Action::Custom(FN_LOCK), layout.action_at((0, 0)));
+        assert_eq!(&l(1), layout.action_at((0, 1)));
+        assert_eq!(0, layout.active_customs().count());
+
+        layout.event(Press(0, 0));
+        assert_eq!(CustomEvent::Press(&FN_LOCK), layout.tick());
+        assert_eq!(Some(&FN_LOCK), layout.active_customs().next());
+        assert_keys(&[], layout.keycodes());
+        layout.event(Release(0, 0));
+        assert_eq!(CustomEvent::Release(&FN_LOCK), layout.tick());
+        assert_eq!(0, layout.active_customs().count());
+    }
+
     #[test]
     fn multiple_layers() {
         static LAYERS: Layers<2, 1, 4> = [
@@ -1016,6 +6413,47 @@ mod test {
         assert_keys(&[], layout.keycodes());
     }
 
+    #[test]
+    fn tap_hold_interval_layer() {
+        static LAYERS: Layers<2, 1, 2> = [
+            [[
+                HoldTap(&HoldTapAction {
+                    tap_hold_interval: 200,
+                    ..HoldTapAction::new(l(1), k(A))
+                }),
+                k(B),
+            ]],
+            [[Trans, k(C)]],
+        ];
+        let mut layout = Layout::new(&LAYERS);
+
+        // tap then hold: the letter is held, not the layer
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[A]);
+        step(&mut layout, None, &[]);
+        step(&mut layout, Some(Press(0, 0)), &[A]);
+        for _ in 0..300 {
+            step(&mut layout, None, &[A]);
+        }
+        step(&mut layout, Some(Press(0, 1)), &[A, B]);
+        step(&mut layout, Some(Release(0, 1)), &[A]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+
+        // another key pressed in between: the hold activates the layer
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[A]);
+        step(&mut layout, None, &[]);
+        step(&mut layout, Some(Press(0, 1)), &[B]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        step(&mut layout, Some(Press(0, 0)), &[]);
+        for _ in 0..200 {
+            step(&mut layout, None, &[]);
+        }
+        step(&mut layout, Some(Press(0, 1)), &[C]);
+        step(&mut layout, Some(Release(0, 1)), &[]);
+        step(&mut layout, Some(Release(0, 0)), &[]);
+    }
+
     #[test]
     fn tap_hold_interval_interleave() {
         static LAYERS: Layers<3, 1, 1> = [[[
@@ -1178,6 +6616,49 @@ mod test {
         assert_keys(&[], layout.keycodes());
     }
 
+    #[test]
+    fn tap_hold_force_hold() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            HoldTap(&HoldTapAction {
+                timeout: 50,
+                hold: k(LAlt),
+                tap: k(Space),
+                config: HoldTapConfig::Default,
+                tap_hold_interval: 0,
+            }),
+            HoldTap(&HoldTapAction {
+                timeout: 50,
+                hold: k(LCtrl),
+                tap: k(Enter),
+                config: HoldTapConfig::Default,
+                tap_hold_interval: 200,
+            }),
+        ]]];
+        let mut layout = Layout::new(&LAYERS);
+
+        for (j, tap, hold) in [(0, Space, LAlt), (1, Enter, Enter)] {
+            // tap
+            layout.event(Press(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            layout.event(Release(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[tap], layout.keycodes());
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+
+            // quick press and hold: the tap is repeated only with a
+            // tap_hold_interval
+            layout.event(Press(0, j));
+            for _ in 0..51 {
+                assert_eq!(CustomEvent::NoEvent, layout.tick());
+            }
+            assert_keys(&[hold], layout.keycodes());
+            layout.event(Release(0, j));
+            assert_eq!(CustomEvent::NoEvent, layout.tick());
+            assert_keys(&[], layout.keycodes());
+        }
+    }
+
     #[test]
     fn tap_hold_interval_different_hold() {
         static LAYERS: Layers<2, 1, 1> = [[[
@@ -15,17 +15,26 @@
 #![no_std]
 #![deny(missing_docs)]
 
+#[cfg(feature = "serde-leak")]
+extern crate alloc;
+
 use usb_device::bus::UsbBusAllocator;
 use usb_device::prelude::*;
 
 pub mod action;
 pub mod chording;
+pub mod clock;
+pub mod composite;
 pub mod debounce;
 pub mod hid;
 pub mod key_code;
 pub mod keyboard;
 pub mod layout;
 pub mod matrix;
+#[cfg(any(test, feature = "replay"))]
+pub mod replay;
+#[cfg(feature = "async")]
+pub mod tasks;
 
 /// A handly shortcut for the keyberon USB class type.
 pub type Class<'a, B, L> = hid::HidClass<'a, B, keyboard::Keyboard<L>>;
@@ -47,14 +56,93 @@ where
     hid::HidClass::new(keyboard::Keyboard::new(leds), bus)
 }
 
+/// The USB identity of a keyberon device, see [`new_device_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsbConfig {
+    /// The vendor ID.
+    pub vid: u16,
+    /// The product ID.
+    pub pid: u16,
+    /// The manufacturer string.
+    pub manufacturer: &'static str,
+    /// The product string.
+    pub product: &'static str,
+    /// The serial number string.
+    pub serial: &'static str,
+}
+
+impl Default for UsbConfig {
+    /// The identity of [`new_device`], with the generic keyboard IDs
+    /// of the V-USB project.
+    fn default() -> Self {
+        Self {
+            vid: VID,
+            pid: PID,
+            manufacturer: "RIIR Task Force",
+            product: "Keyberon",
+            serial: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
 /// Constructor for a keyberon USB device.
 pub fn new_device<B>(bus: &UsbBusAllocator<B>) -> usb_device::device::UsbDevice<'_, B>
 where
     B: usb_device::bus::UsbBus,
 {
-    UsbDeviceBuilder::new(bus, UsbVidPid(VID, PID))
-        .manufacturer("RIIR Task Force")
-        .product("Keyberon")
-        .serial_number(env!("CARGO_PKG_VERSION"))
+    new_device_with(bus, UsbConfig::default())
+}
+
+/// Constructor for a keyberon USB device with the given identity,
+/// allowing several board variants to share a firmware.
+///
+/// ```
+/// use keyberon::UsbConfig;
+/// use usb_device::bus::{UsbBus, UsbBusAllocator};
+/// use usb_device::device::UsbDevice;
+///
+/// fn new_device<B: UsbBus>(bus: &UsbBusAllocator<B>) -> UsbDevice<'_, B> {
+///     let config = UsbConfig {
+///         product: "Keyberon 60%",
+///         ..UsbConfig::default()
+///     };
+///     keyberon::new_device_with(bus, config)
+/// }
+/// ```
+pub fn new_device_with<B>(
+    bus: &UsbBusAllocator<B>,
+    config: UsbConfig,
+) -> usb_device::device::UsbDevice<'_, B>
+where
+    B: usb_device::bus::UsbBus,
+{
+    UsbDeviceBuilder::new(bus, UsbVidPid(config.vid, config.pid))
+        .manufacturer(config.manufacturer)
+        .product(config.product)
+        .serial_number(config.serial)
+        .supports_remote_wakeup(true)
         .build()
 }
+
+/// Wakes up the host, typically on a key press, if the device is
+/// suspended and the host enabled the remote wakeup. Returns `true`
+/// if the host is woken up.
+///
+/// The device built by [`new_device`] advertises the remote wakeup
+/// support. As `usb-device` doesn't provide a way to signal the
+/// resume, `signal` must do it using the USB peripheral of the MCU,
+/// for example by setting the resume bit during a few milliseconds.
+pub fn wakeup_host<B>(
+    device: &usb_device::device::UsbDevice<'_, B>,
+    signal: impl FnOnce(&B),
+) -> bool
+where
+    B: usb_device::bus::UsbBus,
+{
+    if device.state() == UsbDeviceState::Suspend && device.remote_wakeup_enabled() {
+        signal(device.bus());
+        true
+    } else {
+        false
+    }
+}
@@ -2,6 +2,34 @@
 
 use embedded_hal::digital::v2::{InputPin, OutputPin};
 
+/// The state of the keys of a matrix, `true` meaning pressed.
+///
+/// `keys[1][2]` corresponds to the key on row 1, column 2.
+pub type PressedKeys<const CS: usize, const RS: usize> = [[bool; CS]; RS];
+
+/// Iterates on the coordinates `(row, column)` of the pressed keys,
+/// in row-major order.
+///
+/// As [`PressedKeys`] is an array, this is a function rather than a
+/// method. Useful to send only the pressed keys, as between the
+/// halves of a split keyboard.
+///
+/// ```
+/// use keyberon::matrix::iter_pressed;
+/// let keys = [[false, true, false], [true, false, false]];
+/// assert!(iter_pressed(&keys).eq([(0, 1), (1, 0)]));
+/// ```
+pub fn iter_pressed<const CS: usize, const RS: usize>(
+    keys: &PressedKeys<CS, RS>,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    keys.iter().enumerate().flat_map(|(i, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, &pressed)| pressed)
+            .map(move |(j, _)| (i, j))
+    })
+}
+
 /// Describes the hardware-level matrix of switches.
 ///
 /// Generic parameters are in order: The type of column pins,
@@ -18,6 +46,7 @@ where
 {
     cols: [C; CS],
     rows: [R; RS],
+    prev: PressedKeys<CS, RS>,
 }
 
 impl<C, R, const CS: usize, const RS: usize> Matrix<C, R, CS, RS>
@@ -34,7 +63,11 @@ where
         C: InputPin<Error = E>,
         R: OutputPin<Error = E>,
     {
-        let mut res = Self { cols, rows };
+        let mut res = Self {
+            cols,
+            rows,
+            prev: [[false; CS]; RS],
+        };
         res.clear()?;
         Ok(res)
     }
@@ -87,6 +120,117 @@ where
     {
         self.get_with_delay(|| ())
     }
+
+    /// Scans the matrix, returning the pressed keys and the keys
+    /// that changed since the previous call to this method.
+    ///
+    /// If no key changed, the change mask is all `false`, allowing to
+    /// skip the downstream processing. The first call compares with
+    /// all keys released.
+    pub fn scan_diff<E>(&mut self) -> Result<(PressedKeys<CS, RS>, PressedKeys<CS, RS>), E>
+    where
+        C: InputPin<Error = E>,
+        R: OutputPin<Error = E>,
+    {
+        let keys = self.get()?;
+        let mut changed = [[false; CS]; RS];
+        for ((c, k), p) in changed.iter_mut().zip(&keys).zip(&self.prev) {
+            for ((c, k), p) in c.iter_mut().zip(k).zip(p) {
+                *c = k != p;
+            }
+        }
+        self.prev = keys;
+        Ok((keys, changed))
+    }
+
+    /// Scans the matrix for a production test, nothing being
+    /// physically pressed: the returned keys are read as pressed, and
+    /// are thus stuck closed, as a shorted diode or a solder bridge.
+    ///
+    /// A test jig can assert that no key is returned. The state used
+    /// by [`scan_diff`](Self::scan_diff) is not modified.
+    pub fn self_test<E>(&mut self) -> Result<PressedKeys<CS, RS>, E>
+    where
+        C: InputPin<Error = E>,
+        R: OutputPin<Error = E>,
+    {
+        self.get()
+    }
+}
+
+/// Describes the hardware-level matrix of switches, with the diodes
+/// in the "row to column" direction.
+///
+/// Contrary to [`Matrix`], the columns are driven and the rows are
+/// read. The generic parameters are in order: The type of column
+/// pins, the type of row pins, the number of columns and rows. The
+/// pressed keys are given in the same order as [`Matrix`]:
+/// `keys[1][2]` corresponds to the key on row 1, column 2.
+pub struct Row2ColMatrix<C, R, const CS: usize, const RS: usize>
+where
+    C: OutputPin,
+    R: InputPin,
+{
+    cols: [C; CS],
+    rows: [R; RS],
+}
+
+impl<C, R, const CS: usize, const RS: usize> Row2ColMatrix<C, R, CS, RS>
+where
+    C: OutputPin,
+    R: InputPin,
+{
+    /// Creates a new Row2ColMatrix.
+    ///
+    /// Assumes rows are pull-up inputs, and columns are output pins
+    /// which are set high when not being scanned.
+    pub fn new<E>(cols: [C; CS], rows: [R; RS]) -> Result<Self, E>
+    where
+        C: OutputPin<Error = E>,
+        R: InputPin<Error = E>,
+    {
+        let mut res = Self { cols, rows };
+        for c in res.cols.iter_mut() {
+            c.set_high()?;
+        }
+        Ok(res)
+    }
+
+    /// Scans the matrix and checks which keys are pressed.
+    ///
+    /// Every column pin in order is pulled low, and then each row pin
+    /// is tested; if it's low, the key is marked as pressed. The
+    /// column is set high before scanning the next one.
+    ///
+    /// Delay function allows pause to let input pins settle
+    pub fn get_with_delay<F: FnMut(), E>(&mut self, mut delay: F) -> Result<PressedKeys<CS, RS>, E>
+    where
+        C: OutputPin<Error = E>,
+        R: InputPin<Error = E>,
+    {
+        let mut keys = [[false; CS]; RS];
+
+        for (ci, col) in self.cols.iter_mut().enumerate() {
+            col.set_low()?;
+            delay();
+            for (ri, row) in self.rows.iter().enumerate() {
+                if row.is_low()? {
+                    keys[ri][ci] = true;
+                }
+            }
+            col.set_high()?;
+        }
+        Ok(keys)
+    }
+
+    /// Scans the matrix and checks which keys are pressed.
+    pub fn get<E>(&mut self) -> Result<PressedKeys<CS, RS>, E>
+    where
+        C: OutputPin<Error = E>,
+        R: InputPin<Error = E>,
+    {
+        self.get_with_delay(|| ())
+    }
 }
 
 /// Matrix-representation of switches directly attached to the pins ("diodeless").
@@ -135,3 +279,254 @@ where
         Ok(keys)
     }
 }
+
+/// Switches directly attached to the pins, as a single row.
+///
+/// A simpler alternative to [`DirectPinMatrix`] when each switch has
+/// its own pin, as on a macropad: the pressed keys are in the row 0,
+/// the column being the index of the pin.
+pub struct DirectPins<P, const N: usize>
+where
+    P: InputPin,
+{
+    pins: [P; N],
+}
+
+impl<P, const N: usize> DirectPins<P, N>
+where
+    P: InputPin,
+{
+    /// Creates a new DirectPins.
+    ///
+    /// Assumes pins are pull-up inputs.
+    pub fn new<E>(pins: [P; N]) -> Result<Self, E>
+    where
+        P: InputPin<Error = E>,
+    {
+        Ok(Self { pins })
+    }
+
+    /// Scans the pins and checks which keys are pressed (state is "low").
+    pub fn get<E>(&mut self) -> Result<PressedKeys<N, 1>, E>
+    where
+        P: InputPin<Error = E>,
+    {
+        let mut keys = [[false; N]; 1];
+        for (key, pin) in keys[0].iter_mut().zip(self.pins.iter()) {
+            *key = pin.is_low()?;
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    struct Row<'a> {
+        i: usize,
+        scanned: &'a Cell<Option<usize>>,
+    }
+    impl OutputPin for Row<'_> {
+        type Error = Infallible;
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.scanned.set(Some(self.i));
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.scanned.set(None);
+            Ok(())
+        }
+    }
+
+    struct Col<'a> {
+        j: usize,
+        scanned: &'a Cell<Option<usize>>,
+        pressed: &'a Cell<[[bool; 2]; 2]>,
+    }
+    impl InputPin for Col<'_> {
+        type Error = Infallible;
+        fn is_high(&self) -> Result<bool, Infallible> {
+            self.is_low().map(|low| !low)
+        }
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(self
+                .scanned
+                .get()
+                .is_some_and(|i| self.pressed.get()[i][self.j]))
+        }
+    }
+
+    #[test]
+    fn scan_diff() {
+        let scanned = Cell::new(None);
+        let pressed = Cell::new([[false; 2]; 2]);
+        let cols = [0, 1].map(|j| Col {
+            j,
+            scanned: &scanned,
+            pressed: &pressed,
+        });
+        let rows = [0, 1].map(|i| Row {
+            i,
+            scanned: &scanned,
+        });
+        let mut matrix = Matrix::new(cols, rows).unwrap();
+
+        pressed.set([[false, true], [false, false]]);
+        let (keys, changed) = matrix.scan_diff().unwrap();
+        assert_eq!([[false, true], [false, false]], keys);
+        assert_eq!([[false, true], [false, false]], changed);
+
+        let (keys, changed) = matrix.scan_diff().unwrap();
+        assert_eq!([[false, true], [false, false]], keys);
+        assert_eq!([[false; 2]; 2], changed);
+
+        pressed.set([[false, false], [true, false]]);
+        let (keys, changed) = matrix.scan_diff().unwrap();
+        assert_eq!([[false, false], [true, false]], keys);
+        assert_eq!([[false, true], [true, false]], changed);
+    }
+
+    #[test]
+    fn get_with_delay() {
+        let scanned = Cell::new(None);
+        let pressed = Cell::new([[false, true], [true, false]]);
+        let cols = [0, 1].map(|j| Col {
+            j,
+            scanned: &scanned,
+            pressed: &pressed,
+        });
+        let rows = [0, 1].map(|i| Row {
+            i,
+            scanned: &scanned,
+        });
+        let mut matrix = Matrix::new(cols, rows).unwrap();
+
+        // called once per row, after driving it
+        let mut delayed = [None; 3];
+        let mut nb = 0;
+        let keys = matrix
+            .get_with_delay(|| {
+                delayed[nb] = scanned.get();
+                nb += 1;
+            })
+            .unwrap();
+        assert_eq!([[false, true], [true, false]], keys);
+        assert_eq!([Some(0), Some(1), None], delayed);
+        assert_eq!(2, nb);
+    }
+
+    #[test]
+    fn pressed_coordinates() {
+        extern crate std;
+        let keys = [
+            [false, false, true],
+            [false, false, false],
+            [true, false, true],
+        ];
+        let pressed: std::vec::Vec<_> = iter_pressed(&keys).collect();
+        assert_eq!(&[(0, 2), (2, 0), (2, 2)], &pressed[..]);
+        assert_eq!(0, iter_pressed(&[[false; 3]; 2]).count());
+    }
+
+    #[test]
+    fn self_test() {
+        let scanned = Cell::new(None);
+        let pressed = Cell::new([[false, false], [true, false]]);
+        let cols = [0, 1].map(|j| Col {
+            j,
+            scanned: &scanned,
+            pressed: &pressed,
+        });
+        let rows = [0, 1].map(|i| Row {
+            i,
+            scanned: &scanned,
+        });
+        let mut matrix = Matrix::new(cols, rows).unwrap();
+
+        let stuck = matrix.self_test().unwrap();
+        assert_eq!([[false, false], [true, false]], stuck);
+    }
+
+    struct Button<'a>(&'a Cell<bool>);
+    impl InputPin for Button<'_> {
+        type Error = Infallible;
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.get())
+        }
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(self.0.get())
+        }
+    }
+
+    #[test]
+    fn direct_pins() {
+        let pressed = [(); 6].map(|_| Cell::new(false));
+        let pins = [0, 1, 2, 3, 4, 5].map(|i| Button(&pressed[i]));
+        let mut buttons = DirectPins::new(pins).unwrap();
+        assert_eq!([[false; 6]], buttons.get().unwrap());
+
+        pressed[1].set(true);
+        pressed[4].set(true);
+        assert_eq!(
+            [[false, true, false, false, true, false]],
+            buttons.get().unwrap()
+        );
+    }
+
+    #[test]
+    fn row2col() {
+        // the rows are read, the columns are driven: the mock pins
+        // are used the other way around
+        let scanned = Cell::new(None);
+        let pressed = Cell::new([[false; 2]; 2]);
+        let rows = [0, 1].map(|j| Col {
+            j,
+            scanned: &scanned,
+            pressed: &pressed,
+        });
+        let cols = [0, 1].map(|i| Row {
+            i,
+            scanned: &scanned,
+        });
+        let mut matrix = Row2ColMatrix::new(cols, rows).unwrap();
+
+        // `pressed` is indexed by [col][row]
+        pressed.set([[false, true], [false, false]]);
+        assert_eq!([[false, false], [true, false]], matrix.get().unwrap());
+        assert_eq!(None, scanned.get());
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum PinError {
+        Disconnected,
+    }
+    struct BrokenCol;
+    impl InputPin for BrokenCol {
+        type Error = PinError;
+        fn is_high(&self) -> Result<bool, PinError> {
+            Err(PinError::Disconnected)
+        }
+        fn is_low(&self) -> Result<bool, PinError> {
+            Err(PinError::Disconnected)
+        }
+    }
+    struct GoodRow;
+    impl OutputPin for GoodRow {
+        type Error = PinError;
+        fn set_low(&mut self) -> Result<(), PinError> {
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), PinError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pin_error() {
+        let mut matrix = Matrix::new([BrokenCol, BrokenCol], [GoodRow]).unwrap();
+        assert_eq!(Err(PinError::Disconnected), matrix.get());
+    }
+}
@@ -0,0 +1,212 @@
+//! Replay of recorded key events against a layout.
+//!
+//! This module allows to validate a layout against golden files,
+//! for example generated by another firmware as QMK: a sequence of
+//! timestamped key events is replayed on a [`Layout`], and the
+//! resulting keyboard reports are compared to the expected ones.
+//!
+//! This module is only available with the `replay` feature, as it
+//! is intended to be used in tests.
+//!
+//! # Fixture format
+//!
+//! A fixture is a text with one step per line. Empty lines and
+//! lines starting with `#` are ignored. The first field of each step
+//! is the time in milliseconds (i.e. in ticks) since the start of
+//! the replay. The steps must be sorted by time.
+//!
+//! - `<time> down <row> <col>`: the key at the given coordinates is
+//!   pressed.
+//! - `<time> up <row> <col>`: the key at the given coordinates is
+//!   released.
+//! - `<time> report <b0> ... <b7>`: the keyboard report, given as 8
+//!   hexadecimal bytes, is expected.
+//!
+//! The events at time `t` are registered after `t` ticks, and the
+//! report expected at time `t` is compared to the report generated
+//! after `t` ticks.
+//!
+//! # Example
+//!
+//! ```
+//! use keyberon::layout::Layout;
+//! use keyberon::replay::{parse, replay};
+//!
+//! pub static LAYERS: keyberon::layout::Layers<2, 1, 1> = keyberon::layout::layout! {
+//!     { [ A B ] }
+//! };
+//! const FIXTURE: &str = "
+//! 0 down 0 1
+//! 1 report 00 00 05 00 00 00 00 00
+//! 1 up 0 1
+//! 2 report 00 00 00 00 00 00 00 00
+//! ";
+//!
+//! let mut layout = Layout::new(&LAYERS);
+//! let steps = parse(FIXTURE).map(Result::unwrap);
+//! assert_eq!(Ok(()), replay(&mut layout, steps));
+//! ```
+
+use crate::key_code::{KbHidReport, KeyCode};
+use crate::layout::{Event, Layout};
+
+/// A step of a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// A key event at the given time.
+    Event(u32, Event),
+    /// The expected keyboard report at the given time.
+    Report(u32, [u8; 8]),
+}
+impl Step {
+    /// Returns the time of the step.
+    pub fn time(&self) -> u32 {
+        match *self {
+            Step::Event(time, _) | Step::Report(time, _) => time,
+        }
+    }
+}
+
+/// Error while parsing a fixture, containing the 1-based number of
+/// the invalid line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError(pub usize);
+
+/// A report that doesn't match the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The time of the mismatch.
+    pub time: u32,
+    /// The expected report.
+    pub expected: [u8; 8],
+    /// The report generated by the layout.
+    pub actual: [u8; 8],
+}
+
+/// Parses a fixture, yielding its steps.
+///
+/// See the [module documentation](self) for the format.
+pub fn parse(fixture: &str) -> impl Iterator<Item = Result<Step, ParseError>> + '_ {
+    fixture
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(nb, line)| parse_step(line).ok_or(ParseError(nb)))
+}
+
+fn parse_step(line: &str) -> Option<Step> {
+    let mut fields = line.split_whitespace();
+    let time = fields.next()?.parse().ok()?;
+    let step = match fields.next()? {
+        kind @ ("down" | "up") => {
+            let i = fields.next()?.parse().ok()?;
+            let j = fields.next()?.parse().ok()?;
+            let event = if kind == "down" {
+                Event::Press(i, j)
+            } else {
+                Event::Release(i, j)
+            };
+            Step::Event(time, event)
+        }
+        "report" => {
+            let mut report = [0; 8];
+            for b in &mut report {
+                *b = u8::from_str_radix(fields.next()?, 16).ok()?;
+            }
+            Step::Report(time, report)
+        }
+        _ => return None,
+    };
+    match fields.next() {
+        None => Some(step),
+        Some(_) => None,
+    }
+}
+
+/// Replays the given steps on the layout, checking the expected
+/// reports.
+///
+/// The layout is ticked once per millisecond. Returns the first
+/// mismatching report, if any.
+pub fn replay<const C: usize, const R: usize, const L: usize, T: 'static>(
+    layout: &mut Layout<C, R, L, T, KeyCode>,
+    steps: impl IntoIterator<Item = Step>,
+) -> Result<(), Mismatch> {
+    let mut now = 0;
+    for step in steps {
+        while now < step.time() {
+            layout.tick();
+            now += 1;
+        }
+        match step {
+            Step::Event(_, event) => layout.event(event),
+            Step::Report(time, expected) => {
+                let report: KbHidReport = layout.keycodes().collect();
+                let mut actual = [0; 8];
+                actual.copy_from_slice(report.as_bytes());
+                if actual != expected {
+                    return Err(Mismatch {
+                        time,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::{k, l, Action::*, HoldTapAction, HoldTapConfig};
+    use crate::key_code::KeyCode::*;
+    use crate::layout::Layers;
+
+    static LAYERS: Layers<3, 1, 2> = [
+        [[
+            HoldTap(&HoldTapAction {
+                timeout: 200,
+                hold: k(LCtrl),
+                tap: k(Space),
+                config: HoldTapConfig::Default,
+                tap_hold_interval: 0,
+            }),
+            l(1),
+            k(A),
+        ]],
+        [[Trans, Trans, k(B)]],
+    ];
+
+    #[test]
+    fn hold_tap_layer_fixture() {
+        let fixture = include_str!("../tests/fixtures/hold_tap_layer.txt");
+        let steps = parse(fixture).map(Result::unwrap);
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(Ok(()), replay(&mut layout, steps));
+    }
+
+    #[test]
+    fn mismatch() {
+        let steps = parse("0 down 0 2\n1 report 00 00 05 00 00 00 00 00").map(Result::unwrap);
+        let mut layout = Layout::new(&LAYERS);
+        assert_eq!(
+            Err(Mismatch {
+                time: 1,
+                expected: [0, 0, 5, 0, 0, 0, 0, 0],
+                actual: [0, 0, 4, 0, 0, 0, 0, 0],
+            }),
+            replay(&mut layout, steps)
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        let mut steps = parse("# comment\n\n0 down 0 0\n1 dowm 0 0");
+        assert_eq!(Some(Ok(Step::Event(0, Event::Press(0, 0)))), steps.next());
+        assert_eq!(Some(Err(ParseError(4))), steps.next());
+        assert_eq!(None, steps.next());
+    }
+}
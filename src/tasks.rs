@@ -0,0 +1,168 @@
+//! Async tasks for the scan, debounce and layout pipeline.
+//!
+//! This module is only available with the `async` feature. It
+//! provides the building blocks of a keyboard firmware as futures,
+//! to be spawned by an async executor as
+//! [embassy](https://embassy.dev). The timing is given by an
+//! [`embedded_hal_async::delay::DelayNs`] implementation.
+//!
+//! The events are transmitted from the [`scan_task`] to the
+//! [`layout_task`] with a [`heapless::spsc::Queue`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! static mut QUEUE: Queue<Event, 16> = Queue::new();
+//! let (producer, consumer) = unsafe { QUEUE.split() };
+//! let scan = scan_task(delay1, debouncer, || matrix.get(), producer);
+//! let layout = layout_task(delay2, layout, consumer, |_custom, report| {
+//!     let _ = usb_class.send_keyboard(&report);
+//! });
+//! join(scan, layout).await;
+//! ```
+
+use crate::debounce::Debouncer;
+use crate::key_code::{KbHidReport, KeyCode};
+use crate::layout::{CustomEvent, Event, Keymap, Layout};
+use embedded_hal_async::delay::DelayNs;
+use heapless::spsc::{Consumer, Producer};
+
+/// Scans the key matrix every millisecond, and sends the debounced
+/// events to the queue.
+///
+/// `scan` returns the state of the key matrix, typically using
+/// [`Matrix::get`](crate::matrix::Matrix::get). If the queue is
+/// full, the events are dropped. The task runs until the scan
+/// returns an error, this error being returned.
+pub async fn scan_task<D, S, E, const CS: usize, const RS: usize, const N: usize>(
+    mut delay: D,
+    mut debouncer: Debouncer<[[bool; CS]; RS]>,
+    mut scan: S,
+    mut events: Producer<'_, Event, N>,
+) -> E
+where
+    D: DelayNs,
+    S: FnMut() -> Result<[[bool; CS]; RS], E>,
+{
+    loop {
+        delay.delay_ms(1).await;
+        let keys = match scan() {
+            Ok(keys) => keys,
+            Err(e) => return e,
+        };
+        for event in debouncer.events(keys) {
+            let _ = events.enqueue(event);
+        }
+    }
+}
+
+/// Ticks the layout every millisecond, after registering the
+/// events of the queue.
+///
+/// After each tick, `report` is called with the custom event and the
+/// corresponding keyboard report, the key overrides being applied
+/// (see [`Layout::set_key_overrides`]). It can be used to send the report
+/// and manage the custom actions. This task never ends.
+pub async fn layout_task<
+    D,
+    F,
+    T,
+    M,
+    const C: usize,
+    const R: usize,
+    const L: usize,
+    const N: usize,
+>(
+    mut delay: D,
+    mut layout: Layout<C, R, L, T, KeyCode, M>,
+    mut events: Consumer<'_, Event, N>,
+    mut report: F,
+) -> !
+where
+    D: DelayNs,
+    F: FnMut(CustomEvent<T>, KbHidReport),
+    T: 'static,
+    M: Keymap<T, KeyCode>,
+{
+    loop {
+        delay.delay_ms(1).await;
+        while let Some(event) = events.dequeue() {
+            layout.event(event);
+        }
+        let custom = layout.tick();
+        report(custom, layout.overridden_keycodes().collect());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use crate::key_code::KeyCode::*;
+    use crate::layout::Layers;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use heapless::spsc::Queue;
+    use std::vec::Vec;
+
+    /// A delay yielding once to the executor.
+    struct YieldDelay;
+    impl DelayNs for YieldDelay {
+        async fn delay_ns(&mut self, _ns: u32) {
+            let mut yielded = false;
+            core::future::poll_fn(|_| {
+                if yielded {
+                    Poll::Ready(())
+                } else {
+                    yielded = true;
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+
+    #[test]
+    fn scan_to_report() {
+        static LAYERS: Layers<2, 1, 1> = [[[
+            crate::action::Action::KeyCode(A),
+            crate::action::Action::KeyCode(B),
+        ]]];
+        let mut queue = Queue::<Event, 8>::new();
+        let (producer, consumer) = queue.split();
+
+        let mut scans = 0;
+        let scan = || {
+            scans += 1;
+            match scans {
+                1..=9 => Ok([[false, true]]),
+                10..=19 => Ok([[false, false]]),
+                _ => Err(()),
+            }
+        };
+        let debouncer = Debouncer::new([[false; 2]; 1], [[false; 2]; 1], 2);
+        let mut reports = Vec::new();
+        let layout = Layout::new(&LAYERS);
+
+        {
+            let mut scan = pin!(scan_task(YieldDelay, debouncer, scan, producer));
+            let mut layout = pin!(layout_task(YieldDelay, layout, consumer, |_, r| {
+                reports.push(r)
+            }));
+            let mut cx = Context::from_waker(Waker::noop());
+            while scan.as_mut().poll(&mut cx).is_pending() {
+                assert!(layout.as_mut().poll(&mut cx).is_pending());
+            }
+            assert!(layout.as_mut().poll(&mut cx).is_pending());
+        }
+
+        let b: KbHidReport = [B].iter().copied().collect();
+        let pressed: Vec<_> = reports.iter().map(|r| *r == b).collect();
+        let first = pressed.iter().position(|&p| p).unwrap();
+        let last = pressed.iter().rposition(|&p| p).unwrap();
+        assert_eq!(2, first);
+        assert_eq!(10, last);
+        assert!(pressed[first..=last].iter().all(|&p| p));
+    }
+}